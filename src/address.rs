@@ -0,0 +1,181 @@
+//! Helpers for picking apart address strings such as `Acme <no-reply@acme.com>`.
+
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by the global `--mask-recipients`, unless `--verbose` is given too.
+static MASK: AtomicBool = AtomicBool::new(false);
+
+/// Turns masking on for every later [`preview`] and [`mask_request`].
+pub fn set_masked(masked: bool) {
+    MASK.store(masked, Ordering::Relaxed);
+}
+
+/// Returns the bare address of a mailbox, e.g. `no-reply@acme.com` for
+/// `Acme <no-reply@acme.com>`. Inputs without angle brackets are returned trimmed.
+pub fn mailbox(s: &str) -> &str {
+    let s = s.trim();
+    match (s.rfind('<'), s.rfind('>')) {
+        (Some(start), Some(end)) if start < end => s[start + 1..end].trim(),
+        _ => s,
+    }
+}
+
+/// Returns the domain part of a mailbox, if it has one.
+pub fn domain(s: &str) -> Option<&str> {
+    mailbox(s)
+        .rsplit_once('@')
+        .map(|(_, domain)| domain)
+        .filter(|domain| !domain.is_empty())
+}
+
+/// Whether two domains are the same or one is a subdomain of the other, the
+/// way DMARC's relaxed alignment usually sees them. Case is ignored.
+pub fn domains_aligned(a: &str, b: &str) -> bool {
+    let (a, b) = (a.to_ascii_lowercase(), b.to_ascii_lowercase());
+    a == b || a.ends_with(&format!(".{b}")) || b.ends_with(&format!(".{a}"))
+}
+
+/// Drops repeated addresses, comparing bare mailboxes case-insensitively and
+/// keeping the first occurrence. Returns how many entries were removed.
+pub fn dedupe(addrs: &mut Vec<String>) -> usize {
+    let before = addrs.len();
+    let mut seen = HashSet::with_capacity(before);
+    addrs.retain(|addr| seen.insert(mailbox(addr).to_lowercase()));
+    before - addrs.len()
+}
+
+/// Drops the addresses that are already in `present` (compared like
+/// [`dedupe`]). Returns how many entries were removed.
+pub fn remove_present(addrs: &mut Vec<String>, present: &[String]) -> usize {
+    let present: HashSet<String> = present.iter().map(|a| mailbox(a).to_lowercase()).collect();
+    let before = addrs.len();
+    addrs.retain(|addr| !present.contains(&mailbox(addr).to_lowercase()));
+    before - addrs.len()
+}
+
+/// Loose syntactic check for a bare address: `local@domain.tld`, no spaces.
+///
+/// This only catches obvious typos; the API remains the source of truth.
+pub fn is_valid(addr: &str) -> bool {
+    let Some((local, domain)) = addr.rsplit_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !addr.chars().any(char::is_whitespace)
+        && domain
+            .split_once('.')
+            .is_some_and(|(name, tld)| !name.is_empty() && !tld.is_empty())
+        && !domain.ends_with('.')
+}
+
+/// Hides most of a mailbox: `j***@example.com` for `jane@example.com`, and
+/// `J*** <j***@example.com>` when it has a display name.
+pub fn mask(addr: &str) -> String {
+    let hide = |s: &str| s.chars().next().map_or_else(String::new, |c| format!("{c}***"));
+    let bare = mailbox(addr);
+    let masked = match bare.rsplit_once('@') {
+        Some((local, domain)) => format!("{}@{domain}", hide(local)),
+        None => hide(bare),
+    };
+    let name = addr.trim().strip_suffix(&format!("<{bare}>")).map(str::trim);
+    match name.map(|n| n.trim_matches('"')).filter(|n| !n.is_empty()) {
+        Some(name) => format!("{} <{masked}>", hide(name)),
+        None => masked,
+    }
+}
+
+/// Recipients as confirmations and dry runs show them: comma-separated, and
+/// masked with `--mask-recipients`.
+pub fn preview<'a>(addrs: impl IntoIterator<Item = &'a str>) -> String {
+    let masked = MASK.load(Ordering::Relaxed);
+    let shown: Vec<String> = addrs
+        .into_iter()
+        .map(|addr| if masked { mask(addr) } else { addr.trim().to_string() })
+        .collect();
+    shown.join(", ")
+}
+
+/// With `--mask-recipients`, masks the `to`, `cc`, `bcc` and `reply_to` of a
+/// request body, or of each body in an array, before it is printed.
+pub fn mask_request(body: &mut Value) {
+    if !MASK.load(Ordering::Relaxed) {
+        return;
+    }
+    match body {
+        Value::Array(bodies) => bodies.iter_mut().for_each(mask_request),
+        Value::Object(fields) => {
+            for name in ["to", "cc", "bcc", "reply_to"] {
+                match fields.get_mut(name) {
+                    Some(Value::String(addr)) => *addr = mask(addr),
+                    Some(Value::Array(addrs)) => {
+                        for addr in addrs {
+                            if let Value::String(s) = addr {
+                                *s = mask(s);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Builds a `Name <email>` mailbox, quoting the display name when it contains
+/// characters that are special in a header (commas, quotes, brackets, ...).
+pub fn format_mailbox(name: &str, email: &str) -> String {
+    let name = name.trim();
+    if name.is_empty() {
+        return email.to_string();
+    }
+    let special = |c: char| "()<>[]:;@\\,.\"".contains(c) || c.is_control();
+    if name.chars().any(special) {
+        let escaped = name.replace('\\', "\\\\").replace('"', "\\\"");
+        format!("\"{escaped}\" <{email}>")
+    } else {
+        format!("{name} <{email}>")
+    }
+}
+
+/// Reads one address per line from `path`. Blank lines and lines starting
+/// with `#` are skipped; anything else must look like an address.
+pub fn read_list(path: &Path) -> Result<Vec<String>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("read address file {}", path.display()))?;
+    let mut addrs = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if !is_valid(mailbox(line)) {
+            bail!("{}:{}: invalid address {line:?}", path.display(), i + 1);
+        }
+        addrs.push(line.to_string());
+    }
+    Ok(addrs)
+}
+
+/// Addresses to copy on every message, from `RUSEND_ALWAYS_CC` or
+/// `RUSEND_ALWAYS_BCC` (comma separated). Unset or empty means none.
+pub fn always(var: &str) -> Result<Vec<String>> {
+    let Ok(value) = std::env::var(var) else {
+        return Ok(Vec::new());
+    };
+    let addrs: Vec<String> = value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect();
+    if let Some(bad) = addrs.iter().find(|a| !is_valid(mailbox(a))) {
+        bail!("{var} contains an invalid address {bad:?}");
+    }
+    Ok(addrs)
+}
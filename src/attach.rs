@@ -0,0 +1,300 @@
+use anyhow::{Context, Result, bail};
+use resend_rs::types::CreateAttachment;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Resend's limit on the combined size of an email's attachments.
+pub const MAX_TOTAL_BYTES: u64 = 40 * 1024 * 1024;
+
+/// An `--attach` argument: a file plus an optional explicit content type.
+#[derive(Clone, Debug)]
+pub struct AttachSpec {
+    path: PathBuf,
+    content_type: Option<String>,
+}
+
+/// Parses `PATH` or `PATH:TYPE/SUBTYPE`. A path that exists as written is
+/// never split, so files with a colon in their name still work.
+pub fn parse_spec(s: &str) -> Result<AttachSpec, String> {
+    if !Path::new(s).exists()
+        && let Some((path, ty)) = s.rsplit_once(':')
+        && !path.is_empty()
+        && is_mime(ty)
+    {
+        return Ok(AttachSpec {
+            path: path.into(),
+            content_type: Some(ty.to_string()),
+        });
+    }
+    Ok(AttachSpec {
+        path: s.into(),
+        content_type: None,
+    })
+}
+
+fn is_mime(s: &str) -> bool {
+    let valid = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$&^_.+-".contains(c))
+    };
+    matches!(s.split_once('/'), Some((ty, sub)) if valid(ty) && valid(sub))
+}
+
+impl From<PathBuf> for AttachSpec {
+    fn from(path: PathBuf) -> Self {
+        Self {
+            path,
+            content_type: None,
+        }
+    }
+}
+
+impl AttachSpec {
+    /// Reads the file. Without an explicit type one is guessed from the
+    /// extension, falling back to `application/octet-stream`.
+    pub fn load(&self) -> Result<CreateAttachment> {
+        from_file(&self.path, self.content_type.as_deref())
+    }
+}
+
+fn from_file(path: &Path, content_type: Option<&str>) -> Result<CreateAttachment> {
+    let content =
+        fs::read(path).with_context(|| format!("read attachment {}", path.display()))?;
+    let content_type = content_type.map_or_else(
+        || mime_guess::from_path(path).first_or_octet_stream().to_string(),
+        String::from,
+    );
+    Ok(CreateAttachment::from_content(content)
+        .with_filename(&file_name(path))
+        .with_content_type(&content_type))
+}
+
+/// Reads all of stdin as an attachment named `filename`, whose extension
+/// decides the content type. Returns it with its size in bytes.
+pub fn from_stdin(filename: &str) -> Result<(CreateAttachment, u64)> {
+    let mut content = Vec::new();
+    io::stdin()
+        .read_to_end(&mut content)
+        .context("read attachment from stdin")?;
+    if content.is_empty() {
+        bail!("--attach-stdin got no data on stdin");
+    }
+    let size = content.len() as u64;
+    let content_type = mime_guess::from_path(filename).first_or_octet_stream().to_string();
+    let attachment = CreateAttachment::from_content(content)
+        .with_filename(filename)
+        .with_content_type(&content_type);
+    Ok((attachment, size))
+}
+
+/// The files in `dir`, sorted by path. Hidden entries (names starting with
+/// `.`) are skipped, and subdirectories are only entered when `recursive`.
+/// Symlinks to files are followed, symlinks to directories are not, since one
+/// pointing at an ancestor would make the walk endless.
+pub fn dir_files(dir: &Path, recursive: bool) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let entries =
+        fs::read_dir(dir).with_context(|| format!("read attachments dir {}", dir.display()))?;
+    for entry in entries {
+        let path = entry
+            .with_context(|| format!("read attachments dir {}", dir.display()))?
+            .path();
+        if file_name(&path).starts_with('.') {
+            continue;
+        }
+        let meta = fs::metadata(&path).with_context(|| format!("stat {}", path.display()))?;
+        if meta.is_dir() {
+            let link = fs::symlink_metadata(&path)
+                .with_context(|| format!("stat {}", path.display()))?
+                .is_symlink();
+            if recursive && !link {
+                files.extend(dir_files(&path, true)?);
+            }
+        } else if meta.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The files matching a `--attach-glob` pattern, sorted by path. Directories
+/// that match are left out.
+pub fn glob_files(pattern: &str) -> Result<Vec<PathBuf>> {
+    let paths = glob::glob(pattern).with_context(|| format!("invalid glob {pattern:?}"))?;
+    let mut files = Vec::new();
+    for path in paths {
+        let path = path.with_context(|| format!("expand glob {pattern:?}"))?;
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Fails if the attachments together exceed [`MAX_TOTAL_BYTES`], counting
+/// `extra` bytes of attachments read separately (`--attach-stdin`, `--ics`,
+/// inline images).
+/// Returns the total.
+pub fn check_total_size(specs: &[AttachSpec], extra: u64) -> Result<u64> {
+    let total = extra + total_size(specs)?;
+    if total > MAX_TOTAL_BYTES {
+        bail!(
+            "attachments total {:.1} MB, over the {} MB limit",
+            total as f64 / (1024.0 * 1024.0),
+            MAX_TOTAL_BYTES / (1024 * 1024)
+        );
+    }
+    Ok(total)
+}
+
+/// The combined size of the files in `specs`, in bytes.
+pub fn total_size(specs: &[AttachSpec]) -> Result<u64> {
+    let mut total = 0;
+    for spec in specs {
+        total += fs::metadata(&spec.path)
+            .with_context(|| format!("read attachment {}", spec.path.display()))?
+            .len();
+    }
+    Ok(total)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Local images found in an HTML body, with the body rewritten to refer to them.
+pub struct Inlined {
+    pub html: String,
+    pub attachments: Vec<CreateAttachment>,
+    /// The `src` values that were embedded, in order of first use.
+    pub embedded: Vec<String>,
+    /// The combined size of the embedded files, in bytes.
+    pub bytes: u64,
+}
+
+/// Attaches every local file referenced by an `<img src>` in `html` as an
+/// inline attachment and points the `src` at its `cid:`.
+///
+/// Remote (`http:`, `https:`, `//`), `data:` and `cid:` sources are left alone.
+/// Relative paths are resolved against `base`; a missing file is an error.
+pub fn inline_images(html: &str, base: &Path) -> Result<Inlined> {
+    let lower = html.to_ascii_lowercase();
+    let mut out = String::with_capacity(html.len());
+    let mut attachments = Vec::new();
+    let mut embedded: Vec<String> = Vec::new();
+    let mut cids: Vec<String> = Vec::new();
+    let mut bytes = 0;
+    let mut pos = 0;
+
+    while let Some(found) = lower[pos..].find("<img") {
+        let tag_start = pos + found;
+        let tag_end = tag_end(&lower, tag_start);
+        let Some((src_start, src_end)) = src_value(&lower, tag_start, tag_end) else {
+            out.push_str(&html[pos..tag_end]);
+            pos = tag_end;
+            continue;
+        };
+        let src = &html[src_start..src_end];
+        out.push_str(&html[pos..src_start]);
+        if is_local(src) {
+            let n = match embedded.iter().position(|e| e == src) {
+                Some(n) => n,
+                None => {
+                    let path = base.join(src.strip_prefix("file://").unwrap_or(src));
+                    let cid = content_id(cids.len(), &path);
+                    let attachment = from_file(&path, None)?;
+                    bytes += fs::metadata(&path)
+                        .with_context(|| format!("read attachment {}", path.display()))?
+                        .len();
+                    attachments.push(attachment.with_content_id(&cid));
+                    embedded.push(src.to_string());
+                    cids.push(cid);
+                    cids.len() - 1
+                }
+            };
+            out.push_str("cid:");
+            out.push_str(&cids[n]);
+        } else {
+            out.push_str(src);
+        }
+        pos = src_end;
+    }
+    out.push_str(&html[pos..]);
+
+    Ok(Inlined {
+        html: out,
+        attachments,
+        embedded,
+        bytes,
+    })
+}
+
+/// Index of the `>` closing the tag at `start`, ignoring any inside quotes.
+fn tag_end(lower: &str, start: usize) -> usize {
+    let mut quote = None;
+    for (i, b) in lower.bytes().enumerate().skip(start) {
+        match (quote, b) {
+            (None, b'"' | b'\'') => quote = Some(b),
+            (Some(q), _) if b == q => quote = None,
+            (None, b'>') => return i,
+            _ => {}
+        }
+    }
+    lower.len()
+}
+
+/// Byte range of the quoted `src` attribute value within an `<img ...>` tag.
+fn src_value(lower: &str, tag_start: usize, tag_end: usize) -> Option<(usize, usize)> {
+    let bytes = lower.as_bytes();
+    let skip_ws = |mut i: usize| {
+        while i < tag_end && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        i
+    };
+    let mut from = tag_start;
+    while let Some(found) = lower[from..tag_end].find("src") {
+        let at = from + found;
+        from = at + 3;
+        // Must be a whole attribute name, not e.g. `data-src`.
+        if !bytes[at - 1].is_ascii_whitespace() {
+            continue;
+        }
+        let eq = skip_ws(from);
+        if eq >= tag_end || bytes[eq] != b'=' {
+            continue;
+        }
+        let open = skip_ws(eq + 1);
+        let quote = *bytes[..tag_end].get(open).filter(|b| matches!(b, b'"' | b'\''))?;
+        let start = open + 1;
+        let len = lower[start..tag_end].find(char::from(quote))?;
+        return Some((start, start + len));
+    }
+    None
+}
+
+fn is_local(src: &str) -> bool {
+    let lower = src.trim().to_ascii_lowercase();
+    !(lower.is_empty()
+        || lower.starts_with("http:")
+        || lower.starts_with("https:")
+        || lower.starts_with("//")
+        || lower.starts_with("data:")
+        || lower.starts_with("cid:"))
+}
+
+/// A content ID unique within the message, e.g. `img0-logo.png`.
+fn content_id(n: usize, path: &Path) -> String {
+    let name: String = file_name(path)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .collect();
+    format!("img{n}-{name}")
+}
@@ -0,0 +1,52 @@
+use anyhow::{Context, Result};
+use resend_rs::types::InboundEmail;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::output::InboundEmailView;
+
+/// `--json-lines` of the received commands: a JSON object per email handled,
+/// appended to a file (or FIFO) for a downstream process, whatever `--output` says.
+pub struct JsonLines {
+    file: File,
+    path: PathBuf,
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    /// Milliseconds since the Unix epoch when the email was handled.
+    ts_ms: u128,
+    /// `received-list` or `received-get`.
+    command: &'static str,
+    email: InboundEmailView<'a>,
+}
+
+impl JsonLines {
+    /// Opens `path` for appending, creating it if needed.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("open --json-lines file {}", path.display()))?;
+        Ok(Self { file, path: path.to_path_buf() })
+    }
+
+    /// Appends `email`, flushed at once so a reader sees it right away.
+    pub fn write(&mut self, command: &'static str, email: &InboundEmail) -> Result<()> {
+        let ts_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        let line = Line { ts_ms, command, email: InboundEmailView::from(email) };
+        let mut json = serde_json::to_string(&line)?;
+        json.push('\n');
+        self.file
+            .write_all(json.as_bytes())
+            .and_then(|()| self.file.flush())
+            .with_context(|| format!("write --json-lines file {}", self.path.display()))
+    }
+}
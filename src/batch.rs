@@ -0,0 +1,1059 @@
+use anyhow::{Context, Result, bail};
+use chrono::{SecondsFormat, TimeDelta, Utc};
+use clap::ValueEnum;
+use resend_rs::types::{BatchValidation, CreateAttachment, CreateEmailBaseOptions};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::address;
+use crate::attach::{self, AttachSpec};
+use crate::client::Api;
+use crate::domains::DomainPreflight;
+use crate::output::Output;
+use crate::template;
+use crate::throttle::Throttle;
+
+/// Most messages the batch endpoint accepts in one request.
+pub const MAX_BATCH_SIZE: usize = 100;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BatchEmailInput {
+    /// May be omitted when `--from-pool` supplies it.
+    #[serde(default)]
+    pub from: String,
+    pub to: Vec<String>,
+    #[serde(default)]
+    pub cc: Vec<String>,
+    #[serde(default)]
+    pub bcc: Vec<String>,
+    /// May be omitted, like the bodies, when [`Defaults`] supplies it.
+    #[serde(default)]
+    pub subject: String,
+    pub html: Option<String>,
+    pub text: Option<String>,
+    /// Files to attach, as `send --attach` takes them (`PATH` or `PATH:TYPE/SUBTYPE`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachments: Option<Vec<String>>,
+}
+
+impl BatchEmailInput {
+    fn attach_specs(&self) -> Result<Vec<AttachSpec>> {
+        self.attachments
+            .iter()
+            .flatten()
+            .map(|s| attach::parse_spec(s).map_err(anyhow::Error::msg))
+            .collect()
+    }
+
+    /// Whether the message lists attachments, which the batch endpoint doesn't
+    /// take: such a message goes out on its own through `POST /emails`.
+    pub fn has_attachments(&self) -> bool {
+        self.attachments.as_ref().is_some_and(|a| !a.is_empty())
+    }
+
+    /// Fails if the request carrying the message, its bodies and attachments
+    /// together, would be larger than `limit` bytes (`--attach-max-total`).
+    pub fn check_request_size(&self, limit: u64) -> Result<()> {
+        if !self.has_attachments() {
+            return Ok(());
+        }
+        let body = self.html.as_deref().map_or(0, str::len)
+            + self.text.as_deref().map_or(0, str::len);
+        let total = attach::total_size(&self.attach_specs()?)? + body as u64;
+        if total > limit {
+            let mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+            bail!(
+                "the message and its attachments total {:.1} MB, over the {:.1} MB limit \
+                 (--attach-max-total)",
+                mb(total),
+                mb(limit)
+            );
+        }
+        Ok(())
+    }
+
+    /// Reads the message's attachments from disk.
+    pub fn load_attachments(&self) -> Result<Vec<CreateAttachment>> {
+        self.attach_specs()?.iter().map(AttachSpec::load).collect()
+    }
+}
+
+/// The subject and bodies given by `--default-subject`, `--default-html-file`
+/// and `--default-text-file`, for messages that leave them out.
+#[derive(Default)]
+pub struct Defaults {
+    pub subject: Option<String>,
+    pub html: Option<String>,
+    pub text: Option<String>,
+}
+
+impl Defaults {
+    /// Fills in each of the subject, HTML and text that `message` doesn't set;
+    /// the message's own values always win.
+    pub fn apply(&self, message: &mut BatchEmailInput) {
+        if message.subject.trim().is_empty()
+            && let Some(subject) = &self.subject
+        {
+            message.subject = subject.clone();
+        }
+        if message.html.is_none() {
+            message.html = self.html.clone();
+        }
+        if message.text.is_none() {
+            message.text = self.text.clone();
+        }
+    }
+}
+
+/// Applies `defaults` to every message, then fails listing each message that
+/// still has no subject or no body.
+pub fn apply_defaults(batch: &mut [BatchEmailInput], defaults: &Defaults) -> Result<()> {
+    let mut problems = Vec::new();
+    for (i, b) in batch.iter_mut().enumerate() {
+        defaults.apply(b);
+        problems.extend(missing_content(b).map(|p| format!("message {}: {p}", i + 1)));
+    }
+    if !problems.is_empty() {
+        bail!(
+            "{} problem(s) after applying the defaults:\n  {}",
+            problems.len(),
+            problems.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+/// Drops blank entries from every message's recipient lists, then fails,
+/// listing each message left without a To address, before anything is sent.
+pub fn require_recipients(batch: &mut [BatchEmailInput]) -> Result<()> {
+    let mut empty = Vec::new();
+    for (i, b) in batch.iter_mut().enumerate() {
+        for addrs in [&mut b.to, &mut b.cc, &mut b.bcc] {
+            addrs.retain(|a| !a.trim().is_empty());
+        }
+        if b.to.is_empty() {
+            empty.push((i + 1).to_string());
+        }
+    }
+    if !empty.is_empty() {
+        bail!("no valid recipients in message(s) {}: `to` is empty", empty.join(", "));
+    }
+    Ok(())
+}
+
+/// `--global-dedupe`: removes every recipient (To, Cc or Bcc) that an earlier
+/// message already has, compared like [`address::dedupe`], and drops messages
+/// left without a To address. Returns the recipients and messages removed.
+pub fn dedupe_across(batch: &mut Vec<BatchEmailInput>) -> (usize, usize) {
+    let mut seen = HashSet::new();
+    let mut recipients = 0;
+    for b in batch.iter_mut() {
+        let mut own = HashSet::new();
+        for addrs in [&mut b.to, &mut b.cc, &mut b.bcc] {
+            let before = addrs.len();
+            addrs.retain(|addr| {
+                let key = address::mailbox(addr).to_lowercase();
+                !seen.contains(&key) && own.insert(key)
+            });
+            recipients += before - addrs.len();
+        }
+        seen.extend(own);
+    }
+    let before = batch.len();
+    batch.retain(|b| !b.to.is_empty());
+    (recipients, before - batch.len())
+}
+
+/// The messages `--max-per-domain` held back, and how many emails each
+/// recipient domain would have gone over by.
+pub struct Overflow {
+    pub held: Vec<BatchEmailInput>,
+    pub domains: BTreeMap<String, usize>,
+}
+
+/// `--max-per-domain`: keeps messages, in order, while every recipient domain
+/// stays within `max` emails, and holds back each message that would take one
+/// of its domains past it. Every To, Cc and Bcc address counts toward its domain.
+pub fn cap_per_domain(batch: &mut Vec<BatchEmailInput>, max: usize) -> Overflow {
+    let mut sent: HashMap<String, usize> = HashMap::new();
+    let mut overflow = Overflow { held: Vec::new(), domains: BTreeMap::new() };
+    for b in std::mem::take(batch) {
+        let mut wanted: HashMap<String, usize> = HashMap::new();
+        for addr in b.to.iter().chain(&b.cc).chain(&b.bcc) {
+            let domain = address::domain(addr).unwrap_or_default().to_ascii_lowercase();
+            *wanted.entry(domain).or_default() += 1;
+        }
+        let over: Vec<_> = wanted
+            .iter()
+            .filter(|(domain, n)| sent.get(*domain).copied().unwrap_or(0) + **n > max)
+            .collect();
+        if over.is_empty() {
+            for (domain, n) in wanted {
+                *sent.entry(domain).or_default() += n;
+            }
+            batch.push(b);
+        } else {
+            for (domain, n) in over {
+                *overflow.domains.entry(domain.clone()).or_default() += n;
+            }
+            overflow.held.push(b);
+        }
+    }
+    overflow
+}
+
+/// What a message needs but lacks: a subject and at least one body.
+fn missing_content(b: &BatchEmailInput) -> impl Iterator<Item = &'static str> {
+    let no_subject = b.subject.trim().is_empty();
+    let no_body = b.html.is_none() && b.text.is_none();
+    [
+        no_subject.then_some("empty subject"),
+        no_body.then_some("no html or text body"),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+/// Layout of a batch input file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum BatchFormat {
+    /// A single JSON array of messages
+    Json,
+    /// One JSON message object per line
+    Ndjson,
+}
+
+impl BatchFormat {
+    /// Guesses the format from the file extension, defaulting to JSON.
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("ndjson" | "jsonl") => Self::Ndjson,
+            _ => Self::Json,
+        }
+    }
+}
+
+/// Puts `items` in a random order that only depends on `seed` (a Fisher-Yates
+/// shuffle driven by SplitMix64, so a seed gives the same order on every
+/// platform and release).
+pub fn shuffle<T>(items: &mut [T], seed: u64) {
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+    for i in (1..items.len()).rev() {
+        let j = (next() % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+}
+
+/// Reads batch messages from `path`, or from stdin when `path` is `-`.
+///
+/// Without an explicit `format` the extension decides; stdin defaults to JSON.
+pub fn read_input(path: &Path, format: Option<BatchFormat>) -> Result<Vec<BatchEmailInput>> {
+    let (content, format) = read_content(path, format)?;
+    match format {
+        BatchFormat::Json => serde_json::from_str(&content).context("parse json"),
+        BatchFormat::Ndjson => content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line).with_context(|| format!("parse ndjson line {}", i + 1))
+            })
+            .collect(),
+    }
+}
+
+/// A batch message as read for `--validate-only`, or why it couldn't be read.
+pub struct Entry {
+    /// Where the message came from: `message 3`, `line 3` or `row 3`.
+    pub label: String,
+    pub message: Result<BatchEmailInput, String>,
+}
+
+/// Like [`read_input`], but a message that doesn't parse is kept as an error
+/// instead of failing the read. Only a JSON file that isn't an array fails.
+pub fn read_entries(path: &Path, format: Option<BatchFormat>) -> Result<Vec<Entry>> {
+    let (content, format) = read_content(path, format)?;
+    let entry = |label: String, parsed: serde_json::Result<BatchEmailInput>| Entry {
+        label,
+        message: parsed.map_err(|e| e.to_string()),
+    };
+    Ok(match format {
+        BatchFormat::Json => {
+            let values: Vec<serde_json::Value> =
+                serde_json::from_str(&content).context("parse json")?;
+            values
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| entry(format!("message {}", i + 1), serde_json::from_value(v)))
+                .collect()
+        }
+        BatchFormat::Ndjson => content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| entry(format!("line {}", i + 1), serde_json::from_str(line)))
+            .collect(),
+    })
+}
+
+fn read_content(path: &Path, format: Option<BatchFormat>) -> Result<(String, BatchFormat)> {
+    if path.as_os_str() == "-" {
+        let mut s = String::new();
+        io::stdin()
+            .read_to_string(&mut s)
+            .context("read batch from stdin")?;
+        Ok((s, format.unwrap_or(BatchFormat::Json)))
+    } else {
+        let s = fs::read_to_string(path).context("read batch file")?;
+        Ok((s, format.unwrap_or_else(|| BatchFormat::from_path(path))))
+    }
+}
+
+/// Builds one message per row of the CSV at `data` by rendering `template`
+/// (HTML) and `subject` with that row's columns.
+///
+/// The header row names the variables; a `to` column is required and a `from`
+/// column is used when present. Without a `subject` template the row's own
+/// `subject` column is taken. Rows are numbered as in a spreadsheet (the header
+/// is row 1), and every row missing a variable is reported before failing.
+pub fn read_merge(
+    template: &Path,
+    data: &Path,
+    subject: Option<&str>,
+) -> Result<Vec<BatchEmailInput>> {
+    let mut batch = Vec::new();
+    let mut problems = Vec::new();
+    for entry in merge_entries(template, data, subject)? {
+        match entry.message {
+            Ok(b) => batch.push(b),
+            Err(e) => problems.push(format!("{}: {e}", entry.label)),
+        }
+    }
+
+    if !problems.is_empty() {
+        bail!(
+            "{} row(s) lack template variables:\n  {}",
+            problems.len(),
+            problems.join("\n  ")
+        );
+    }
+    Ok(batch)
+}
+
+/// The rows behind [`read_merge`], with a row that lacks variables kept as an
+/// error naming them.
+pub fn merge_entries(template: &Path, data: &Path, subject: Option<&str>) -> Result<Vec<Entry>> {
+    let html = fs::read_to_string(template).context("read template file")?;
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_path(data)
+        .context("open data file")?;
+    let headers = reader.headers().context("read data header")?.clone();
+    if !headers.iter().any(|h| h == "to") {
+        bail!("data file has no `to` column");
+    }
+    if subject.is_none() && !headers.iter().any(|h| h == "subject") {
+        bail!("no --subject given and the data file has no `subject` column");
+    }
+
+    let mut entries = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let row = i + 2;
+        let record = record.with_context(|| format!("read data row {row}"))?;
+        let vars: HashMap<String, String> = headers
+            .iter()
+            .zip(record.iter())
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let var = |name: &str| vars.get(name).cloned().unwrap_or_default();
+
+        let subject = match subject {
+            Some(t) => template::render(t, &vars, false),
+            None => Ok(var("subject")),
+        };
+        let body = template::render(&html, &vars, true);
+        let to: Vec<String> = var("to")
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect();
+
+        let mut missing = Vec::new();
+        if to.is_empty() {
+            missing.push("to".to_string());
+        }
+        let message = match (subject, body) {
+            (Ok(subject), Ok(body)) if missing.is_empty() => Ok(BatchEmailInput {
+                from: var("from"),
+                to,
+                cc: Vec::new(),
+                bcc: Vec::new(),
+                subject,
+                html: Some(body),
+                text: None,
+                attachments: None,
+            }),
+            (subject, body) => {
+                let vars = subject.err().into_iter().chain(body.err()).flatten();
+                for name in vars {
+                    if !missing.contains(&name) {
+                        missing.push(name);
+                    }
+                }
+                Err(format!("missing {}", missing.join(", ")))
+            }
+        };
+        entries.push(Entry {
+            label: format!("row {row}"),
+            message,
+        });
+    }
+    Ok(entries)
+}
+
+/// Fills in the From of messages that don't set one, cycling through `pool`.
+///
+/// A message's own `from` always wins. Every pool entry is validated before
+/// anything is assigned, and a message left without a From is an error.
+pub fn apply_from_pool(batch: &mut [BatchEmailInput], pool: &[String]) -> Result<()> {
+    let invalid: Vec<_> = pool
+        .iter()
+        .filter(|from| !address::is_valid(address::mailbox(from)))
+        .map(String::as_str)
+        .collect();
+    if !invalid.is_empty() {
+        bail!("invalid --from-pool address(es): {}", invalid.join(", "));
+    }
+
+    let mut next = pool.iter().cycle();
+    for (i, b) in batch.iter_mut().enumerate() {
+        if !b.from.trim().is_empty() {
+            continue;
+        }
+        match next.next() {
+            Some(from) => b.from = from.clone(),
+            None => bail!("message {} has no from and no --from-pool was given", i + 1),
+        }
+    }
+    Ok(())
+}
+
+/// Runs every preflight check on `entries` without sending anything.
+///
+/// Covers parsing and template variables, From pool assignment, address syntax
+/// and whether each From domain is verified. Every problem is returned,
+/// prefixed with the label of its message.
+pub async fn validate(
+    api: &Api,
+    entries: Vec<Entry>,
+    pool: &[String],
+    defaults: &Defaults,
+    attach_max_total: u64,
+) -> Result<Vec<String>> {
+    let mut problems = Vec::new();
+    let invalid_pool: Vec<_> = pool
+        .iter()
+        .filter(|from| !address::is_valid(address::mailbox(from)))
+        .map(String::as_str)
+        .collect();
+    if !invalid_pool.is_empty() {
+        problems.push(format!("invalid --from-pool address(es): {}", invalid_pool.join(", ")));
+    }
+
+    let mut next = pool.iter().cycle();
+    let mut domains = DomainPreflight::default();
+    for entry in entries {
+        let mut b = match entry.message {
+            Ok(b) => b,
+            Err(e) => {
+                problems.push(format!("{}: {e}", entry.label));
+                continue;
+            }
+        };
+        defaults.apply(&mut b);
+        let mut found = Vec::new();
+        if b.from.trim().is_empty() {
+            match next.next() {
+                Some(from) => b.from = from.clone(),
+                None => found.push("no from and no --from-pool was given".to_string()),
+            }
+        }
+        if !b.from.trim().is_empty() {
+            if !address::is_valid(address::mailbox(&b.from)) {
+                found.push(format!("invalid from address {:?}", b.from));
+            } else if let Some(problem) = domains.problem(api, &b.from).await? {
+                found.push(problem);
+            }
+        }
+        b.to.retain(|a| !a.trim().is_empty());
+        if b.to.is_empty() {
+            found.push("no to address".to_string());
+        }
+        for (field, addrs) in [("to", &b.to), ("cc", &b.cc), ("bcc", &b.bcc)] {
+            for addr in addrs.iter().filter(|a| !address::is_valid(address::mailbox(a))) {
+                found.push(format!("invalid {field} address {addr:?}"));
+            }
+        }
+        found.extend(missing_content(&b).map(String::from));
+        if let Err(e) = b.check_request_size(attach_max_total) {
+            found.push(format!("{e:#}"));
+        }
+        problems.extend(found.into_iter().map(|p| format!("{}: {p}", entry.label)));
+    }
+    Ok(problems)
+}
+
+/// What [`send_individually`] managed to send.
+pub struct Individual {
+    /// Recipients that were sent to, with the id of their email.
+    pub sent: Vec<(String, String)>,
+    pub failed: usize,
+}
+
+impl Individual {
+    /// Fails the command if any message could not be sent.
+    pub fn check(&self) -> Result<()> {
+        if self.failed > 0 {
+            bail!("{} message(s) could not be sent", self.failed);
+        }
+        Ok(())
+    }
+}
+
+/// Sends one message per recipient through the batch endpoint.
+///
+/// `emails[i]` must be addressed to `recipients[i]`. Permissive validation is
+/// used so a bad address only fails its own message; failures are reported
+/// per recipient, and [`Individual::check`] turns them into an error.
+///
+/// With `attached`, the messages carry attachments, which the batch endpoint
+/// doesn't take, so each goes in a `POST /emails` request of its own.
+pub async fn send_individually(
+    api: &Api,
+    out: Output,
+    recipients: &[String],
+    emails: Vec<CreateEmailBaseOptions>,
+    attached: bool,
+) -> Result<Individual> {
+    let mut sent = Vec::new();
+    let mut failed = 0;
+    let mut emails = emails.into_iter();
+    for chunk in recipients.chunks(MAX_BATCH_SIZE) {
+        let messages: Vec<_> = emails.by_ref().take(chunk.len()).collect();
+        if attached {
+            for (to, email) in chunk.iter().zip(messages) {
+                match api.send_email(&email, None).await {
+                    Ok((res, _)) => sent.push((to.clone(), res.id.to_string())),
+                    Err(e) => {
+                        eprintln!("failed {to}: {e}");
+                        failed += 1;
+                    }
+                }
+            }
+            continue;
+        }
+        let res = api
+            .send_batch(&messages, BatchValidation::Permissive)
+            .await
+            .context("batch send failed")?;
+        let mut failed_indexes = Vec::new();
+        for err in &res.errors {
+            let index = usize::try_from(err.index).ok();
+            let to = index.and_then(|i| chunk.get(i)).map_or("<unknown>", String::as_str);
+            eprintln!("failed {to}: {}", err.message);
+            failed_indexes.extend(index);
+        }
+        // Ids come back in order for the messages that were accepted.
+        let accepted = chunk
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !failed_indexes.contains(i))
+            .map(|(_, to)| to.clone());
+        sent.extend(accepted.zip(res.data.iter().map(|r| r.id.to_string())));
+        failed += res.errors.len();
+    }
+
+    if out.is_json() {
+        let sent: Vec<_> =
+            sent.iter().map(|(to, id)| serde_json::json!({ "to": to, "id": id })).collect();
+        out.print_json(&serde_json::json!({ "sent": sent, "failed": failed }))?;
+    } else {
+        println!("Sent {} individual message(s), {failed} failed.", sent.len());
+    }
+    Ok(Individual { sent, failed })
+}
+
+/// End-of-run figures for a chunked batch send.
+#[derive(Serialize)]
+struct BatchMetrics {
+    total: usize,
+    /// Messages left out at the start with `--continue-from`.
+    skipped: usize,
+    attempted: usize,
+    succeeded: usize,
+    failed: usize,
+    not_attempted: usize,
+    requests: usize,
+    elapsed_secs: f64,
+    /// Attempted messages per second of wall-clock time.
+    per_sec: f64,
+    /// How long each batch request took; absent when none was made.
+    latency: Option<Latency>,
+    /// The `--continue-from` that picks up where a `--fail-fast` run stopped.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resume_from: Option<usize>,
+    /// The ids of the sent emails, in batch order.
+    ids: Vec<String>,
+}
+
+/// Nearest-rank percentiles of the request durations, in milliseconds.
+/// A request's duration includes any retries [`Api::call`] made.
+#[derive(Serialize)]
+struct Latency {
+    p50_ms: f64,
+    p95_ms: f64,
+    p99_ms: f64,
+}
+
+impl Latency {
+    fn from_durations(mut durations: Vec<Duration>) -> Option<Self> {
+        if durations.is_empty() {
+            return None;
+        }
+        durations.sort();
+        let rank = |p: f64| {
+            let i = ((p / 100.0 * durations.len() as f64).ceil() as usize).max(1) - 1;
+            durations[i].as_secs_f64() * 1000.0
+        };
+        Some(Self {
+            p50_ms: rank(50.0),
+            p95_ms: rank(95.0),
+            p99_ms: rank(99.0),
+        })
+    }
+}
+
+/// How [`send_chunked`] splits, paces and reports a batch.
+pub struct ChunkOptions {
+    /// Messages per request, at most [`MAX_BATCH_SIZE`].
+    pub chunk_size: usize,
+    /// Average emails per second to stay under.
+    pub rate: Option<f64>,
+    /// Stop at the first failed chunk.
+    pub fail_fast: bool,
+    /// Print only the failed messages as JSON on stdout; the summary goes to stderr.
+    pub errors_only: bool,
+    /// Write a [`Report`] of every message here after the run.
+    pub report: Option<PathBuf>,
+    /// `--summary-only`: no line per failed chunk, just the summary at the end.
+    pub summary_only: bool,
+    /// Write the failed messages here (as `--json-errors-only` prints them).
+    pub failures_file: Option<PathBuf>,
+    /// `--concurrency-auto`: send several chunks at once, tuning how many up
+    /// to this cap; otherwise one request is in flight at a time.
+    pub max_concurrency: Option<usize>,
+    /// `--continue-from`: how many messages at the start of the batch were
+    /// skipped. Reported positions still count from the start of the batch.
+    pub first_index: usize,
+    /// Positions in the emails sent of those with attachments. The batch
+    /// endpoint doesn't take attachments, so each goes in a `POST /emails`
+    /// request of its own, counted as a chunk of one.
+    pub attached: HashSet<usize>,
+}
+
+/// How many chunks `--concurrency-auto` sends at once.
+///
+/// Starts at one and grows by one after every wave of requests that all
+/// succeeded without slowing down: no slower than twice the fastest wave so
+/// far, or a quarter second, whichever is longer. A failed wave, rate limits
+/// included, halves it; a slow one takes one away.
+struct Concurrency {
+    current: usize,
+    max: usize,
+    fastest: Option<Duration>,
+}
+
+impl Concurrency {
+    fn new(max: usize) -> Self {
+        Self { current: 1, max: max.max(1), fastest: None }
+    }
+
+    /// Adjusts after a wave whose slowest request took `slowest`. Returns
+    /// whether the concurrency changed.
+    fn update(&mut self, slowest: Duration, failed: bool) -> bool {
+        let fastest = *self.fastest.get_or_insert(slowest);
+        self.fastest = Some(fastest.min(slowest));
+        let before = self.current;
+        self.current = if failed {
+            self.current / 2
+        } else if slowest > (fastest * 2).max(Duration::from_millis(250)) {
+            self.current - 1
+        } else {
+            self.current + 1
+        }
+        .clamp(1, self.max);
+        self.current != before
+    }
+}
+
+/// Spreads `emails` over time for `--stagger`: chunk `k` of `chunk_size`
+/// messages is scheduled `k * stagger` from now, the first going out at once.
+///
+/// The batch endpoint doesn't take `scheduled_at`, so every message is sent
+/// on its own, paced by `rate` when one is set. `fail_fast` stops at the first
+/// failure; either way a summary is printed and any failure fails the command.
+pub async fn send_staggered(
+    api: &Api,
+    out: Output,
+    emails: Vec<CreateEmailBaseOptions>,
+    opts: ChunkOptions,
+    stagger: TimeDelta,
+) -> Result<()> {
+    let total = emails.len();
+    let chunks = total.div_ceil(opts.chunk_size);
+    let start = Utc::now();
+    let at = |k: usize| start + stagger * i32::try_from(k).unwrap_or(i32::MAX);
+    let last = at(chunks.saturating_sub(1)).to_rfc3339_opts(SecondsFormat::Secs, true);
+    eprintln!(
+        "Staggering {total} message(s) in {chunks} chunk(s) of up to {}, the last at {}.",
+        opts.chunk_size,
+        out.time(&last)
+    );
+
+    let mut throttle = opts.rate.map(|rate| Throttle::new(rate, 1));
+    let (mut scheduled, mut failed) = (0, 0);
+    let mut ids = Vec::new();
+    'chunks: for (k, chunk) in emails.chunks(opts.chunk_size).enumerate() {
+        let scheduled_at = (k > 0).then(|| at(k).to_rfc3339_opts(SecondsFormat::Secs, true));
+        for (i, email) in chunk.iter().enumerate() {
+            if let Some(throttle) = &mut throttle {
+                throttle.acquire(1).await;
+            }
+            let email = match &scheduled_at {
+                Some(when) => email.clone().with_scheduled_at(when),
+                None => email.clone(),
+            };
+            match api.send_email(&email, None).await {
+                Ok((res, _)) => {
+                    scheduled += 1;
+                    ids.push(res.id.to_string());
+                }
+                Err(e) => {
+                    failed += 1;
+                    if !opts.summary_only {
+                        eprintln!(
+                            "failed message {}: {e}",
+                            opts.first_index + k * opts.chunk_size + i + 1
+                        );
+                    }
+                    if opts.fail_fast {
+                        break 'chunks;
+                    }
+                }
+            }
+        }
+    }
+
+    let not_attempted = total - scheduled - failed;
+    if out.is_json() {
+        out.print_json(&serde_json::json!({
+            "total": total,
+            "scheduled": scheduled,
+            "failed": failed,
+            "not_attempted": not_attempted,
+            "last_scheduled_at": last,
+            "ids": ids,
+        }))?;
+    } else {
+        println!(
+            "Staggered batch submitted: {scheduled} scheduled, {failed} failed, \
+             {not_attempted} not attempted."
+        );
+    }
+    if failed > 0 {
+        bail!("{failed} of {total} message(s) failed");
+    }
+    Ok(())
+}
+
+/// A message that failed, as reported by `--json-errors-only`.
+#[derive(Serialize)]
+struct FailedMessage {
+    /// Zero-based position in the batch input.
+    index: usize,
+    error: String,
+    email: serde_json::Value,
+}
+
+/// Version of the `batch --report` layout; bumped on incompatible changes.
+const REPORT_VERSION: u32 = 1;
+
+/// The `batch --report` file: the run's metrics, then one entry per message.
+#[derive(Serialize)]
+struct Report<'a> {
+    version: u32,
+    metrics: &'a BatchMetrics,
+    messages: Vec<ReportEntry>,
+}
+
+#[derive(Serialize)]
+struct ReportEntry {
+    /// Zero-based position in the batch input.
+    index: usize,
+    to: Vec<String>,
+    subject: String,
+    /// `sent`, `failed` or `not_attempted`.
+    status: &'static str,
+    id: Option<String>,
+    error: Option<String>,
+    /// One-based number of the request that carried the message.
+    request: Option<usize>,
+    /// Duration of that request, which the whole chunk shares.
+    duration_ms: Option<f64>,
+}
+
+impl ReportEntry {
+    fn new(index: usize, email: &CreateEmailBaseOptions, status: &'static str) -> Self {
+        let value = serde_json::to_value(email).unwrap_or_default();
+        let to = match &value["to"] {
+            serde_json::Value::String(to) => vec![to.clone()],
+            to => serde_json::from_value(to.clone()).unwrap_or_default(),
+        };
+        Self {
+            index,
+            to,
+            subject: value["subject"].as_str().unwrap_or_default().to_string(),
+            status,
+            id: None,
+            error: None,
+            request: None,
+            duration_ms: None,
+        }
+    }
+}
+
+/// Sends `emails` in requests of at most `chunk_size` messages.
+///
+/// Each chunk is all-or-nothing on the API side. With `fail_fast` the run
+/// stops at the first failed chunk; otherwise every chunk is attempted.
+/// `rate` (emails per second) holds chunks back to keep the average under it.
+/// Either way a summary is printed and any failure fails the command.
+pub async fn send_chunked(
+    api: &Api,
+    out: Output,
+    emails: Vec<CreateEmailBaseOptions>,
+    opts: ChunkOptions,
+) -> Result<()> {
+    let chunk_size = opts.chunk_size;
+    let mut throttle = opts.rate.map(|rate| Throttle::new(rate, chunk_size));
+    let mut failures = Vec::new();
+    let started = Instant::now();
+    let total = emails.len();
+    let first = opts.first_index;
+    let mut sent = 0;
+    let mut failed = 0;
+    let mut first_failed = None;
+    // 1-based ranges of the failed chunks, and whether any chunk after the
+    // first failure went out: concurrent chunks finish in any order.
+    let mut failed_ranges = Vec::new();
+    let mut sent_after_failure = false;
+    let mut requests = 0;
+    let mut durations = Vec::new();
+    let mut entries = Vec::new();
+    let mut ids = Vec::new();
+    let mut concurrency = opts.max_concurrency.map(Concurrency::new);
+    let mut emails = emails.into_iter();
+    let mut start = 0;
+    let mut taken = 0;
+    let mut stop = false;
+    while start < total && !stop {
+        let width = concurrency.as_ref().map_or(1, |c| c.current);
+        let mut wave = Vec::with_capacity(width);
+        while wave.len() < width {
+            // A message with attachments goes alone; other chunks stop short of one.
+            let alone = opts.attached.contains(&taken);
+            let len = if alone {
+                1
+            } else {
+                (taken..taken + chunk_size).take_while(|i| !opts.attached.contains(i)).count()
+            };
+            let chunk: Vec<_> = emails.by_ref().take(len).collect();
+            if chunk.is_empty() {
+                break;
+            }
+            taken += chunk.len();
+            if let Some(throttle) = &mut throttle {
+                throttle.acquire(chunk.len()).await;
+            }
+            wave.push((alone, chunk));
+        }
+        let results = futures::future::join_all(wave.iter().map(|(alone, chunk)| async move {
+            let request_started = Instant::now();
+            let res = if *alone {
+                api.send_email(&chunk[0], None).await.map(|(sent, _)| vec![sent])
+            } else {
+                api.send_batch(chunk, BatchValidation::Strict).await.map(|sent| sent.data)
+            };
+            (res, request_started.elapsed())
+        }))
+        .await;
+        let slowest = results.iter().map(|(_, d)| *d).max().unwrap_or_default();
+        let wave_failed = results.iter().any(|(res, _)| res.is_err());
+        for ((_, chunk), (res, duration)) in wave.iter().zip(results) {
+            let len = chunk.len();
+            requests += 1;
+            durations.push(duration);
+            if opts.report.is_some() {
+                let ids: Vec<String> = match &res {
+                    Ok(sent) => sent.iter().map(|r| r.id.to_string()).collect(),
+                    Err(_) => Vec::new(),
+                };
+                let status = if res.is_ok() { "sent" } else { "failed" };
+                for (i, email) in chunk.iter().enumerate() {
+                    let mut entry = ReportEntry::new(first + start + i, email, status);
+                    entry.id = ids.get(i).cloned();
+                    entry.error = res.as_ref().err().map(ToString::to_string);
+                    entry.request = Some(requests);
+                    entry.duration_ms = Some(duration.as_secs_f64() * 1000.0);
+                    entries.push(entry);
+                }
+            }
+            match res {
+                Ok(res) => {
+                    sent += len;
+                    sent_after_failure |= first_failed.is_some();
+                    ids.extend(res.iter().map(|r| r.id.to_string()));
+                }
+                Err(e) => {
+                    if !opts.summary_only {
+                        eprintln!(
+                            "messages {}-{} failed: {e}",
+                            first + start + 1,
+                            first + start + len
+                        );
+                    }
+                    failed += len;
+                    first_failed.get_or_insert(first + start);
+                    failed_ranges.push(format!("{}-{}", first + start + 1, first + start + len));
+                    if opts.errors_only || opts.failures_file.is_some() {
+                        let error = e.to_string();
+                        for (i, email) in chunk.iter().enumerate() {
+                            let email =
+                                serde_json::to_value(email).context("serialize batch chunk")?;
+                            failures.push(FailedMessage {
+                                index: first + start + i,
+                                error: error.clone(),
+                                email,
+                            });
+                        }
+                    }
+                    if opts.fail_fast {
+                        stop = true;
+                    }
+                }
+            }
+            start += len;
+        }
+        if let Some(c) = &mut concurrency
+            && c.update(slowest, wave_failed)
+        {
+            api.trace(&format!("batch concurrency now {}", c.current));
+        }
+    }
+
+    let elapsed = started.elapsed().as_secs_f64();
+    let metrics = BatchMetrics {
+        total,
+        skipped: first,
+        attempted: sent + failed,
+        succeeded: sent,
+        failed,
+        not_attempted: total - sent - failed,
+        requests,
+        elapsed_secs: elapsed,
+        per_sec: if elapsed > 0.0 { (sent + failed) as f64 / elapsed } else { 0.0 },
+        latency: Latency::from_durations(durations),
+        // Only --fail-fast stops early, right after the wave with the first failure.
+        // Resuming there would resend any later chunk of that wave that went out.
+        resume_from: first_failed.filter(|_| stop && !sent_after_failure),
+        ids,
+    };
+    if let Some(path) = &opts.report {
+        let index = first + entries.len();
+        entries.extend(
+            emails
+                .enumerate()
+                .map(|(i, email)| ReportEntry::new(index + i, &email, "not_attempted")),
+        );
+        let report = Report {
+            version: REPORT_VERSION,
+            metrics: &metrics,
+            messages: entries,
+        };
+        fs::write(path, serde_json::to_string_pretty(&report)?)
+            .with_context(|| format!("write report {}", path.display()))?;
+    }
+    if let Some(path) = &opts.failures_file {
+        fs::write(path, serde_json::to_string_pretty(&failures)?)
+            .with_context(|| format!("write failures file {}", path.display()))?;
+        eprintln!("Wrote {} failed message(s) to {}.", failures.len(), path.display());
+    }
+    if opts.errors_only {
+        out.print_json(&failures)?;
+        eprintln!(
+            "Batch send request submitted: {sent} sent, {failed} failed, {} not attempted \
+             ({requests} request(s)) in {elapsed:.2}s.",
+            metrics.not_attempted
+        );
+    } else if out.is_json() {
+        out.print_json(&metrics)?;
+    } else {
+        println!(
+            "Batch send request submitted: {sent} sent, {failed} failed, {} not attempted \
+             ({requests} request(s)).",
+            metrics.not_attempted
+        );
+        println!(
+            "Attempted {} of {total} in {elapsed:.2}s ({:.1} emails/s).",
+            metrics.attempted, metrics.per_sec
+        );
+        if let Some(latency) = &metrics.latency {
+            println!(
+                "Request latency: p50 {:.0} ms, p95 {:.0} ms, p99 {:.0} ms.",
+                latency.p50_ms, latency.p95_ms, latency.p99_ms
+            );
+        }
+    }
+    if let Some(resume) = metrics.resume_from {
+        eprintln!("Stopped early; rerun with --continue-from {resume} to send the rest.");
+    } else if stop {
+        let rest = if start < total {
+            format!(", then --continue-from {} for the rest", first + start)
+        } else {
+            String::new()
+        };
+        eprintln!(
+            "Stopped early, but later messages of the same wave went out, so \
+             --continue-from would send those twice. Resend only messages {} \
+             (--failures-file writes them out){rest}.",
+            failed_ranges.join(", ")
+        );
+    }
+    if failed > 0 {
+        bail!("{failed} of {total} message(s) failed");
+    }
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use anyhow::{Context, Result};
+use resend_rs::types::InboundEmail;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use crate::timestamp;
+
+/// The newest received email a `received-list --since-last` run has shown.
+#[derive(Serialize, Deserialize)]
+pub struct Bookmark {
+    pub id: String,
+    pub created_at: String,
+}
+
+impl Bookmark {
+    /// Reads the bookmark at `path`; a missing file means there is none yet.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).context("read bookmark"),
+        };
+        let bookmark = serde_json::from_str(&content)
+            .with_context(|| format!("parse bookmark {}", path.display()))?;
+        Ok(Some(bookmark))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?).context("write bookmark")
+    }
+
+    /// Deletes the bookmark at `path`, returning whether there was one.
+    pub fn clear(path: &Path) -> Result<bool> {
+        match fs::remove_file(path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e).context("remove bookmark"),
+        }
+    }
+
+    /// The bookmark for the newest of `emails`, if there are any.
+    pub fn newest(emails: &[InboundEmail]) -> Option<Self> {
+        emails
+            .iter()
+            .max_by_key(|e| timestamp::parse(&e.created_at))
+            .map(|e| Self {
+                id: e.id.to_string(),
+                created_at: e.created_at.clone(),
+            })
+    }
+
+    /// Whether `email` arrived after the bookmarked one.
+    ///
+    /// Emails with the same timestamp count as newer unless they are the
+    /// bookmarked email itself.
+    pub fn is_older_than(&self, email: &InboundEmail) -> bool {
+        let (Some(mark), Some(created)) = (
+            timestamp::parse(&self.created_at),
+            timestamp::parse(&email.created_at),
+        ) else {
+            return *email.id != *self.id;
+        };
+        created > mark || (created == mark && *email.id != *self.id)
+    }
+}
@@ -0,0 +1,623 @@
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use futures::FutureExt;
+use reqwest::header::{CONTENT_TYPE, HeaderMap, USER_AGENT};
+use reqwest::{StatusCode, Url};
+use resend_rs::types::{
+    BatchValidation, CreateEmailBaseOptions, CreateEmailResponse, ErrorKind, ErrorResponse,
+    SendEmailBatchPermissiveResponse,
+};
+use resend_rs::{ConfigBuilder, Error, Resend};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::future::Future;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How many times [`Api::call`] tries a request before giving up (see
+/// [`RetryPolicy::max_attempts`]).
+const MAX_ATTEMPTS: u32 = 4;
+
+static BASE_URL: OnceLock<Url> = OnceLock::new();
+
+/// Points every client at `url` (`--base-url`) instead of `RESEND_BASE_URL`
+/// or Resend's own API.
+pub fn set_base_url(url: Url) {
+    // Called once, before any client exists.
+    let _ = BASE_URL.set(url);
+}
+
+/// A resend-rs client for `api_key` that honors `--base-url`.
+pub fn resend(api_key: &str, client: Option<reqwest::Client>) -> Resend {
+    let mut config = ConfigBuilder::new(api_key);
+    if let Some(url) = BASE_URL.get() {
+        config = config.base_url(url.clone());
+    }
+    if let Some(client) = client {
+        config = config.client(client);
+    }
+    Resend::with_config(config.build())
+}
+
+/// Which failed API calls are tried again.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum RetryPolicy {
+    /// Never retry
+    None,
+    /// Retry rate-limited (429) responses
+    #[default]
+    #[value(name = "429-only")]
+    RateLimited,
+    /// Retry rate-limited and server (5xx) errors
+    Full,
+    /// Retry only connection failures (DNS included) and timeouts, twice, quickly
+    #[value(name = "network-only")]
+    NetworkOnly,
+}
+
+impl RetryPolicy {
+    /// How long to wait before attempt `attempt + 1`, or `None` to give up.
+    ///
+    /// Rate limits wait for the advertised reset (capped at a minute), other
+    /// errors back off exponentially from one second, or from a quarter second
+    /// for `network-only`.
+    fn delay(self, error: &Error, attempt: u32) -> Option<Duration> {
+        let backoff = Duration::from_secs(1 << (attempt - 1));
+        match (self, error_status(error)) {
+            (Self::None, _) => None,
+            (Self::NetworkOnly, _) => {
+                is_network(error).then(|| Duration::from_millis(250 << (attempt - 1)))
+            }
+            (_, Some(429)) => Some(match error {
+                Error::RateLimit {
+                    ratelimit_reset: Some(reset),
+                    ..
+                } => Duration::from_secs((*reset).clamp(1, 60)),
+                _ => backoff,
+            }),
+            (Self::Full, Some(500..=599)) => Some(backoff),
+            (Self::Full, None) if is_timeout(error) => Some(backoff),
+            _ => None,
+        }
+    }
+
+    /// How many times [`Api::call`] tries a request under this policy.
+    fn max_attempts(self) -> u32 {
+        match self {
+            Self::NetworkOnly => 3,
+            _ => MAX_ATTEMPTS,
+        }
+    }
+}
+
+/// `--retry-policy` plus `--force-retry`, as every [`Api`] gets them.
+#[derive(Clone, Copy)]
+pub struct Retry {
+    pub policy: RetryPolicy,
+    /// Retry sends without an idempotency key even when the failure leaves
+    /// it unclear whether they went through.
+    pub force: bool,
+}
+
+/// Whether a send may have been processed although it failed: a server error
+/// or a timeout, as opposed to a rejection like a 4xx or a rate limit.
+fn is_ambiguous(error: &Error) -> bool {
+    matches!(error_status(error), Some(500..=599)) || is_timeout(error)
+}
+
+fn is_timeout(error: &Error) -> bool {
+    matches!(error, Error::Http(e) if e.is_timeout())
+}
+
+/// A failure to reach the API or hear back from it, with no HTTP status.
+fn is_network(error: &Error) -> bool {
+    matches!(error, Error::Http(e) if e.status().is_none() && (e.is_connect() || e.is_timeout()))
+}
+
+/// The `RESEND_API_KEY` environment variable when it is set, so CI can inject
+/// a key without writing files, or else the `saved` key.
+pub fn effective_api_key(saved: &str) -> String {
+    std::env::var("RESEND_API_KEY")
+        .ok()
+        .filter(|key| !key.is_empty())
+        .unwrap_or_else(|| saved.to_string())
+}
+
+/// Reads a resend-rs style result out of `response`: rate limits, API errors
+/// and HTML error pages become the [`Error`] resend-rs would return.
+async fn parse_response<T: DeserializeOwned>(
+    response: reqwest::Response,
+    headers: &ResponseHeaders,
+) -> resend_rs::Result<T> {
+    let status = response.status();
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let number = |value: &Option<String>| value.as_deref().and_then(|v| v.parse().ok());
+        return Err(Error::RateLimit {
+            ratelimit_limit: number(&headers.ratelimit_limit),
+            ratelimit_remaining: number(&headers.ratelimit_remaining),
+            ratelimit_reset: number(&headers.ratelimit_reset),
+        });
+    }
+    if status.is_client_error() || status.is_server_error() {
+        let html = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.contains("html"));
+        if html {
+            return Err(Error::Parse(response.text().await?));
+        }
+        return Err(Error::Resend(response.json::<ErrorResponse>().await?));
+    }
+    Ok(response.json().await?)
+}
+
+/// The response headers `send --return-headers` reports.
+#[derive(Default, Serialize)]
+pub struct ResponseHeaders {
+    /// `x-request-id`, Resend's id for the request (handy in a support ticket).
+    pub request_id: Option<String>,
+    pub ratelimit_limit: Option<String>,
+    pub ratelimit_remaining: Option<String>,
+    /// Seconds until the rate-limit window resets.
+    pub ratelimit_reset: Option<String>,
+}
+
+impl From<&HeaderMap> for ResponseHeaders {
+    fn from(headers: &HeaderMap) -> Self {
+        let get = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(String::from);
+        Self {
+            request_id: get("x-request-id"),
+            ratelimit_limit: get("ratelimit-limit"),
+            ratelimit_remaining: get("ratelimit-remaining"),
+            ratelimit_reset: get("ratelimit-reset"),
+        }
+    }
+}
+
+/// The resend-rs client plus the bookkeeping every API call goes through.
+pub struct Api {
+    pub resend: Resend,
+    log: Option<ApiLog>,
+    retry: Retry,
+    /// Successful lookup responses keyed by endpoint, see [`Api::cached`].
+    memo: Mutex<HashMap<String, Arc<dyn Any + Send + Sync>>>,
+}
+
+impl Api {
+    /// Builds the client for `RESEND_API_KEY` when it is set, or else for the saved `api_key`.
+    pub fn new(api_key: &str, log: Option<ApiLog>, retry: Retry) -> Self {
+        Self::with_key(&effective_api_key(api_key), log, retry)
+    }
+
+    /// Builds the client for exactly `api_key`, whatever `RESEND_API_KEY` says,
+    /// for commands that check or replace a particular key.
+    pub fn with_key(api_key: &str, log: Option<ApiLog>, retry: Retry) -> Self {
+        Self {
+            resend: resend(
+                api_key,
+                crate::wire::enabled().then(|| {
+                    reqwest::Client::builder()
+                        .connection_verbose(true)
+                        .build()
+                        .unwrap_or_default()
+                }),
+            ),
+            log,
+            retry,
+            memo: Mutex::default(),
+        }
+    }
+
+    /// Runs an API call, recording it to the request log when one is configured.
+    ///
+    /// `request` builds the call and is invoked again for each retry the
+    /// [`RetryPolicy`] allows; every attempt is logged. `endpoint` is a short
+    /// `METHOD /path` label; the API key is never logged.
+    pub async fn call<T, Fut>(
+        &self,
+        endpoint: &str,
+        mut request: impl FnMut() -> Fut,
+    ) -> resend_rs::Result<T>
+    where
+        Fut: Future<Output = resend_rs::Result<T>>,
+    {
+        // resend-rs keeps the response headers, request id included, to itself.
+        self.attempt(endpoint, true, || request().map(|result| (None, result))).await
+    }
+
+    /// Like [`Api::call`], for a request that sends email.
+    ///
+    /// Without an idempotency key (`keyed`), a retry after a server error or a
+    /// timeout could deliver the email twice, so those failures are only
+    /// retried with `--force-retry`. Rate limits are retried either way.
+    pub async fn send<T, Fut>(
+        &self,
+        endpoint: &str,
+        keyed: bool,
+        mut request: impl FnMut() -> Fut,
+    ) -> resend_rs::Result<T>
+    where
+        Fut: Future<Output = resend_rs::Result<T>>,
+    {
+        let retry_ambiguous = keyed || self.retry.force;
+        self.attempt(endpoint, retry_ambiguous, || request().map(|result| (None, result))).await
+    }
+
+    /// The retry loop behind [`Api::call`] and [`Api::send`]. `request` also
+    /// returns Resend's request id, when its response was seen, for the log.
+    async fn attempt<T, Fut>(
+        &self,
+        endpoint: &str,
+        retry_ambiguous: bool,
+        mut request: impl FnMut() -> Fut,
+    ) -> resend_rs::Result<T>
+    where
+        Fut: Future<Output = (Option<String>, resend_rs::Result<T>)>,
+    {
+        let mut attempt = 1;
+        loop {
+            let started = Instant::now();
+            let (request_id, result) = request().await;
+            if let Some(log) = &self.log {
+                let error = result.as_ref().err();
+                log.record(
+                    endpoint,
+                    started,
+                    error.map_or(Some(200), error_status),
+                    request_id.as_deref(),
+                    error.map(ToString::to_string),
+                );
+            }
+            let max_attempts = self.retry.policy.max_attempts();
+            if let Err(e) = &result
+                && attempt < max_attempts
+                && let Some(wait) = self.retry.policy.delay(e, attempt)
+            {
+                if !retry_ambiguous && is_ambiguous(e) {
+                    eprintln!(
+                        "{endpoint} failed ({e}), not retrying: it has no idempotency key \
+                         and may have gone through (--force-retry retries anyway)"
+                    );
+                    return result;
+                }
+                attempt += 1;
+                let wait_label = if wait < Duration::from_secs(1) {
+                    format!("{}ms", wait.as_millis())
+                } else {
+                    format!("{}s", wait.as_secs())
+                };
+                eprintln!(
+                    "{endpoint} failed ({e}), retrying in {wait_label} \
+                     (attempt {attempt} of {max_attempts})"
+                );
+                tokio::time::sleep(wait).await;
+                continue;
+            }
+            return result;
+        }
+    }
+
+    /// Whether `--verbose` is on.
+    pub fn verbose(&self) -> bool {
+        self.log.as_ref().is_some_and(|l| l.correlation_id.is_some())
+    }
+
+    /// Prints `message` to stderr in `--verbose` mode, tagged like the call lines.
+    pub fn trace(&self, message: &str) {
+        if let Some(id) = self.log.as_ref().and_then(|l| l.correlation_id.as_deref()) {
+            eprintln!("[{id}] {message}");
+        }
+    }
+
+    /// GETs `path` (e.g. `/emails/<id>`) and returns the response body exactly
+    /// as the API sent it, bypassing resend-rs' typed models. Used by `--raw`.
+    pub async fn get_raw(&self, endpoint: &str, path: &str) -> Result<String> {
+        let url = format!("{}{path}", self.resend.base_url().trim_end_matches('/'));
+        let started = Instant::now();
+        let result = async {
+            let response = self
+                .resend
+                .client()
+                .get(&url)
+                .bearer_auth(self.resend.api_key())
+                .header(USER_AGENT, self.resend.user_agent())
+                .send()
+                .await?;
+            let status = response.status();
+            let request_id = response
+                .headers()
+                .get("x-request-id")
+                .and_then(|v| v.to_str().ok())
+                .map(String::from);
+            Ok::<_, reqwest::Error>((status, request_id, response.text().await?))
+        }
+        .await;
+        if let Some(log) = &self.log {
+            match &result {
+                Ok((status, request_id, _)) => log.record(
+                    endpoint,
+                    started,
+                    Some(status.as_u16()),
+                    request_id.as_deref(),
+                    None,
+                ),
+                Err(e) => log.record(
+                    endpoint,
+                    started,
+                    e.status().map(|s| s.as_u16()),
+                    None,
+                    Some(e.to_string()),
+                ),
+            }
+        }
+
+        let (status, _, body) = result.with_context(|| format!("{endpoint} failed"))?;
+        if !status.is_success() {
+            bail!("{endpoint} returned {status}: {body}");
+        }
+        Ok(body)
+    }
+
+    /// Sends `email` with `POST /emails`, retried as [`Api::send`] does. Unlike
+    /// `resend.emails.send` this keeps the response headers, so the log gets
+    /// the request id and `send --return-headers` the rate limits.
+    pub async fn send_email(
+        &self,
+        email: &CreateEmailBaseOptions,
+        idempotency_key: Option<&str>,
+    ) -> resend_rs::Result<(CreateEmailResponse, ResponseHeaders)> {
+        let headers: Vec<_> = idempotency_key.iter().map(|key| ("Idempotency-Key", *key)).collect();
+        let keyed = idempotency_key.is_some() || self.retry.force;
+        self.attempt("POST /emails", keyed, || async {
+            let (response, result) = self.post("/emails", email, &headers).await;
+            let request_id = response.as_ref().and_then(|r| r.request_id.clone());
+            (request_id, result.map(|sent| (sent, response.unwrap_or_default())))
+        })
+        .await
+    }
+
+    /// Sends `emails` in one `POST /emails/batch`, retried as [`Api::send`]
+    /// does for a request without an idempotency key. Like `resend.batch`, but
+    /// the log gets the request id.
+    pub async fn send_batch(
+        &self,
+        emails: &[CreateEmailBaseOptions],
+        validation: BatchValidation,
+    ) -> resend_rs::Result<SendEmailBatchPermissiveResponse> {
+        let validation = validation.to_string();
+        let headers = [("x-batch-validation", validation.as_str())];
+        self.attempt("POST /emails/batch", self.retry.force, || async {
+            let (response, result) = self.post("/emails/batch", emails, &headers).await;
+            (response.and_then(|r| r.request_id), result)
+        })
+        .await
+    }
+
+    /// POSTs `body` as JSON to `path` as resend-rs does, with `headers` added.
+    /// Errors come back as resend-rs reports them; the response headers come
+    /// back whenever there was a response, failed or not.
+    async fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &(impl Serialize + ?Sized),
+        headers: &[(&str, &str)],
+    ) -> (Option<ResponseHeaders>, resend_rs::Result<T>) {
+        let url = format!("{}{path}", self.resend.base_url().trim_end_matches('/'));
+        let mut request = self
+            .resend
+            .client()
+            .post(&url)
+            .bearer_auth(self.resend.api_key())
+            .header(USER_AGENT, self.resend.user_agent())
+            .json(body);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) => return (None, Err(e.into())),
+        };
+        let headers = ResponseHeaders::from(response.headers());
+        let result = parse_response(response, &headers).await;
+        (Some(headers), result)
+    }
+
+    /// Like [`Api::call`], but remembers a successful response so later lookups
+    /// of the same `endpoint` in this process don't hit the API again.
+    ///
+    /// Meant for read-only listings (domains, audiences) that preflights consult
+    /// repeatedly. The label must identify the request fully, path parameters
+    /// included. Errors are not cached, and nothing is kept beyond the process.
+    pub async fn cached<T, Fut>(
+        &self,
+        endpoint: &str,
+        request: impl FnMut() -> Fut,
+    ) -> resend_rs::Result<T>
+    where
+        T: Clone + Send + Sync + 'static,
+        Fut: Future<Output = resend_rs::Result<T>>,
+    {
+        let hit = self.memo.lock().unwrap().get(endpoint).cloned();
+        if let Some(value) = hit.and_then(|v| v.downcast::<T>().ok()) {
+            return Ok(T::clone(&value));
+        }
+        let value = self.call(endpoint, request).await?;
+        self.memo
+            .lock()
+            .unwrap()
+            .insert(endpoint.to_string(), Arc::new(value.clone()));
+        Ok(value)
+    }
+}
+
+/// Record of API calls: JSON lines appended to `--log-file`, and with
+/// `--verbose` a line per call on stderr.
+pub struct ApiLog {
+    file: Option<File>,
+    /// Set with `--verbose`; tags every record of this invocation.
+    correlation_id: Option<String>,
+}
+
+/// A practically unique id for this invocation, e.g. `rs-18f2c3a4b5d-1a2b`.
+pub fn correlation_id() -> String {
+    let ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    format!("rs-{ms:x}-{:x}", std::process::id())
+}
+
+#[derive(Serialize)]
+struct LogRecord<'a> {
+    /// Milliseconds since the Unix epoch when the call finished.
+    ts_ms: u128,
+    endpoint: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    correlation_id: Option<&'a str>,
+    /// Resend's id for the request, for the calls that read the response
+    /// themselves (sends and [`Api::get_raw`]); resend-rs hides it otherwise.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<&'a str>,
+    /// HTTP status. resend-rs doesn't expose the code of successful responses,
+    /// so those are logged as 200; transport failures have no status.
+    status: Option<u16>,
+    latency_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl ApiLog {
+    /// Opens `path` for appending. With `rotate`, an existing log is first moved
+    /// aside to `<path>.1` (replacing any previous rotation).
+    ///
+    /// Returns `None` when there is neither a file nor a correlation id, i.e.
+    /// nothing to record.
+    pub fn open(
+        path: Option<&Path>,
+        rotate: bool,
+        correlation_id: Option<String>,
+    ) -> Result<Option<Self>> {
+        let file = match path {
+            Some(path) => {
+                if rotate && path.exists() {
+                    let mut rotated = path.as_os_str().to_owned();
+                    rotated.push(".1");
+                    fs::rename(path, &rotated).context("rotate log file")?;
+                }
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .context("open log file")?;
+                Some(file)
+            }
+            None if correlation_id.is_none() => return Ok(None),
+            None => None,
+        };
+        Ok(Some(Self {
+            file,
+            correlation_id,
+        }))
+    }
+
+    fn record(
+        &self,
+        endpoint: &str,
+        started: Instant,
+        status: Option<u16>,
+        request_id: Option<&str>,
+        error: Option<String>,
+    ) {
+        let record = LogRecord {
+            ts_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis())
+                .unwrap_or_default(),
+            endpoint,
+            correlation_id: self.correlation_id.as_deref(),
+            request_id,
+            status,
+            latency_ms: started.elapsed().as_millis(),
+            error,
+        };
+        if let Some(id) = &self.correlation_id {
+            let status = record.status.map_or("-".to_string(), |s| s.to_string());
+            let request = request_id.map_or(String::new(), |r| format!(" request {r}"));
+            let error = record.error.as_deref().map_or(String::new(), |e| format!(": {e}"));
+            eprintln!(
+                "[{id}] {endpoint} {status} {}ms{request}{error}",
+                record.latency_ms
+            );
+        }
+        if let Some(mut file) = self.file.as_ref() {
+            let mut line = serde_json::to_string(&record).unwrap_or_default();
+            line.push('\n');
+            if let Err(e) = file.write_all(line.as_bytes()) {
+                eprintln!("warning: failed to write log file: {e}");
+            }
+        }
+    }
+}
+
+/// A suggestion for the user when `error` came from a well-known API failure.
+///
+/// Shown under the error itself, which keeps Resend's code and message.
+pub fn hint(error: &anyhow::Error) -> Option<&'static str> {
+    let api = error.chain().find_map(|e| e.downcast_ref::<Error>())?;
+    let response = match api {
+        Error::RateLimit { .. } => {
+            return Some(
+                "Resend is rate limiting this key. Wait a moment and retry, \
+                 or pace batches with `batch --rate`.",
+            );
+        }
+        Error::Resend(response) => response,
+        Error::Http(_) | Error::Parse(_) => return None,
+    };
+    // `kind()` doesn't tell the two `validation_error`s apart, so those go by message.
+    let message = response.message.to_ascii_lowercase();
+    let validation = response.name == "validation_error";
+    Some(match response.kind() {
+        ErrorKind::MissingApiKey | ErrorKind::InvalidApiKey => {
+            "Check your API key, or save a new one with `rusend config --key re_...`."
+        }
+        ErrorKind::RestrictedApiKey => {
+            "This key can only send emails; use a full-access key for other commands."
+        }
+        ErrorKind::InvalidFromAddress => {
+            "Use `email@example.com` or `Name <email@example.com>` as the From address, \
+             or build it with --from-email and --from-name."
+        }
+        _ if validation && message.contains("not verified") => {
+            "Your From domain isn't verified. Verify it at https://resend.com/domains, \
+             and catch this before sending with `send --check-domain`."
+        }
+        _ if validation && message.contains("testing emails") => {
+            "Without a verified domain, Resend only delivers to your own address. \
+             Add a domain at https://resend.com/domains."
+        }
+        ErrorKind::RateLimitExceeded => {
+            "Resend is rate limiting this key. Wait a moment and retry, \
+             or pace batches with `batch --rate`."
+        }
+        ErrorKind::DailyQuotaExceeded | ErrorKind::MonthlyQuotaExceeded => {
+            "The account's sending quota is used up. Wait for it to reset or upgrade the plan."
+        }
+        _ => return None,
+    })
+}
+
+fn error_status(error: &Error) -> Option<u16> {
+    match error {
+        Error::Resend(e) => Some(e.status_code),
+        Error::RateLimit { .. } => Some(429),
+        Error::Http(e) => e.status().map(|s| s.as_u16()),
+        Error::Parse(_) => None,
+    }
+}
@@ -0,0 +1,120 @@
+use anyhow::{Context, Result, bail};
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use crate::address;
+use crate::editor;
+
+/// A message gathered interactively by `rusend compose`.
+pub struct Draft {
+    pub from: String,
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+    pub html: bool,
+}
+
+/// Prompts for each field of a message, opening the editor for the body, and
+/// asks for confirmation. Returns `None` if the user declines to send.
+pub fn run(default_from: Option<&str>, default_to: Option<&str>) -> Result<Option<Draft>> {
+    if !interactive() {
+        bail!("compose needs an interactive terminal; use `rusend send` in scripts");
+    }
+
+    let from = prompt_required("From", default_from)?;
+    let to = prompt_required("To (comma separated)", default_to)?;
+    let subject = prompt_required("Subject", None)?;
+    let html = confirm("Write the body as HTML?")?;
+
+    let body = editor::edit("", if html { "html" } else { "txt" })?;
+    if body.trim().is_empty() {
+        bail!("empty body, nothing sent");
+    }
+
+    println!();
+    println!("From:    {from}");
+    println!("To:      {}", address::preview(to.split(',').filter(|a| !a.trim().is_empty())));
+    println!("Subject: {subject}");
+    println!(
+        "Body:    {} bytes ({})",
+        body.len(),
+        if html { "HTML" } else { "text" }
+    );
+    if !confirm("Send this email?")? {
+        println!("Not sent.");
+        return Ok(None);
+    }
+
+    Ok(Some(Draft {
+        from,
+        to,
+        subject,
+        body,
+        html,
+    }))
+}
+
+/// Whether both stdin and stdout are a terminal, so prompts can be answered.
+pub fn interactive() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+/// Lets the user pick a From address when none was given: one of the saved
+/// senders, or an address on one of the account's verified `domains`.
+pub fn pick_from(senders: &BTreeMap<String, String>, domains: &[String]) -> Result<String> {
+    if senders.is_empty() && domains.is_empty() {
+        bail!("From address not provided, and there are no saved senders or verified domains");
+    }
+    let mut items: Vec<String> =
+        senders.iter().map(|(name, from)| format!("{from}  (sender {name})")).collect();
+    items.extend(domains.iter().map(|d| format!("...@{d}")));
+    let choice = dialoguer::Select::new()
+        .with_prompt("No From address given. Send from")
+        .items(&items)
+        .default(0)
+        .interact()
+        .context("pick a From address")?;
+    if let Some(from) = senders.values().nth(choice) {
+        return Ok(from.clone());
+    }
+    let domain = &domains[choice - senders.len()];
+    loop {
+        let local = prompt_required(&format!("Address at {domain}"), Some("no-reply"))?;
+        let email = format!("{}@{domain}", local.trim_end_matches(&format!("@{domain}")));
+        if address::is_valid(&email) {
+            return Ok(email);
+        }
+        println!("{email} is not a valid address.");
+    }
+}
+
+fn read_line(label: &str) -> Result<String> {
+    print!("{label}");
+    io::stdout().flush().context("flush prompt")?;
+    let mut line = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .context("read answer")?;
+    Ok(line.trim().to_string())
+}
+
+/// Asks until a non-empty answer is given; Enter accepts `default`.
+fn prompt_required(label: &str, default: Option<&str>) -> Result<String> {
+    loop {
+        let answer = match default {
+            Some(d) => read_line(&format!("{label} [{d}]: "))?,
+            None => read_line(&format!("{label}: "))?,
+        };
+        match (answer.is_empty(), default) {
+            (false, _) => return Ok(answer),
+            (true, Some(d)) => return Ok(d.to_string()),
+            (true, None) => println!("{label} is required."),
+        }
+    }
+}
+
+pub fn confirm(question: &str) -> Result<bool> {
+    let answer = read_line(&format!("{question} [y/N]: "))?;
+    Ok(matches!(answer.to_ascii_lowercase().as_str(), "y" | "yes"))
+}
@@ -0,0 +1,41 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::path::Path;
+
+/// File format for `config export` and `config import`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ConfigFormat {
+    /// JSON, as rusend stores its own configuration
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl ConfigFormat {
+    /// Guesses the format from the file extension, defaulting to JSON.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => Self::Yaml,
+            Some("toml") => Self::Toml,
+            _ => Self::Json,
+        }
+    }
+
+    pub fn parse<T: DeserializeOwned>(self, content: &str) -> Result<T> {
+        Ok(match self {
+            Self::Json => serde_json::from_str(content)?,
+            Self::Yaml => serde_yaml::from_str(content)?,
+            Self::Toml => toml::from_str(content)?,
+        })
+    }
+
+    pub fn render<T: Serialize>(self, value: &T) -> Result<String> {
+        Ok(match self {
+            Self::Json => serde_json::to_string_pretty(value)?,
+            Self::Yaml => serde_yaml::to_string(value)?,
+            Self::Toml => toml::to_string_pretty(value)?,
+        })
+    }
+}
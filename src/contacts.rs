@@ -0,0 +1,439 @@
+use anyhow::{Context, Result, bail};
+use clap::{Subcommand, ValueEnum};
+use resend_rs::list_opts::{ListOptions, ListResponse};
+use resend_rs::types::{Contact, ContactChanges, CreateContactOptions};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::address;
+use crate::client::Api;
+use crate::compose;
+use crate::parallel;
+
+#[derive(Subcommand)]
+pub enum ContactsCommand {
+    /// Create contacts in an audience from a CSV file
+    ///
+    /// Columns: email,first_name,last_name,unsubscribed. Only `email` is
+    /// required; a header row starting with `email` is skipped.
+    Import {
+        /// Audience to add the contacts to
+        #[arg(long)]
+        audience_id: String,
+
+        /// CSV file to import
+        #[arg(long)]
+        file: PathBuf,
+
+        /// Number of contacts created in parallel
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+    },
+
+    /// Write every contact of an audience as CSV or JSON
+    Export {
+        /// Audience to export
+        #[arg(long)]
+        audience_id: String,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+
+        /// File to write (defaults to stdout)
+        #[arg(long, value_name = "PATH")]
+        out: Option<PathBuf>,
+    },
+
+    /// Mark a contact as unsubscribed from an audience's broadcasts
+    Unsubscribe(SubscriptionArgs),
+
+    /// Subscribe a previously unsubscribed contact again
+    Resubscribe(SubscriptionArgs),
+}
+
+#[derive(clap::Args)]
+pub struct SubscriptionArgs {
+    /// Audience the contact belongs to
+    audience_id: String,
+
+    /// Contact email address or ID
+    contact: String,
+
+    /// Apply the change without asking
+    #[arg(short, long)]
+    yes: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+pub async fn run(api: &Api, command: ContactsCommand) -> Result<()> {
+    match command {
+        ContactsCommand::Import {
+            audience_id,
+            file,
+            concurrency,
+        } => import(api, &audience_id, &file, concurrency).await,
+        ContactsCommand::Export {
+            audience_id,
+            format,
+            out,
+        } => export(api, &audience_id, format, out.as_deref()).await,
+        ContactsCommand::Unsubscribe(args) => set_subscription(api, args, true).await,
+        ContactsCommand::Resubscribe(args) => set_subscription(api, args, false).await,
+    }
+}
+
+/// Finds the contact in the audience, then sets its `unsubscribed` flag and
+/// prints the state the API reports afterwards.
+async fn set_subscription(api: &Api, args: SubscriptionArgs, unsubscribed: bool) -> Result<()> {
+    let contact = find_contact(api, &args.audience_id, &args.contact)
+        .await?
+        .with_context(|| {
+            format!("no contact {} in audience {}", args.contact, args.audience_id)
+        })?;
+    let action = if unsubscribed { "Unsubscribe" } else { "Resubscribe" };
+    if contact.unsubscribed == unsubscribed {
+        println!("{} is already {}.", contact.email, subscription_state(unsubscribed));
+        return Ok(());
+    }
+    if !args.yes && !compose::confirm(&format!("{action} {}?", contact.email))? {
+        bail!("{} cancelled", action.to_ascii_lowercase());
+    }
+
+    let changes = ContactChanges::new().with_unsubscribed(unsubscribed);
+    api.call("PATCH /contacts/{id}", || {
+        api.resend.contacts.update(&contact.id, changes.clone())
+    })
+    .await
+    .context("update contact failed")?;
+    let updated = api
+        .call("GET /contacts/{id}", || api.resend.contacts.get(&contact.id))
+        .await
+        .context("get contact failed")?;
+    println!(
+        "{} ({}) is now {}.",
+        updated.email,
+        updated.id,
+        subscription_state(updated.unsubscribed)
+    );
+    Ok(())
+}
+
+fn subscription_state(unsubscribed: bool) -> &'static str {
+    if unsubscribed { "unsubscribed" } else { "subscribed" }
+}
+
+/// One page of the audience's contacts: the first, or the one after the
+/// contact with ID `after`.
+async fn contacts_page(
+    api: &Api,
+    audience_id: &str,
+    after: Option<&str>,
+) -> Result<ListResponse<Contact>> {
+    let opts = ListOptions::default().with_limit(PAGE_SIZE);
+    match after {
+        None => {
+            api.call(
+                "GET /audiences/{id}/contacts",
+                || api.resend.contacts.list(audience_id, opts.clone()),
+            )
+            .await
+        }
+        Some(id) => {
+            api.call(
+                "GET /audiences/{id}/contacts",
+                || api.resend.contacts.list(audience_id, opts.clone().list_after(id)),
+            )
+            .await
+        }
+    }
+    .context("list contacts failed")
+}
+
+/// Pages through the audience for a contact matching `contact` by ID or,
+/// ignoring case, by email address.
+async fn find_contact(api: &Api, audience_id: &str, contact: &str) -> Result<Option<Contact>> {
+    let mut after: Option<String> = None;
+    loop {
+        let page = contacts_page(api, audience_id, after.as_deref()).await?;
+        if let Some(found) = page
+            .data
+            .iter()
+            .find(|c| c.id.as_ref() == contact || c.email.eq_ignore_ascii_case(contact))
+        {
+            return Ok(Some(found.clone()));
+        }
+        match page.data.last() {
+            Some(last) if page.has_more => after = Some(last.id.to_string()),
+            _ => return Ok(None),
+        }
+    }
+}
+
+struct ImportRow {
+    line: u64,
+    contact: CreateContactOptions,
+    email: String,
+}
+
+async fn import(api: &Api, audience_id: &str, file: &Path, concurrency: usize) -> Result<()> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_path(file)
+        .context("open contacts file")?;
+
+    let mut rows = Vec::new();
+    let mut skipped = 0;
+    for (i, record) in reader.records().enumerate() {
+        let record = record.context("read contacts file")?;
+        let line = record.position().map_or(i as u64 + 1, |p| p.line());
+        let email = record.get(0).unwrap_or_default();
+        if i == 0 && email.eq_ignore_ascii_case("email") {
+            continue;
+        }
+        if !address::is_valid(email) {
+            println!("line {line}: skipped, invalid email {email:?}");
+            skipped += 1;
+            continue;
+        }
+
+        let mut contact = CreateContactOptions::new(email).with_audience_id(audience_id);
+        if let Some(first) = record.get(1).filter(|s| !s.is_empty()) {
+            contact = contact.with_first_name(first);
+        }
+        if let Some(last) = record.get(2).filter(|s| !s.is_empty()) {
+            contact = contact.with_last_name(last);
+        }
+        match record.get(3).filter(|s| !s.is_empty()).map(parse_bool) {
+            Some(Some(unsubscribed)) => contact = contact.with_unsubscribed(unsubscribed),
+            Some(None) => {
+                println!("line {line}: skipped, unsubscribed must be true or false");
+                skipped += 1;
+                continue;
+            }
+            None => {}
+        }
+        rows.push(ImportRow {
+            line,
+            contact,
+            email: email.to_string(),
+        });
+    }
+
+    let results = parallel::ordered(rows, concurrency, |row| async move {
+        let result = api
+            .call(
+                "POST /audiences/{id}/contacts",
+                || api.resend.contacts.create(row.contact.clone()),
+            )
+            .await;
+        (row.line, row.email, result)
+    })
+    .await;
+
+    let mut created = 0;
+    let mut failed = 0;
+    for (line, email, result) in results {
+        match result {
+            Ok(id) => {
+                println!("line {line}: created {email} ({id})");
+                created += 1;
+            }
+            Err(e) => {
+                println!("line {line}: failed {email}: {e}");
+                failed += 1;
+            }
+        }
+    }
+
+    println!("Imported {created} contact(s), {failed} failed, {skipped} skipped.");
+    if failed > 0 {
+        bail!("{failed} contact(s) could not be imported");
+    }
+    Ok(())
+}
+
+fn parse_bool(s: &str) -> Option<bool> {
+    match s.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "1" => Some(true),
+        "false" | "no" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Largest page size the list endpoints accept.
+const PAGE_SIZE: u8 = 100;
+
+#[derive(Serialize)]
+struct ContactRecord<'a> {
+    id: &'a str,
+    email: &'a str,
+    first_name: &'a str,
+    last_name: &'a str,
+    unsubscribed: bool,
+    created_at: &'a str,
+}
+
+impl<'a> From<&'a Contact> for ContactRecord<'a> {
+    fn from(c: &'a Contact) -> Self {
+        Self {
+            id: &c.id,
+            email: &c.email,
+            first_name: &c.first_name,
+            last_name: &c.last_name,
+            unsubscribed: c.unsubscribed,
+            created_at: &c.created_at,
+        }
+    }
+}
+
+/// Streams records out as either CSV rows or the elements of a JSON array.
+enum ExportWriter<W: Write> {
+    Csv(Box<csv::Writer<W>>),
+    Json { out: W, empty: bool },
+}
+
+impl<W: Write> ExportWriter<W> {
+    fn new(format: ExportFormat, out: W) -> Self {
+        match format {
+            ExportFormat::Csv => Self::Csv(Box::new(csv::Writer::from_writer(out))),
+            ExportFormat::Json => Self::Json { out, empty: true },
+        }
+    }
+
+    fn write(&mut self, record: &ContactRecord) -> Result<()> {
+        match self {
+            Self::Csv(w) => w.serialize(record)?,
+            Self::Json { out, empty } => {
+                out.write_all(if *empty { b"[\n" } else { b",\n" })?;
+                serde_json::to_writer(&mut *out, record)?;
+                *empty = false;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            Self::Csv(mut w) => w.flush()?,
+            Self::Json { mut out, empty } => {
+                out.write_all(if empty { b"[]\n" } else { b"\n]\n" })?;
+                out.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Pages through the audience and writes each page as soon as it arrives, so
+/// memory use stays flat however large the audience is.
+async fn export(
+    api: &Api,
+    audience_id: &str,
+    format: ExportFormat,
+    out: Option<&Path>,
+) -> Result<()> {
+    let sink: Box<dyn Write> = match out {
+        Some(path) => Box::new(File::create(path).context("create export file")?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut writer = ExportWriter::new(format, BufWriter::new(sink));
+
+    let mut after: Option<String> = None;
+    let mut total = 0;
+    loop {
+        let page = contacts_page(api, audience_id, after.as_deref()).await?;
+
+        for contact in &page.data {
+            writer
+                .write(&ContactRecord::from(contact))
+                .context("write contacts")?;
+        }
+        total += page.data.len();
+
+        match page.data.last() {
+            Some(last) if page.has_more => after = Some(last.id.to_string()),
+            _ => break,
+        }
+    }
+    writer.finish().context("write contacts")?;
+
+    if out.is_some() {
+        eprintln!("Exported {total} contact(s).");
+    }
+    Ok(())
+}
+
+/// `list --export-to-audience`: creates a contact for each of `recipients`
+/// (bare, de-duplicated addresses) that the audience doesn't have yet.
+pub async fn add_recipients(api: &Api, audience_id: &str, recipients: Vec<String>) -> Result<()> {
+    let mut existing = HashSet::new();
+    let mut after: Option<String> = None;
+    loop {
+        let page = contacts_page(api, audience_id, after.as_deref()).await?;
+        existing.extend(page.data.iter().map(|c| c.email.to_lowercase()));
+        match page.data.last() {
+            Some(last) if page.has_more => after = Some(last.id.to_string()),
+            _ => break,
+        }
+    }
+
+    let (mut skipped, mut present) = (0, 0);
+    let mut new = Vec::new();
+    for email in recipients {
+        if !address::is_valid(&email) {
+            println!("skipped, invalid email {email:?}");
+            skipped += 1;
+        } else if existing.contains(&email.to_lowercase()) {
+            present += 1;
+        } else {
+            new.push(email);
+        }
+    }
+
+    // Same parallelism as `contacts import` uses by default.
+    let results = parallel::ordered(new, 4, |email| async move {
+        let contact = CreateContactOptions::new(&email).with_audience_id(audience_id);
+        let result = api
+            .call(
+                "POST /audiences/{id}/contacts",
+                || api.resend.contacts.create(contact.clone()),
+            )
+            .await;
+        (email, result)
+    })
+    .await;
+
+    let (mut created, mut failed) = (0, 0);
+    for (email, result) in results {
+        match result {
+            Ok(id) => {
+                println!("created {email} ({id})");
+                created += 1;
+            }
+            Err(e) => {
+                println!("failed {email}: {e}");
+                failed += 1;
+            }
+        }
+    }
+
+    println!(
+        "Added {created} contact(s) to audience {audience_id}, {present} already there, \
+         {failed} failed, {skipped} skipped."
+    );
+    if failed > 0 {
+        bail!("{failed} contact(s) could not be added");
+    }
+    Ok(())
+}
@@ -0,0 +1,336 @@
+use anyhow::{Context, Result, bail};
+use hickory_resolver::TokioAsyncResolver;
+use hickory_resolver::error::ResolveErrorKind;
+use hickory_resolver::proto::rr::{RData, RecordType};
+use resend_rs::types::{
+    DkimRecordType, Domain, DomainRecord, DomainStatus, Region, SpfRecordType,
+};
+use serde::Serialize;
+
+use crate::client::Api;
+use crate::output::Output;
+use crate::parallel;
+
+/// How one of a domain's required records looks in public DNS.
+#[derive(Serialize)]
+struct Check {
+    /// `SPF`, `DKIM` or `Receiving`, as the API groups them.
+    record: &'static str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    name: String,
+    expected: String,
+    /// `ok`, `missing`, `wrong` or `error`.
+    status: &'static str,
+    /// What DNS returned instead, or the lookup error.
+    found: Vec<String>,
+}
+
+/// `domains dns-check`: resolves every record the API asks the domain to
+/// publish and reports whether DNS already serves it.
+///
+/// Fails when any record is missing or wrong, so it can gate `verify` in a script.
+pub async fn check(api: &Api, domain_id: &str, out: Output) -> Result<()> {
+    let domain = api
+        .call("GET /domains/{id}", || api.resend.domains.get(domain_id))
+        .await
+        .context("get domain failed")?;
+    let records = domain.records.unwrap_or_default();
+    if records.is_empty() {
+        bail!("the API lists no DNS records for {}", domain.name);
+    }
+    let resolver = TokioAsyncResolver::tokio_from_system_conf()
+        .context("read the system DNS configuration")?;
+
+    let mut checks = Vec::with_capacity(records.len());
+    for record in &records {
+        let (group, kind, name, value) = match record {
+            DomainRecord::DomainSpfRecord(r) => {
+                let kind = match r.d_type {
+                    SpfRecordType::MX => "MX",
+                    SpfRecordType::TXT => "TXT",
+                };
+                ("SPF", kind, &r.name, &r.value)
+            }
+            DomainRecord::DomainDkimRecord(r) => {
+                let kind = match r.d_type {
+                    DkimRecordType::CNAME => "CNAME",
+                    DkimRecordType::TXT => "TXT",
+                };
+                ("DKIM", kind, &r.name, &r.value)
+            }
+            DomainRecord::ReceivingRecord(r) => ("Receiving", "MX", &r.name, &r.value),
+        };
+        let fqdn = fully_qualified(name, &domain.name);
+        let (status, found) = lookup(&resolver, kind, &fqdn, value).await;
+        checks.push(Check {
+            record: group,
+            kind,
+            name: fqdn,
+            expected: value.clone(),
+            status,
+            found,
+        });
+    }
+
+    if out.is_json() {
+        out.print_json(&checks)?;
+    } else {
+        let name_width = checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+        for c in &checks {
+            let detail = match c.status {
+                "ok" => String::new(),
+                "missing" => format!("  expected {}", c.expected),
+                _ => format!("  expected {}, found {}", c.expected, c.found.join(" | ")),
+            };
+            println!(
+                "{:<7} {:<9} {:<5} {:<name_width$}{detail}",
+                c.status.to_uppercase(),
+                c.record,
+                c.kind,
+                c.name
+            );
+        }
+    }
+    let bad = checks.iter().filter(|c| c.status != "ok").count();
+    if bad > 0 {
+        bail!(
+            "{bad} of {} DNS record(s) for {} are not published correctly",
+            checks.len(),
+            domain.name
+        );
+    }
+    if !out.is_json() {
+        println!("All {} record(s) are published as the API expects.", checks.len());
+    }
+    Ok(())
+}
+
+/// One domain as `domains list` shows it. The record columns are only there
+/// with `--check`.
+#[derive(Serialize)]
+struct Listed {
+    id: String,
+    name: String,
+    status: String,
+    region: Region,
+    created_at: String,
+    /// `ok`, `pending`, `failed` or `none`, from the record statuses the API reports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spf: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dkim: Option<&'static str>,
+    /// `ok`, `missing` or `error`: whether `_dmarc.<domain>` publishes a DMARC policy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dmarc: Option<&'static str>,
+}
+
+/// `domains list`: the account's domains, and with `check` which of SPF, DKIM
+/// and DMARC each has in place.
+///
+/// Domain details are fetched under `--max-concurrent`. Problems are reported,
+/// not treated as a failure.
+pub async fn list(api: &Api, check: bool, out: Output) -> Result<()> {
+    let domains = api
+        .call("GET /domains", || api.resend.domains.list(Default::default()))
+        .await
+        .context("list domains failed")?
+        .data;
+
+    let mut listed: Vec<Listed> = domains
+        .iter()
+        .map(|d| Listed {
+            id: d.id.to_string(),
+            name: d.name.clone(),
+            status: d.status.clone(),
+            region: d.region.clone(),
+            created_at: d.created_at.clone(),
+            spf: None,
+            dkim: None,
+            dmarc: None,
+        })
+        .collect();
+    if check {
+        let resolver = TokioAsyncResolver::tokio_from_system_conf()
+            .context("read the system DNS configuration")?;
+        let checked = parallel::fetch_all(&domains, |d| {
+            let resolver = &resolver;
+            async move {
+                let full = api
+                    .call("GET /domains/{id}", || api.resend.domains.get(&d.id))
+                    .await
+                    .with_context(|| format!("get domain {} failed", d.name))?;
+                let dmarc = dmarc(resolver, &d.name).await;
+                anyhow::Ok((full, dmarc))
+            }
+        })
+        .await;
+        for (row, result) in listed.iter_mut().zip(checked) {
+            let (full, dmarc) = result?;
+            row.spf =
+                Some(records_status(&full, |r| matches!(r, DomainRecord::DomainSpfRecord(_))));
+            row.dkim =
+                Some(records_status(&full, |r| matches!(r, DomainRecord::DomainDkimRecord(_))));
+            row.dmarc = Some(dmarc);
+        }
+    }
+
+    if out.is_json() {
+        return out.print_json(&listed);
+    }
+    if listed.is_empty() {
+        println!("No domains on this account.");
+        return Ok(());
+    }
+    let name_width = listed.iter().map(|d| d.name.len()).max().unwrap_or(0).max(4);
+    let status_width = listed.iter().map(|d| d.status.len()).max().unwrap_or(0).max(6);
+    if check {
+        println!(
+            "{:<name_width$}  {:<status_width$}  {:<7}  {:<7}  {:<7}  ID",
+            "NAME", "STATUS", "SPF", "DKIM", "DMARC"
+        );
+    } else {
+        println!("{:<name_width$}  {:<status_width$}  {:<10}  ID", "NAME", "STATUS", "CREATED");
+    }
+    for d in &listed {
+        if check {
+            println!(
+                "{:<name_width$}  {:<status_width$}  {:<7}  {:<7}  {:<7}  {}",
+                d.name,
+                d.status,
+                d.spf.unwrap_or_default(),
+                d.dkim.unwrap_or_default(),
+                d.dmarc.unwrap_or_default(),
+                d.id
+            );
+        } else {
+            let created = out.time(&d.created_at);
+            println!(
+                "{:<name_width$}  {:<status_width$}  {created:<10}  {}",
+                d.name, d.status, d.id
+            );
+        }
+    }
+    if check {
+        let healthy = listed
+            .iter()
+            .filter(|d| d.status == "verified")
+            .filter(|d| [d.spf, d.dkim, d.dmarc].iter().all(|s| *s == Some("ok")))
+            .count();
+        println!(
+            "{healthy} of {} domain(s) verified with SPF, DKIM and DMARC in place.",
+            listed.len()
+        );
+    }
+    Ok(())
+}
+
+/// Sums up the statuses of the records of one kind: `ok` once all are verified.
+fn records_status(domain: &Domain, kind: impl Fn(&DomainRecord) -> bool) -> &'static str {
+    let statuses: Vec<DomainStatus> = domain
+        .records
+        .iter()
+        .flatten()
+        .filter(|r| kind(r))
+        .map(|r| match r {
+            DomainRecord::DomainSpfRecord(r) => r.status,
+            DomainRecord::DomainDkimRecord(r) => r.status,
+            DomainRecord::ReceivingRecord(r) => r.status,
+        })
+        .collect();
+    if statuses.is_empty() {
+        "none"
+    } else if statuses.iter().all(|s| matches!(s, DomainStatus::Verified)) {
+        "ok"
+    } else if statuses
+        .iter()
+        .any(|s| matches!(s, DomainStatus::Failed | DomainStatus::TemporaryFailure))
+    {
+        "failed"
+    } else {
+        "pending"
+    }
+}
+
+/// The API doesn't manage DMARC, so it is looked up in DNS: a TXT record at
+/// `_dmarc.<domain>` starting with `v=DMARC1`.
+async fn dmarc(resolver: &TokioAsyncResolver, domain: &str) -> &'static str {
+    match resolver.txt_lookup(format!("_dmarc.{domain}.")).await {
+        Ok(txt) => {
+            let policy = txt.iter().any(|record| {
+                let value: String =
+                    record.txt_data().iter().map(|part| String::from_utf8_lossy(part)).collect();
+                value.trim_start().to_ascii_lowercase().starts_with("v=dmarc1")
+            });
+            if policy { "ok" } else { "missing" }
+        }
+        Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => "missing",
+        Err(_) => "error",
+    }
+}
+
+/// The API gives record names relative to the domain (`send`, `resend._domainkey`).
+fn fully_qualified(name: &str, domain: &str) -> String {
+    let name = name.trim_end_matches('.');
+    let lower = name.to_ascii_lowercase();
+    let domain_lower = domain.to_ascii_lowercase();
+    if name.is_empty() || name == "@" {
+        domain.to_string()
+    } else if lower == domain_lower || lower.ends_with(&format!(".{domain_lower}")) {
+        name.to_string()
+    } else {
+        format!("{name}.{domain}")
+    }
+}
+
+/// Looks `name` up and compares what DNS serves with `expected`.
+async fn lookup(
+    resolver: &TokioAsyncResolver,
+    kind: &str,
+    name: &str,
+    expected: &str,
+) -> (&'static str, Vec<String>) {
+    let record_type = match kind {
+        "MX" => RecordType::MX,
+        "CNAME" => RecordType::CNAME,
+        _ => RecordType::TXT,
+    };
+    let lookup = match resolver.lookup(format!("{name}."), record_type).await {
+        Ok(lookup) => lookup,
+        Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => {
+            return ("missing", Vec::new());
+        }
+        Err(e) => return ("error", vec![e.to_string()]),
+    };
+    let found: Vec<String> = lookup
+        .iter()
+        .filter_map(|data| match data {
+            RData::TXT(txt) => Some(
+                txt.txt_data()
+                    .iter()
+                    .map(|part| String::from_utf8_lossy(part))
+                    .collect(),
+            ),
+            RData::MX(mx) => Some(mx.exchange().to_string()),
+            RData::CNAME(cname) => Some(cname.0.to_string()),
+            _ => None,
+        })
+        .collect();
+    // Host names compare without case or the root dot; TXT values exactly.
+    let expected = expected.trim().trim_end_matches('.');
+    let matches = |value: &String| {
+        let value = value.trim().trim_end_matches('.');
+        if record_type == RecordType::TXT {
+            value == expected
+        } else {
+            value.eq_ignore_ascii_case(expected)
+        }
+    };
+    if found.iter().any(matches) {
+        ("ok", Vec::new())
+    } else if found.is_empty() {
+        ("missing", Vec::new())
+    } else {
+        ("wrong", found)
+    }
+}
@@ -0,0 +1,165 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::address;
+use crate::client::Api;
+
+/// Resend's shared testing domain, usable without being added to the account.
+const TESTING_DOMAIN: &str = "resend.dev";
+
+/// Checks From addresses against the domains registered on the account.
+///
+/// The domain list goes through [`Api::cached`], so a batch only costs a
+/// single `domains.list` call however many messages it holds.
+#[derive(Default)]
+pub struct DomainPreflight {
+    /// The problem found with each domain looked at so far, if any.
+    checked: HashMap<String, Option<String>>,
+}
+
+impl DomainPreflight {
+    /// Warns (or fails when `strict`) if the domain of `from` isn't verified.
+    ///
+    /// A warning is printed once per domain.
+    pub async fn check(&mut self, api: &Api, from: &str, strict: bool) -> Result<()> {
+        let seen = self.checked.contains_key(&domain_of(from)?);
+        match self.problem(api, from).await? {
+            None => Ok(()),
+            Some(msg) if strict => bail!(msg),
+            Some(_) if seen => Ok(()),
+            Some(msg) => {
+                eprintln!("warning: {msg}");
+                Ok(())
+            }
+        }
+    }
+
+    /// Why mail can't be sent from the domain of `from`, or `None` if it can.
+    pub async fn problem(&mut self, api: &Api, from: &str) -> Result<Option<String>> {
+        let domain = domain_of(from)?;
+        if domain == TESTING_DOMAIN {
+            return Ok(None);
+        }
+        if let Some(problem) = self.checked.get(&domain) {
+            return Ok(problem.clone());
+        }
+
+        let domains = api
+            .cached("GET /domains", || api.resend.domains.list(Default::default()))
+            .await
+            .context("list domains for preflight")?
+            .data;
+
+        let problem = match domains.iter().find(|d| d.name.eq_ignore_ascii_case(&domain)) {
+            Some(d) if d.status == "verified" => None,
+            Some(d) => Some(format!(
+                "From domain {domain} is not verified (status: {})",
+                d.status
+            )),
+            None => Some(format!("From domain {domain} is not added to this account")),
+        };
+        self.checked.insert(domain, problem.clone());
+        Ok(problem)
+    }
+}
+
+/// The names of the account's verified domains, for the From picker.
+pub async fn verified(api: &Api) -> Result<Vec<String>> {
+    let domains = api
+        .cached("GET /domains", || api.resend.domains.list(Default::default()))
+        .await
+        .context("list domains")?
+        .data;
+    Ok(domains
+        .into_iter()
+        .filter(|d| d.status == "verified")
+        .map(|d| d.name)
+        .collect())
+}
+
+/// How long `send --only-if-verified` trusts its cached list of verified domains.
+const VERIFIED_CACHE_TTL_SECS: u64 = 15 * 60;
+
+/// The verified domains on the account as of `fetched_at` (seconds since the epoch).
+#[derive(Serialize, Deserialize)]
+struct VerifiedCache {
+    fetched_at: u64,
+    domains: Vec<String>,
+}
+
+/// `send --only-if-verified`: fails unless the domain of `from` is verified.
+///
+/// The verified domains are kept in the file at `cache` for
+/// [`VERIFIED_CACHE_TTL_SECS`]. A domain missing from a fresh cache is looked
+/// up again, since it may have been verified since.
+pub async fn require_verified(api: &Api, from: &str, cache: &Path) -> Result<()> {
+    let domain = domain_of(from)?;
+    if domain == TESTING_DOMAIN {
+        return Ok(());
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    if let Some(cached) = load_cache(cache)?
+        && now.saturating_sub(cached.fetched_at) < VERIFIED_CACHE_TTL_SECS
+        && cached.domains.contains(&domain)
+    {
+        eprintln!(
+            "From domain {domain} is verified (cached {}s ago).",
+            now.saturating_sub(cached.fetched_at)
+        );
+        return Ok(());
+    }
+
+    let domains = api
+        .call("GET /domains", || api.resend.domains.list(Default::default()))
+        .await
+        .context("list domains for --only-if-verified")?
+        .data;
+    let verified = VerifiedCache {
+        fetched_at: now,
+        domains: domains
+            .iter()
+            .filter(|d| d.status == "verified")
+            .map(|d| d.name.to_ascii_lowercase())
+            .collect(),
+    };
+    save_cache(cache, &verified)?;
+    if verified.domains.contains(&domain) {
+        return Ok(());
+    }
+    match domains.iter().find(|d| d.name.eq_ignore_ascii_case(&domain)) {
+        Some(d) => bail!("From domain {domain} is not verified (status: {}), not sending", d.status),
+        None => bail!("From domain {domain} is not added to this account, not sending"),
+    }
+}
+
+fn load_cache(path: &Path) -> Result<Option<VerifiedCache>> {
+    match fs::read_to_string(path) {
+        // A cache that doesn't parse is treated as expired and rewritten.
+        Ok(content) => Ok(serde_json::from_str(&content).ok()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).context("read verified domains cache"),
+    }
+}
+
+/// Writes the cache through a temp file so a concurrent send never reads half of it.
+fn save_cache(path: &Path, cache: &VerifiedCache) -> Result<()> {
+    let dir = path.parent().context("cache file has no parent directory")?;
+    let mut file = tempfile::NamedTempFile::new_in(dir).context("create temp cache file")?;
+    file.write_all(serde_json::to_string(cache)?.as_bytes())
+        .context("write verified domains cache")?;
+    file.persist(path).context("replace verified domains cache")?;
+    Ok(())
+}
+
+fn domain_of(from: &str) -> Result<String> {
+    Ok(address::domain(from)
+        .with_context(|| format!("cannot determine the domain of From address {from:?}"))?
+        .to_ascii_lowercase())
+}
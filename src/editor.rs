@@ -0,0 +1,44 @@
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::io::Write;
+use std::process::Command;
+
+/// Opens the user's editor on a temporary file holding `initial` and returns
+/// what was saved.
+///
+/// Uses `$VISUAL`, then `$EDITOR`, falling back to `vi` (`notepad` on Windows).
+/// The editor command may carry arguments, e.g. `code --wait`. `extension`
+/// (such as `html`) lets the editor pick the right syntax mode.
+pub fn edit(initial: &str, extension: &str) -> Result<String> {
+    let mut file = tempfile::Builder::new()
+        .prefix("rusend-")
+        .suffix(&format!(".{extension}"))
+        .tempfile()
+        .context("create temporary file for editor")?;
+    file.write_all(initial.as_bytes())
+        .context("write temporary file for editor")?;
+    file.flush().context("write temporary file for editor")?;
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| default_editor().to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or_else(|| default_editor());
+
+    let status = Command::new(program)
+        .args(parts)
+        .arg(file.path())
+        .status()
+        .with_context(|| format!("launch editor {program:?}"))?;
+    if !status.success() {
+        bail!("editor {program:?} exited with {status}");
+    }
+
+    fs::read_to_string(file.path()).context("read back edited file")
+}
+
+fn default_editor() -> &'static str {
+    if cfg!(windows) { "notepad" } else { "vi" }
+}
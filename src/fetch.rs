@@ -0,0 +1,62 @@
+use anyhow::{Context, Result, bail};
+use reqwest::Url;
+use std::time::Duration;
+
+/// Largest HTML body `--html-url` accepts.
+const MAX_BYTES: usize = 5 * 1024 * 1024;
+
+/// Largest attachment `received-forward` downloads, the most Resend accepts in one email.
+const MAX_ATTACHMENT_BYTES: usize = 40 * 1024 * 1024;
+
+/// How long `--html-url` waits for the whole response.
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+/// How long downloading one attachment may take.
+const ATTACHMENT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Downloads an HTML body from an `http` or `https` URL.
+///
+/// Fails on a non-success status, after [`TIMEOUT`], or once the body grows past
+/// [`MAX_BYTES`]. Nothing is cached; every send fetches again.
+pub async fn html(url: &str) -> Result<String> {
+    let parsed = Url::parse(url).with_context(|| format!("invalid URL {url:?}"))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        bail!("--html-url must be an http or https URL, got {url:?}");
+    }
+    let body = download(parsed, TIMEOUT, MAX_BYTES).await?;
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Downloads a received email's attachment from the `download_url` the API gives.
+pub async fn attachment(url: &str) -> Result<Vec<u8>> {
+    let parsed = Url::parse(url).with_context(|| format!("invalid download URL {url:?}"))?;
+    download(parsed, ATTACHMENT_TIMEOUT, MAX_ATTACHMENT_BYTES).await
+}
+
+async fn download(parsed: Url, timeout: Duration, max_bytes: usize) -> Result<Vec<u8>> {
+    let url = parsed.to_string();
+    let client = reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .context("build HTTP client")?;
+    let mut response = client
+        .get(parsed)
+        .send()
+        .await
+        .with_context(|| format!("fetch {url}"))?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!("fetch {url} returned {status}");
+    }
+    if response.content_length().is_some_and(|len| len > max_bytes as u64) {
+        bail!("{url} is larger than the {} MB limit", max_bytes / (1024 * 1024));
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.with_context(|| format!("read {url}"))? {
+        body.extend_from_slice(&chunk);
+        if body.len() > max_bytes {
+            bail!("{url} is larger than the {} MB limit", max_bytes / (1024 * 1024));
+        }
+    }
+    Ok(body)
+}
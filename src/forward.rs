@@ -0,0 +1,104 @@
+use anyhow::{Context, Result};
+use resend_rs::types::{CreateAttachment, CreateEmailBaseOptions, InboundEmail};
+use serde::Deserialize;
+
+use crate::client::Api;
+use crate::{fetch, parallel, template};
+
+/// An attachment as `GET /emails/receiving/{id}/attachments` lists it. The
+/// typed model of resend-rs leaves out the download URL, so the list is read raw.
+#[derive(Deserialize)]
+struct Listed {
+    filename: String,
+    content_type: String,
+    download_url: String,
+}
+
+#[derive(Deserialize)]
+struct Attachments {
+    data: Vec<Listed>,
+}
+
+/// `received-forward`: `email` sent on to `to`, with an optional note above a
+/// "Forwarded message" block and the original bodies and attachments.
+pub async fn message(
+    api: &Api,
+    email: &InboundEmail,
+    from: &str,
+    to: Vec<String>,
+    note_html: Option<&str>,
+    note_text: Option<&str>,
+) -> Result<CreateEmailBaseOptions> {
+    let subject = if email.subject.to_ascii_lowercase().starts_with("fwd:") {
+        email.subject.clone()
+    } else {
+        format!("Fwd: {}", email.subject)
+    };
+    let mut message = CreateEmailBaseOptions::new(from, to, subject);
+
+    let fields = header_fields(email);
+    let mut text = note_text.map(|note| format!("{note}\n\n")).unwrap_or_default();
+    text.push_str("---------- Forwarded message ----------\n");
+    for (name, value) in &fields {
+        text.push_str(&format!("{name}: {value}\n"));
+    }
+    text.push('\n');
+    text.push_str(email.text.as_deref().unwrap_or_default());
+    message = message.with_text(&text);
+
+    if let Some(original) = &email.html {
+        let note = match (note_html, note_text) {
+            (Some(html), _) => format!("<div>{html}</div><br>"),
+            (None, Some(text)) => format!("<div>{}</div><br>", template::escape(text)),
+            (None, None) => String::new(),
+        };
+        let mut html = format!("{note}<div>---------- Forwarded message ----------<br>");
+        for (name, value) in &fields {
+            html.push_str(&format!("{name}: {}<br>", template::escape(value)));
+        }
+        html.push_str("</div><br>");
+        html.push_str(original);
+        message = message.with_html(&html);
+    }
+
+    for attachment in attachments(api, &email.id).await? {
+        message = message.with_attachment(attachment);
+    }
+    Ok(message)
+}
+
+/// The lines of the "Forwarded message" block, as mail clients write them.
+fn header_fields(email: &InboundEmail) -> Vec<(&'static str, String)> {
+    let mut fields = vec![
+        ("From", email.from.clone()),
+        ("Date", email.created_at.clone()),
+        ("Subject", email.subject.clone()),
+        ("To", email.to.join(", ")),
+    ];
+    if !email.cc.is_empty() {
+        fields.push(("Cc", email.cc.join(", ")));
+    }
+    fields
+}
+
+/// Downloads every attachment of the received email `id`, up to
+/// `--max-concurrent` at a time.
+async fn attachments(api: &Api, id: &str) -> Result<Vec<CreateAttachment>> {
+    let path = format!("/emails/receiving/{id}/attachments");
+    let body = api.get_raw("GET /emails/receiving/{id}/attachments", &path).await?;
+    let listed: Attachments =
+        serde_json::from_str(&body).context("read the attachments of the received email")?;
+    let downloads = parallel::fetch_all(&listed.data, |a| async move {
+        api.trace(&format!("downloading attachment {}", a.filename));
+        let content = fetch::attachment(&a.download_url)
+            .await
+            .with_context(|| format!("download attachment {}", a.filename))?;
+        anyhow::Ok(
+            CreateAttachment::from_content(content)
+                .with_filename(&a.filename)
+                .with_content_type(&a.content_type),
+        )
+    })
+    .await;
+    downloads.into_iter().collect()
+}
@@ -0,0 +1,126 @@
+use clap::{Args, ValueEnum};
+use reqwest::Url;
+
+/// Custom headers for `send`: raw `--header` values and typed shortcuts for
+/// the common ones.
+#[derive(Args, Default)]
+pub struct HeaderArgs {
+    /// Add a header, e.g. `X-Campaign: spring` (repeatable; wins over the shortcuts below)
+    #[arg(long = "header", value_name = "NAME: VALUE", value_parser = parse_header)]
+    pub headers: Vec<(String, String)>,
+
+    /// Set X-Priority: 1 (Highest), 3 (Normal) or 5 (Lowest)
+    #[arg(long, value_enum)]
+    pub priority: Option<Priority>,
+
+    /// Set Importance (as Outlook reads it), and X-Priority to match unless --priority is given
+    #[arg(long, value_enum)]
+    pub importance: Option<Priority>,
+
+    /// Set List-Unsubscribe: <URL>, plus List-Unsubscribe-Post for https URLs
+    #[arg(long, value_name = "URL", value_parser = parse_unsubscribe_url)]
+    pub list_unsubscribe: Option<Url>,
+
+    /// RFC 8058 one-click unsubscribe: both List-Unsubscribe headers, https only
+    #[arg(
+        long,
+        value_name = "URL",
+        value_parser = parse_one_click_url,
+        conflicts_with = "list_unsubscribe"
+    )]
+    pub unsubscribe_url: Option<Url>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Priority {
+    High,
+    Normal,
+    Low,
+}
+
+impl Priority {
+    fn header_value(self) -> &'static str {
+        match self {
+            Self::High => "1 (Highest)",
+            Self::Normal => "3 (Normal)",
+            Self::Low => "5 (Lowest)",
+        }
+    }
+
+    /// The value of the `Importance` header (RFC 2156).
+    fn importance(self) -> &'static str {
+        match self {
+            Self::High => "high",
+            Self::Normal => "normal",
+            Self::Low => "low",
+        }
+    }
+}
+
+impl HeaderArgs {
+    /// The headers to send: the shortcuts first, then each `--header`, which
+    /// replaces an earlier header of the same name (compared ignoring case).
+    pub fn resolve(&self) -> Vec<(String, String)> {
+        let mut resolved = Vec::new();
+        if let Some(priority) = self.priority.or(self.importance) {
+            set(&mut resolved, "X-Priority", priority.header_value());
+        }
+        if let Some(importance) = self.importance {
+            set(&mut resolved, "Importance", importance.importance());
+        }
+        if let Some(url) = self.list_unsubscribe.as_ref().or(self.unsubscribe_url.as_ref()) {
+            set(&mut resolved, "List-Unsubscribe", &format!("<{url}>"));
+            if url.scheme() == "https" {
+                set(&mut resolved, "List-Unsubscribe-Post", "List-Unsubscribe=One-Click");
+            }
+        }
+        for (name, value) in &self.headers {
+            set(&mut resolved, name, value);
+        }
+        resolved
+    }
+}
+
+fn set(headers: &mut Vec<(String, String)>, name: &str, value: &str) {
+    headers.retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+    headers.push((name.to_string(), value.to_string()));
+}
+
+/// Parses a `--header` as `Name: value`.
+fn parse_header(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected NAME: VALUE, got {s:?}"))?;
+    let name = name.trim();
+    let token = |c: char| c.is_ascii_graphic() && c != ':';
+    if name.is_empty() || !name.chars().all(token) {
+        return Err(format!("invalid header name {name:?}"));
+    }
+    let value = value.trim();
+    if value.contains(['\r', '\n']) {
+        return Err(format!("header {name} has a line break in its value"));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Parses a `--list-unsubscribe` URL, accepting `https`, `http` and `mailto`.
+fn parse_unsubscribe_url(s: &str) -> Result<Url, String> {
+    let url = Url::parse(s).map_err(|e| e.to_string())?;
+    match url.scheme() {
+        "https" | "http" | "mailto" => Ok(url),
+        other => Err(format!("unsupported scheme {other:?}, use https or mailto")),
+    }
+}
+
+/// Parses an `--unsubscribe-url`. RFC 8058 requires one-click unsubscribe to
+/// POST to an HTTPS URI, so nothing else is accepted.
+fn parse_one_click_url(s: &str) -> Result<Url, String> {
+    let url = Url::parse(s).map_err(|e| e.to_string())?;
+    if url.scheme() != "https" {
+        return Err(format!("one-click unsubscribe needs an https URL, got {:?}", url.scheme()));
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        return Err("URL has no host".to_string());
+    }
+    Ok(url)
+}
@@ -0,0 +1,100 @@
+use anyhow::{Context, Result, bail};
+use resend_rs::types::CreateAttachment;
+use std::fs;
+use std::path::Path;
+
+/// Reads an iCalendar file for `send --ics`, returning it as an attachment
+/// and its size in bytes.
+///
+/// The content type carries the calendar's `METHOD` (`REQUEST` when it has
+/// none), which is what makes mail clients show an invite with RSVP buttons
+/// instead of a plain file.
+pub fn load(path: &Path) -> Result<(CreateAttachment, u64)> {
+    let content = fs::read(path).with_context(|| format!("read {}", path.display()))?;
+    let text = std::str::from_utf8(&content)
+        .with_context(|| format!("{} is not UTF-8 text", path.display()))?;
+    let method =
+        validate(text).with_context(|| format!("{} is not an iCalendar file", path.display()))?;
+    let filename = path.file_name().map_or_else(
+        || "invite.ics".to_string(),
+        |n| n.to_string_lossy().into_owned(),
+    );
+    let size = content.len() as u64;
+    let attachment = CreateAttachment::from_content(content)
+        .with_filename(&filename)
+        .with_content_type(&format!("text/calendar; charset=UTF-8; method={method}"));
+    Ok((attachment, size))
+}
+
+/// Checks the calendar's structure and returns its `METHOD`.
+///
+/// Needs a single `VCALENDAR` with `VERSION:2.0` around at least one `VEVENT`,
+/// and balanced `BEGIN`/`END` lines.
+fn validate(text: &str) -> Result<String> {
+    let lines = unfold(text);
+    if lines.first().map(String::as_str) != Some("BEGIN:VCALENDAR") {
+        bail!("it doesn't start with BEGIN:VCALENDAR");
+    }
+
+    let mut open: Vec<&str> = Vec::new();
+    let (mut version, mut method, mut events) = (None, None, 0);
+    for (i, line) in lines.iter().enumerate() {
+        let (name, value) = property(line)
+            .with_context(|| format!("line {} is not a NAME:VALUE property", i + 1))?;
+        match name.as_str() {
+            "BEGIN" => {
+                if open.is_empty() && i > 0 {
+                    bail!("line {}: content after END:VCALENDAR", i + 1);
+                }
+                open.push(value);
+            }
+            "END" => match open.pop() {
+                Some(component) if component.eq_ignore_ascii_case(value) => {
+                    if component.eq_ignore_ascii_case("VEVENT") {
+                        events += 1;
+                    }
+                }
+                Some(component) => bail!("line {}: END:{value} closes {component}", i + 1),
+                None => bail!("line {}: END:{value} without BEGIN", i + 1),
+            },
+            "VERSION" if open.len() == 1 => version = Some(value),
+            "METHOD" if open.len() == 1 => method = Some(value.to_ascii_uppercase()),
+            _ if open.is_empty() => bail!("line {}: content after END:VCALENDAR", i + 1),
+            _ => {}
+        }
+    }
+    if let Some(component) = open.last() {
+        bail!("BEGIN:{component} is never closed");
+    }
+    if version != Some("2.0") {
+        bail!("VERSION:2.0 is missing");
+    }
+    if events == 0 {
+        bail!("it has no VEVENT");
+    }
+    Ok(method.unwrap_or_else(|| "REQUEST".to_string()))
+}
+
+/// Joins folded lines (RFC 5545 3.1) and drops blank ones.
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for raw in text.split('\n') {
+        let raw = raw.strip_suffix('\r').unwrap_or(raw);
+        match (raw.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ if raw.trim().is_empty() => {}
+            _ => lines.push(raw.to_string()),
+        }
+    }
+    lines
+}
+
+/// Splits a content line into its upper-cased name (parameters dropped) and value.
+fn property(line: &str) -> Option<(String, &str)> {
+    let (head, value) = line.split_once(':')?;
+    let name = head.split(';').next()?;
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return None;
+    }
+    Some((name.to_ascii_uppercase(), value.trim()))
+}
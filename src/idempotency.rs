@@ -0,0 +1,34 @@
+use sha2::{Digest, Sha256};
+
+/// A stable idempotency key for a message: a SHA-256 over From, the
+/// recipients, Subject and both bodies, so re-running an identical send yields
+/// the same key and Resend drops the repeat.
+///
+/// Recipients are compared as a set (case-insensitive, in any order).
+pub fn from_content(
+    from: &str,
+    to: &[String],
+    subject: &str,
+    html: Option<&str>,
+    text: Option<&str>,
+) -> String {
+    let mut recipients: Vec<String> = to.iter().map(|r| r.trim().to_lowercase()).collect();
+    recipients.sort();
+    recipients.dedup();
+
+    let mut hasher = Sha256::new();
+    for part in [
+        from,
+        &recipients.join(","),
+        subject,
+        html.unwrap_or_default(),
+        text.unwrap_or_default(),
+    ] {
+        // Length-prefixed so moving text between fields changes the key.
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    let digest = hasher.finalize();
+    let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+    format!("rusend-{hex}")
+}
@@ -0,0 +1,118 @@
+/// Link shorteners and redirectors that spam filters commonly score against.
+const SHORTENERS: &[&str] = &[
+    "bit.ly", "bitly.com", "buff.ly", "cutt.ly", "goo.gl", "is.gd", "ow.ly", "rb.gy",
+    "rebrand.ly", "shorturl.at", "t.co", "t.ly", "tiny.cc", "tinyurl.com", "v.gd", "lnkd.in",
+    "s.id", "shorte.st", "adf.ly", "bl.ink", "clck.ru", "qr.ae", "soo.gd", "trib.al",
+];
+
+/// The URLs in `body` whose host is a known link shortener, each once in
+/// order of first appearance.
+///
+/// Any `http://` or `https://` run up to whitespace, a quote or a bracket is
+/// taken as a URL, so links in HTML attributes and in plain text both count.
+pub fn shortened(body: &str) -> Vec<String> {
+    let mut found: Vec<String> = Vec::new();
+    let lower = body.to_ascii_lowercase();
+    let mut pos = 0;
+    while let Some(offset) = lower[pos..].find("http") {
+        let start = pos + offset;
+        let rest = &lower[start..];
+        let Some(after_scheme) = rest
+            .strip_prefix("https://")
+            .or_else(|| rest.strip_prefix("http://"))
+        else {
+            pos = start + 4;
+            continue;
+        };
+        let end = rest
+            .find(|c: char| c.is_whitespace() || "\"'<>()[]".contains(c))
+            .unwrap_or(rest.len());
+        let host = after_scheme
+            .split(['/', '?', '#', ':'])
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches("www.");
+        // Sentence punctuation right after a URL in text isn't part of it.
+        let url = body[start..start + end].trim_end_matches(['.', ',', ';', '!', '?']);
+        if SHORTENERS.contains(&host) && !found.iter().any(|f| f == url) {
+            found.push(url.to_string());
+        }
+        pos = start + end.max(4);
+    }
+    found
+}
+
+/// Query parameters `--strip-tracking-params` removes, besides every `utm_*`.
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "gclsrc", "dclid", "msclkid", "yclid", "mc_cid", "mc_eid", "_hsenc",
+    "_hsmi",
+];
+
+/// Removes tracking query parameters (`utm_*`, `fbclid`, `gclid`, ...) from
+/// the `http(s)` links in the `href` attributes of `html`, and returns the new
+/// HTML with the number of links changed.
+///
+/// Links that don't parse as URLs, e.g. template placeholders, are left as
+/// they are, and so is every link that carries no tracking parameter.
+pub fn strip_tracking(html: &str) -> (String, usize) {
+    let lower = html.to_ascii_lowercase();
+    let mut out = String::with_capacity(html.len());
+    let mut changed = 0;
+    let mut pos = 0;
+    while let Some(offset) = lower[pos..].find("href") {
+        let name_end = pos + offset + "href".len();
+        let after_name = &html[name_end..];
+        let trimmed = after_name.trim_start();
+        let Some(value) = trimmed.strip_prefix('=').map(str::trim_start) else {
+            out.push_str(&html[pos..name_end]);
+            pos = name_end;
+            continue;
+        };
+        let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            out.push_str(&html[pos..name_end]);
+            pos = name_end;
+            continue;
+        };
+        let start = html.len() - value.len() + 1;
+        let Some(len) = html[start..].find(quote) else {
+            break;
+        };
+        out.push_str(&html[pos..start]);
+        let link = &html[start..start + len];
+        match without_tracking(link) {
+            Some(clean) => {
+                out.push_str(&clean);
+                changed += 1;
+            }
+            None => out.push_str(link),
+        }
+        pos = start + len;
+    }
+    out.push_str(&html[pos..]);
+    (out, changed)
+}
+
+/// `link` (as written in the attribute) without its tracking parameters, or
+/// `None` when it has none to remove.
+fn without_tracking(link: &str) -> Option<String> {
+    // `&` usually appears escaped in HTML attributes; keep it that way.
+    let escaped = link.contains("&amp;");
+    let mut url = reqwest::Url::parse(&link.replace("&amp;", "&")).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    let query = url.query()?;
+    let tracking = |pair: &&str| {
+        let key = pair.split('=').next().unwrap_or_default().to_ascii_lowercase();
+        key.starts_with("utm_") || TRACKING_PARAMS.contains(&key.as_str())
+    };
+    let pairs: Vec<&str> = query.split('&').collect();
+    if !pairs.iter().any(tracking) {
+        return None;
+    }
+    let kept: Vec<&str> = pairs.into_iter().filter(|p| !tracking(p) && !p.is_empty()).collect();
+    let kept = kept.join("&");
+    url.set_query((!kept.is_empty()).then_some(kept.as_str()));
+    let clean = url.to_string();
+    Some(if escaped { clean.replace('&', "&amp;") } else { clean })
+}
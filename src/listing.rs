@@ -0,0 +1,334 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use resend_rs::list_opts::{ListOptions, ListResponse};
+use resend_rs::types::{Email, InboundEmail};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::address;
+use crate::bookmark::Bookmark;
+use crate::client::Api;
+use crate::output::{self, Output};
+use crate::timestamp;
+
+/// Largest page size the list endpoints accept.
+const PAGE_SIZE: u8 = 100;
+
+/// Order in which `list`/`received-list` print their rows.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "snake_case")]
+pub enum ListSort {
+    /// Oldest first
+    CreatedAsc,
+    /// Newest first
+    #[default]
+    CreatedDesc,
+    /// By subject, case-insensitively
+    Subject,
+}
+
+/// A column `list --fields` can show.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ListField {
+    Id,
+    Created,
+    From,
+    To,
+    Cc,
+    Bcc,
+    Subject,
+    /// The latest delivery event, e.g. `delivered`
+    Status,
+    Scheduled,
+}
+
+/// The columns `list` shows without `--fields`.
+pub const DEFAULT_FIELDS: &[ListField] =
+    &[ListField::Id, ListField::Created, ListField::From, ListField::To];
+
+impl ListField {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Id => "ID",
+            Self::Created => "Created",
+            Self::From => "From",
+            Self::To => "To",
+            Self::Cc => "Cc",
+            Self::Bcc => "Bcc",
+            Self::Subject => "Subject",
+            Self::Status => "Status",
+            Self::Scheduled => "Scheduled",
+        }
+    }
+
+    fn value(self, email: &Email, out: Output) -> String {
+        match self {
+            Self::Id => email.id.to_string(),
+            Self::Created => out.time(&email.created_at),
+            Self::From => email.from.clone(),
+            Self::To => format!("{:?}", email.to),
+            Self::Cc => format!("{:?}", email.cc),
+            Self::Bcc => format!("{:?}", email.bcc),
+            Self::Subject => email.subject.clone(),
+            Self::Status => output::event_name(email.last_event).to_string(),
+            Self::Scheduled => email.scheduled_at.as_deref().map_or("-".into(), |at| out.time(at)),
+        }
+    }
+}
+
+/// One human-readable `list` line with `fields` in the given order.
+pub fn row(email: &Email, fields: &[ListField], out: Output) -> String {
+    fields
+        .iter()
+        .map(|f| format!("{}: {}", f.label(), f.value(email, out)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// What a listing row exposes for sorting.
+pub trait Listed {
+    fn created_at(&self) -> &str;
+    fn subject(&self) -> &str;
+}
+
+impl Listed for Email {
+    fn created_at(&self) -> &str {
+        &self.created_at
+    }
+    fn subject(&self) -> &str {
+        &self.subject
+    }
+}
+
+impl Listed for InboundEmail {
+    fn created_at(&self) -> &str {
+        &self.created_at
+    }
+    fn subject(&self) -> &str {
+        &self.subject
+    }
+}
+
+/// A single page request big enough for `count` rows (the API caps it at 100).
+pub fn page_for(count: usize) -> ListOptions {
+    ListOptions::default().with_limit(u8::try_from(count.min(100)).unwrap_or(100))
+}
+
+/// One page of at most `count` sent emails, starting after the email with ID
+/// `after` when given.
+pub async fn sent_page(
+    api: &Api,
+    count: usize,
+    after: Option<&str>,
+) -> Result<ListResponse<Email>> {
+    let opts = page_for(count);
+    match after {
+        None => api.call("GET /emails", || api.resend.emails.list(opts.clone())).await,
+        Some(id) => {
+            api.call("GET /emails", || api.resend.emails.list(opts.clone().list_after(id)))
+                .await
+        }
+    }
+    .context("list failed")
+}
+
+/// Like [`sent_page`], for received emails.
+pub async fn received_page(
+    api: &Api,
+    count: usize,
+    after: Option<&str>,
+) -> Result<ListResponse<InboundEmail>> {
+    let opts = page_for(count);
+    match after {
+        None => {
+            api.call("GET /emails/receiving", || api.resend.receiving.list(opts.clone()))
+                .await
+        }
+        Some(id) => {
+            api.call("GET /emails/receiving", || {
+                api.resend.receiving.list(opts.clone().list_after(id))
+            })
+            .await
+        }
+    }
+    .context("list receiving failed")
+}
+
+/// The newest `want` sent emails that `keep` accepts, looking through at most
+/// `scan` emails starting after `after`.
+///
+/// Also returns the ID of the last email looked at when the scan stopped with
+/// more left, so the search can resume from there with `--after`.
+pub async fn filter_sent(
+    api: &Api,
+    after: Option<&str>,
+    scan: usize,
+    want: usize,
+    keep: impl Fn(&Email) -> bool,
+) -> Result<(Vec<Email>, Option<String>)> {
+    let mut kept = Vec::new();
+    let mut seen = 0;
+    let mut after = after.map(str::to_string);
+    while seen < scan && kept.len() < want {
+        let page = sent_page(api, usize::from(PAGE_SIZE), after.as_deref()).await?;
+        let empty = page.data.is_empty();
+        let mut emails = page.data.into_iter();
+        for email in emails.by_ref() {
+            seen += 1;
+            after = Some(email.id.to_string());
+            if keep(&email) {
+                kept.push(email);
+            }
+            if seen == scan || kept.len() == want {
+                break;
+            }
+        }
+        if empty || (!page.has_more && emails.next().is_none()) {
+            return Ok((kept, None));
+        }
+    }
+    Ok((kept, after))
+}
+
+/// The newest `max` sent emails, paging through the list as needed.
+pub async fn fetch_sent(api: &Api, max: usize) -> Result<Vec<Email>> {
+    let mut emails = Vec::new();
+    for_each_sent(api, max, |email| {
+        emails.push(email);
+        Ok(())
+    })
+    .await?;
+    Ok(emails)
+}
+
+/// Hands the newest `max` sent emails to `each`, newest first, as every page
+/// arrives, so callers can stream them without holding the whole list.
+pub async fn for_each_sent(
+    api: &Api,
+    max: usize,
+    mut each: impl FnMut(Email) -> Result<()>,
+) -> Result<()> {
+    let mut seen = 0;
+    let mut after: Option<String> = None;
+    while seen < max {
+        let opts = ListOptions::default().with_limit(PAGE_SIZE);
+        let page = match &after {
+            None => api.call("GET /emails", || api.resend.emails.list(opts.clone())).await,
+            Some(id) => {
+                api.call("GET /emails", || api.resend.emails.list(opts.clone().list_after(id)))
+                    .await
+            }
+        }
+        .context("list failed")?;
+        let next = page.data.last().map(|last| last.id.to_string());
+        for email in page.data.into_iter().take(max - seen) {
+            each(email)?;
+            seen += 1;
+        }
+        match next {
+            Some(id) if page.has_more => after = Some(id),
+            _ => break,
+        }
+    }
+    Ok(())
+}
+
+/// The newest `max` received emails, paging through the list as needed.
+pub async fn fetch_received(api: &Api, max: usize) -> Result<Vec<InboundEmail>> {
+    let mut emails = Vec::new();
+    let mut after: Option<String> = None;
+    while emails.len() < max {
+        let opts = ListOptions::default().with_limit(PAGE_SIZE);
+        let page = match &after {
+            None => {
+                api.call("GET /emails/receiving", || api.resend.receiving.list(opts.clone()))
+                    .await
+            }
+            Some(id) => {
+                api.call("GET /emails/receiving", || {
+                    api.resend.receiving.list(opts.clone().list_after(id))
+                })
+                .await
+            }
+        }
+        .context("list receiving failed")?;
+        let next = page.data.last().map(|last| last.id.to_string());
+        emails.extend(page.data);
+        match next {
+            Some(id) if page.has_more => after = Some(id),
+            _ => break,
+        }
+    }
+    emails.truncate(max);
+    Ok(emails)
+}
+
+/// Every received email newer than `bookmark`, newest first, paging through
+/// the list until the bookmarked one is reached.
+pub async fn fetch_received_since(api: &Api, bookmark: &Bookmark) -> Result<Vec<InboundEmail>> {
+    let mut emails = Vec::new();
+    let mut after: Option<String> = None;
+    loop {
+        let opts = ListOptions::default().with_limit(PAGE_SIZE);
+        let page = match &after {
+            None => {
+                api.call("GET /emails/receiving", || api.resend.receiving.list(opts.clone()))
+                    .await
+            }
+            Some(id) => {
+                api.call("GET /emails/receiving", || {
+                    api.resend.receiving.list(opts.clone().list_after(id))
+                })
+                .await
+            }
+        }
+        .context("list receiving failed")?;
+        let next = page.data.last().map(|last| last.id.to_string());
+        let (page_len, before) = (page.data.len(), emails.len());
+        emails.extend(page.data.into_iter().take_while(|e| bookmark.is_older_than(e)));
+        let reached_bookmark = emails.len() - before < page_len;
+        match next {
+            Some(id) if page.has_more && !reached_bookmark => after = Some(id),
+            _ => break,
+        }
+    }
+    Ok(emails)
+}
+
+/// How many of `emails` went to each recipient domain (To, Cc and Bcc).
+///
+/// An email counts once per domain, however many of its recipients share it.
+/// Domains are compared case-insensitively.
+pub fn count_domains(emails: &[Email]) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for email in emails {
+        let domains: BTreeSet<String> = email
+            .to
+            .iter()
+            .chain(&email.cc)
+            .chain(&email.bcc)
+            .filter_map(|r| address::domain(r))
+            .map(str::to_lowercase)
+            .collect();
+        for domain in domains {
+            *counts.entry(domain).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// Keeps the newest `count` rows and puts them in `sort` order.
+///
+/// Trimming happens before the requested sort, so `--sort created_asc` shows
+/// the newest N oldest-first rather than the oldest N overall.
+pub fn arrange<T: Listed>(rows: &mut Vec<T>, sort: ListSort, count: usize) {
+    // Unparseable timestamps count as oldest.
+    let created = |row: &T| -> Option<DateTime<Utc>> { timestamp::parse(row.created_at()) };
+    rows.sort_by_key(|row| std::cmp::Reverse(created(row)));
+    rows.truncate(count);
+    match sort {
+        ListSort::CreatedDesc => {}
+        ListSort::CreatedAsc => rows.reverse(),
+        ListSort::Subject => rows.sort_by_cached_key(|row| row.subject().to_lowercase()),
+    }
+}
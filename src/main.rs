@@ -1,27 +1,188 @@
 use anyhow::{bail, Context, Result};
-use clap::{Args, Parser, Subcommand, CommandFactory};
-use clap_complete;
+use clap::{Args, Parser, Subcommand, CommandFactory, ValueEnum};
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::io::{self, Read};
+use std::io::{self, IsTerminal, Read, Write};
 use std::num::NonZeroUsize;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 // Use types shown in user's snippet
-use resend_rs::types::{CreateEmailBaseOptions, UpdateEmailOptions};
-use resend_rs::Resend;
+use resend_rs::idempotent::Idempotent;
+use resend_rs::list_opts::ListOptions;
+use resend_rs::types::{
+    CreateApiKeyOptions, CreateEmailBaseOptions, Email, EmailEvent, ErrorKind, UpdateEmailOptions,
+};
+mod address;
+mod attach;
+mod audit;
+mod batch;
+mod bookmark;
+mod client;
+mod compose;
+mod config_format;
+mod contacts;
+mod dns;
+mod domains;
+mod editor;
+mod fetch;
+mod forward;
+mod headers;
+mod ics;
+mod idempotency;
+mod links;
+mod listing;
+mod message;
+mod notify;
+mod output;
+mod parallel;
+mod preheader;
+mod scheduled;
+mod template;
+mod templates;
+mod thread;
+mod throttle;
+mod timestamp;
+mod transfer;
+mod wait;
+mod wire;
+
+use audit::JsonLines;
+use batch::{BatchEmailInput, BatchFormat};
+use bookmark::Bookmark;
+use client::{Api, ApiLog, ResponseHeaders, Retry, RetryPolicy};
+use config_format::ConfigFormat;
+use contacts::ContactsCommand;
+use domains::DomainPreflight;
+use listing::{ListField, ListSort};
+use output::{EmailView, InboundEmailView, Output, OutputFormat, Page};
 
 #[derive(Serialize, Deserialize, Default, Debug)]
 struct AppConfig {
+    /// Left out of `config export` unless `--include-keys` is given.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     api_key: String,
     default_from: Option<String>,
     default_to: Option<String>,
+    /// Where `send --test` delivers instead of the real recipients.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    test_recipient: Option<String>,
+    /// The visible To of `send --hide-recipients`, instead of the From address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hidden_recipients_to: Option<String>,
+    /// Named From headers for `send --as`, managed by `config senders`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    senders: BTreeMap<String, String>,
+    /// Default `--subject-prefix` for send and batch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    subject_prefix: Option<String>,
+    /// Default `--subject-suffix` for send and batch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    subject_suffix: Option<String>,
+    /// Default `send --max-recipients`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_recipients: Option<usize>,
+    /// COUNT for `list` and `received-list` when none is given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default_list_count: Option<NonZeroUsize>,
+    /// Where `send --template-name` looks, instead of `templates` in the config dir.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    templates_dir: Option<PathBuf>,
+    /// Run the From domain check on every send and batch, failing when it doesn't pass.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    require_verified_from: bool,
 }
 
 #[derive(Parser)]
 #[command(name = "rusend", about = "A small user-friendly CLI for resend.com")]
 struct Cli {
+    /// Append a JSON-lines record of every API call to this file
+    ///
+    /// Sends and `--raw` reads also record Resend's request id.
+    #[arg(long, global = true, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    /// Move an existing log file to `<PATH>.1` instead of appending to it
+    #[arg(long, global = true, requires = "log_file")]
+    log_rotate: bool,
+
+    /// Print every API call to stderr, tagged with a per-run correlation id
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// Which failed API calls to retry: none, rate limits only, or also 5xx errors and timeouts
+    #[arg(long, global = true, value_enum, default_value_t = RetryPolicy::RateLimited)]
+    retry_policy: RetryPolicy,
+
+    /// Also retry sends without an idempotency key after a 5xx or timeout (may send twice)
+    #[arg(long, global = true)]
+    force_retry: bool,
+
+    /// Restrict the credentials file to its owner (mode 600) if other users can read it
+    #[arg(long, global = true)]
+    fix_permissions: bool,
+
+    /// Use the API key and settings saved under this profile (`rusend config --profile NAME`)
+    #[arg(long, global = true, value_name = "NAME", value_parser = parse_profile)]
+    profile: Option<String>,
+
+    /// Show recipients as `j***@example.com` in confirmations and dry runs (not with --verbose)
+    #[arg(long, global = true)]
+    mask_recipients: bool,
+
+    /// Send API requests here instead of Resend's API, e.g. a proxy or a mock server
+    /// (overrides RESEND_BASE_URL)
+    #[arg(long, global = true, value_name = "URL")]
+    base_url: Option<reqwest::Url>,
+
+    /// Most read requests in flight at once when a command fetches several things
+    /// (`get` with several ids, `received-get --follow-thread`)
+    #[arg(
+        long,
+        global = true,
+        value_name = "N",
+        default_value_t = NonZeroUsize::new(parallel::DEFAULT_MAX_CONCURRENT).unwrap()
+    )]
+    max_concurrent: NonZeroUsize,
+
+    /// Output format for list/get style commands
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Human)]
+    output: OutputFormat,
+
+    /// Shorthand for `--output json`
+    #[arg(long, global = true, conflicts_with = "output")]
+    json: bool,
+
+    /// With `--output json`, print each document on one line instead of pretty-printing
+    #[arg(long, global = true)]
+    compact: bool,
+
+    /// Timezone for displayed timestamps: `local` or an IANA name like Europe/Berlin
+    #[arg(
+        long,
+        global = true,
+        value_name = "TZ",
+        default_value = "local",
+        value_parser = timestamp::parse_zone
+    )]
+    timezone: timestamp::Zone,
+
+    /// Print raw HTTP traffic to stderr (API key redacted, bodies hidden)
+    #[arg(long, global = true)]
+    trace_http: bool,
+
+    /// With --trace-http, also print request and response bodies after asking
+    #[arg(long, global = true, requires = "trace_http")]
+    trace_http_bodies: bool,
+
+    /// Load environment variables from a .env file; variables already set win
+    #[arg(long, global = true, value_name = "PATH")]
+    env_file: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -34,6 +195,11 @@ enum Commands {
         #[arg(short, long)]
         key: Option<String>,
 
+        /// Read the API key from stdin, e.g. piped from a secrets manager, so it
+        /// stays out of the process arguments and shell history
+        #[arg(long, conflicts_with = "key")]
+        from_stdin: bool,
+
         /// Set default 'from' address
         #[arg(long)]
         default_from: Option<String>,
@@ -41,293 +207,2880 @@ enum Commands {
         /// Set default 'to' address
         #[arg(long)]
         default_to: Option<String>,
+
+        /// Set the address `send --test` delivers to
+        #[arg(long, value_name = "ADDRESS")]
+        test_recipient: Option<String>,
+
+        /// Set the visible To of `send --hide-recipients` (`""` restores the From address)
+        #[arg(long, value_name = "ADDRESS")]
+        hidden_recipients_to: Option<String>,
+
+        /// Set the default subject prefix for send and batch (`""` clears it)
+        #[arg(long, value_name = "TEXT")]
+        subject_prefix: Option<String>,
+
+        /// Set the default subject suffix for send and batch (`""` clears it)
+        #[arg(long, value_name = "TEXT")]
+        subject_suffix: Option<String>,
+
+        /// Set the default `send --max-recipients` (0 removes the limit)
+        #[arg(long, value_name = "N")]
+        max_recipients: Option<usize>,
+
+        /// Set how many emails `list` and `received-list` show by default (0 restores 10)
+        #[arg(long, value_name = "N")]
+        default_list_count: Option<usize>,
+
+        /// Set where `send --template-name` finds templates (`""` restores the default)
+        #[arg(long, value_name = "PATH")]
+        templates_dir: Option<PathBuf>,
+
+        /// Refuse every send and batch whose From domain isn't verified (see `--allow-unverified`)
+        #[arg(long, value_name = "BOOL")]
+        require_verified_from: Option<bool>,
+
+        /// Save a new API key without first checking it against the API
+        #[arg(long)]
+        no_validate: bool,
+
+        #[command(subcommand)]
+        command: Option<ConfigCommand>,
     },
 
     /// Send one email (reads body from --html, --text, or stdin)
-    Send(SendArgs),
+    Send(Box<SendArgs>),
 
-    /// Send batch using a JSON file with an array of messages
-    Batch { file: PathBuf },
+    /// Interactively write and send an email
+    Compose,
+
+    /// Send batch using a JSON (or NDJSON) file of messages
+    Batch(Box<BatchArgs>),
 
     /// List sent emails
     List {
-        /// Number of emails to display
+        /// Number of emails to display (default 10, see `config --default-list-count`)
         #[arg(value_name = "COUNT")]
         count: Option<NonZeroUsize>,
+
+        /// Order of the displayed emails (the newest COUNT are always the ones kept)
+        #[arg(long, value_enum, default_value_t = ListSort::CreatedDesc)]
+        sort: ListSort,
+
+        /// Columns to show, in order (default id,created,from,to; JSON output has them all)
+        #[arg(long, value_enum, value_delimiter = ',', value_name = "FIELDS")]
+        fields: Vec<ListField>,
+
+        /// Count the newest COUNT emails per recipient domain instead of listing them
+        #[arg(long, conflicts_with_all = ["sort", "fields"])]
+        group_by_domain: bool,
+
+        /// Add the unique recipients of the newest COUNT emails to this audience as contacts
+        #[arg(
+            long,
+            value_name = "AUDIENCE_ID",
+            conflicts_with_all = ["sort", "fields", "group_by_domain"]
+        )]
+        export_to_audience: Option<String>,
+
+        /// Start after this email ID, e.g. the `next_cursor` of a previous JSON page
+        #[arg(
+            long,
+            value_name = "CURSOR",
+            conflicts_with_all = ["group_by_domain", "export_to_audience"]
+        )]
+        after: Option<String>,
+
+        /// With `--output json`, print the bare array instead of the `data`/`has_more` envelope
+        #[arg(long)]
+        bare_array: bool,
+
+        /// Only show emails whose latest event is `failed`
+        #[arg(long, conflicts_with_all = ["group_by_domain", "export_to_audience"])]
+        failed_only: bool,
+
+        /// Only show emails whose latest event is `bounced` (with --failed-only, either)
+        #[arg(long, conflicts_with_all = ["group_by_domain", "export_to_audience"])]
+        bounced_only: bool,
+
+        /// In JSON output, one record per To recipient, `to` holding that single address
+        #[arg(long, conflicts_with_all = ["group_by_domain", "export_to_audience"])]
+        flatten: bool,
+
+        /// Only show emails sent to this address (To, Cc or Bcc, ignoring case)
+        #[arg(
+            long,
+            value_name = "ADDRESS",
+            conflicts_with_all = ["group_by_domain", "export_to_audience"]
+        )]
+        to: Option<String>,
+
+        /// How many of the most recent sent emails --failed-only/--bounced-only/--to look through
+        #[arg(long, value_name = "N", default_value_t = 1000)]
+        scan: usize,
     },
 
-    /// Get a single sent email by id (defaults to newest when omitted)
+    /// Get sent emails by id (defaults to newest when omitted)
     Get {
-        /// Email id (omit to show the newest email)
+        /// Email ids (omit to show the newest email)
         #[arg(value_name = "ID")]
-        id: Option<String>,
+        ids: Vec<String>,
+
+        /// Print the API's JSON response verbatim instead of rusend's view of it
+        #[arg(long, conflicts_with = "output")]
+        raw: bool,
+
+        /// Write the email's HTML body, as the API stored it, to PATH (one email only)
+        #[arg(long, value_name = "PATH", conflicts_with = "raw")]
+        download_html: Option<PathBuf>,
     },
 
-    /// Update an email (e.g. schedule)
+    /// Reschedule an email that hasn't been sent yet
+    ///
+    /// Resend only allows changing the send time; subject and body are fixed
+    /// once an email is created.
     Update {
         id: String,
+        /// New send time (ISO 8601, or natural language like "in 1 hour")
         #[arg(short, long)]
-        scheduled_at: Option<String>,
+        scheduled_at: String,
     },
 
     /// Cancel a scheduled email
     Cancel { id: String },
 
-    /// List received emails (inbox)
-    ReceivedList {
-        /// Number of emails to display
-        #[arg(value_name = "COUNT")]
-        count: Option<NonZeroUsize>,
-    },
+    /// List emails scheduled for later, soonest first
+    Scheduled {
+        /// How many of the most recent sent emails to look through
+        #[arg(long, value_name = "N", default_value_t = 1000)]
+        scan: usize,
+    },
+
+    /// List received emails (inbox)
+    ReceivedList {
+        /// Number of emails to display (default 10, see `config --default-list-count`)
+        #[arg(value_name = "COUNT")]
+        count: Option<NonZeroUsize>,
+
+        /// Order of the displayed emails (the newest COUNT are always the ones kept)
+        #[arg(long, value_enum, default_value_t = ListSort::CreatedDesc)]
+        sort: ListSort,
+
+        /// Show only emails newer than the last --since-last run, then move the bookmark
+        #[arg(long)]
+        since_last: bool,
+
+        /// Forget the --since-last bookmark
+        #[arg(long)]
+        reset_bookmark: bool,
+
+        /// Start after this email ID, e.g. the `next_cursor` of a previous JSON page
+        #[arg(long, value_name = "CURSOR", conflicts_with = "since_last")]
+        after: Option<String>,
+
+        /// With `--output json`, print the bare array instead of the `data`/`has_more` envelope
+        #[arg(long)]
+        bare_array: bool,
+
+        /// Also append each email shown to PATH as a JSON line, for a downstream process
+        #[arg(long, value_name = "PATH")]
+        json_lines: Option<PathBuf>,
+    },
+
+    /// Get a received email (defaults to newest when omitted)
+    ReceivedGet {
+        /// Email id (omit to show the newest email)
+        #[arg(value_name = "ID")]
+        id: Option<String>,
+
+        /// Print the API's JSON response verbatim instead of rusend's view of it
+        #[arg(long, conflicts_with_all = ["output", "follow_thread"])]
+        raw: bool,
+
+        /// Show the whole conversation the email belongs to, oldest first
+        #[arg(long)]
+        follow_thread: bool,
+
+        /// How many of the most recent received emails --follow-thread searches
+        #[arg(long, value_name = "N", default_value_t = 100, requires = "follow_thread")]
+        scan: usize,
+
+        /// Also print every header the API returns (authentication results, Received, ...)
+        #[arg(long, conflicts_with = "raw")]
+        headers: bool,
+
+        /// Also append each email shown to PATH as a JSON line, for a downstream process
+        #[arg(long, value_name = "PATH", conflicts_with = "raw")]
+        json_lines: Option<PathBuf>,
+    },
+
+    /// Forward a received email, with its attachments, to other recipients
+    ReceivedForward {
+        /// Email id (omit to forward the newest email)
+        #[arg(value_name = "ID")]
+        id: Option<String>,
+
+        /// Recipient(s), comma-separated
+        #[arg(short, long)]
+        to: String,
+
+        /// Sender (defaults to the configured default)
+        #[arg(short, long)]
+        from: Option<String>,
+
+        /// A note to put above the forwarded message, as HTML
+        #[arg(long)]
+        html: Option<String>,
+
+        /// A note to put above the forwarded message, as plain text
+        #[arg(long)]
+        text: Option<String>,
+    },
+
+    /// Check the sending domains registered on the account
+    Domains {
+        #[command(subcommand)]
+        command: DomainsCommand,
+    },
+
+    /// Work with the named templates used by `send --template-name`
+    Templates {
+        #[command(subcommand)]
+        command: TemplatesCommand,
+    },
+
+    /// Manage audience contacts
+    Contacts {
+        #[command(subcommand)]
+        command: ContactsCommand,
+    },
+
+    /// Show rusend and resend-rs versions and the effective configuration
+    Version,
+
+    /// Generate shell completions
+    #[command(hide = true)]
+    Completions {
+        /// The shell to generate completions for
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Manage named sender identities used by `send --as`
+    Senders {
+        #[command(subcommand)]
+        command: SendersCommand,
+    },
+    /// Write the configuration to a file for use on another machine
+    Export {
+        /// Where to write it
+        #[arg(long, value_name = "PATH")]
+        out: PathBuf,
+
+        /// Also export the API key (the file is written unencrypted)
+        #[arg(long)]
+        include_keys: bool,
+
+        /// File format (detected from the extension, JSON otherwise)
+        #[arg(long, value_enum)]
+        config_format: Option<ConfigFormat>,
+    },
+    /// Replace the configuration with one written by `config export`
+    Import {
+        path: PathBuf,
+
+        /// Overwrite an existing configuration without asking
+        #[arg(short, long)]
+        yes: bool,
+
+        /// File format (detected from the extension, JSON otherwise)
+        #[arg(long, value_enum)]
+        config_format: Option<ConfigFormat>,
+    },
+    /// Replace the stored API key with a newly created one
+    ///
+    /// Creates a full-access key with the current one, checks it and saves
+    /// it. With --revoke-old, the previous key is deleted afterwards.
+    RotateKey {
+        /// Name of the new key (defaults to `rusend-<date>`)
+        #[arg(long)]
+        name: Option<String>,
+
+        /// ID of the key being replaced, to revoke once the new one is saved
+        #[arg(long, value_name = "KEY_ID")]
+        revoke_old: Option<String>,
+
+        /// Revoke without asking
+        #[arg(short, long, requires = "revoke_old")]
+        yes: bool,
+    },
+    /// Convert a config file from an older layout, keeping a backup of it
+    ///
+    /// The first releases stored only the API key, as plain text; it becomes
+    /// the JSON settings file used now.
+    Migrate,
+    /// Remove the stored API key, keeping the other settings
+    ///
+    /// With several profiles saved, name one with --profile (or pass --all).
+    Unset {
+        /// Delete every file rusend keeps: settings, bookmark and domain cache
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum DomainsCommand {
+    /// List the account's domains
+    List {
+        /// Also show which of SPF, DKIM and DMARC each domain has in place
+        #[arg(long, alias = "check-spf-dkim")]
+        check: bool,
+    },
+    /// Ask Resend to verify domains, optionally waiting until they are verified
+    Verify {
+        /// Domain ID(s)
+        #[arg(required = true, value_name = "ID")]
+        ids: Vec<String>,
+
+        /// Poll until every domain is verified, backing off from 5s to 1m between checks
+        #[arg(long)]
+        wait: bool,
+
+        /// How long --wait keeps polling (exit code 4 when exceeded)
+        #[arg(
+            long,
+            value_name = "DURATION",
+            value_parser = timestamp::parse_duration,
+            default_value = "10m",
+            requires = "wait"
+        )]
+        wait_timeout: chrono::TimeDelta,
+    },
+    /// Resolve the DNS records a domain needs and report which are published
+    DnsCheck {
+        /// Domain ID
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TemplatesCommand {
+    /// List the templates in the templates directory
+    List,
+}
+
+#[derive(Subcommand)]
+enum SendersCommand {
+    /// Add or replace a sender, e.g. `add acme "Acme <no-reply@acme.com>"`
+    Add { name: String, from: String },
+    /// Remove a sender
+    Remove { name: String },
+    /// List configured senders
+    List,
+}
+
+#[derive(Args, Default)]
+struct SendArgs {
+    /// From header, e.g. "Acme <no-reply@acme.com>"
+    #[arg(short, long)]
+    from: Option<String>,
+
+    /// Use the From of a sender saved with `config senders add` (--from wins)
+    #[arg(long = "as", value_name = "NAME")]
+    sender: Option<String>,
+
+    /// From address, combined with --from-name instead of writing --from by hand
+    #[arg(long, value_name = "EMAIL", conflicts_with_all = ["from", "sender"])]
+    from_email: Option<String>,
+
+    /// Display name for --from-email; quoted as needed
+    #[arg(long, value_name = "NAME", requires = "from_email")]
+    from_name: Option<String>,
+
+    /// To recipients, comma separated
+    #[arg(short, long)]
+    to: Option<String>,
+
+    /// Cc recipients, comma separated
+    #[arg(long)]
+    cc: Option<String>,
+
+    /// File of Cc addresses, one per line (`#` starts a comment line); adds to --cc
+    #[arg(long, value_name = "PATH")]
+    cc_file: Option<PathBuf>,
+
+    /// Bcc recipients, comma separated
+    #[arg(long)]
+    bcc: Option<String>,
+
+    /// File of Bcc addresses, one per line (`#` starts a comment line); adds to --bcc
+    #[arg(long, value_name = "PATH")]
+    bcc_file: Option<PathBuf>,
+
+    /// Reply-To addresses, comma separated
+    #[arg(long)]
+    reply_to: Option<String>,
+
+    /// File of Reply-To addresses, one per line (`#` starts a comment line)
+    #[arg(long, value_name = "PATH")]
+    reply_to_file: Option<PathBuf>,
+
+    /// Fail instead of warning when a Reply-To domain differs from the From domain
+    #[arg(long)]
+    strict_reply_to: bool,
+
+    /// Also Bcc the From address (the extra copy counts against your quota)
+    #[arg(long)]
+    bcc_self: bool,
+
+    /// Deliver only to the configured test recipient and prefix the subject with [TEST]
+    #[arg(long)]
+    test: bool,
+
+    /// Send only to Resend's test address for this outcome, to exercise event handling
+    #[arg(long, value_enum, value_name = "OUTCOME", conflicts_with = "test")]
+    sandbox: Option<Sandbox>,
+
+    /// Attach a file; the content type is guessed unless given as `PATH:TYPE` (repeatable)
+    #[arg(long, value_name = "PATH[:TYPE]", value_parser = attach::parse_spec)]
+    attach: Vec<attach::AttachSpec>,
+
+    /// Attach every file in a directory (hidden files are skipped)
+    #[arg(long, value_name = "PATH")]
+    attachments_dir: Option<PathBuf>,
+
+    /// Also attach files in subdirectories of --attachments-dir
+    #[arg(long, requires = "attachments_dir")]
+    recursive: bool,
+
+    /// Attach every file matching a glob, e.g. `"reports/*.pdf"` (quote it; repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    attach_glob: Vec<String>,
+
+    /// Don't fail when an --attach-glob pattern matches no files
+    #[arg(long, requires = "attach_glob")]
+    allow_empty_glob: bool,
+
+    /// Attach data piped on stdin under this file name (stdin can't also carry the body)
+    #[arg(
+        long,
+        value_name = "FILENAME",
+        conflicts_with_all = ["from_stdin", "html_stdin", "edit"]
+    )]
+    attach_stdin: Option<String>,
+
+    /// Attach an iCalendar file as a meeting invite (text/calendar with its METHOD)
+    #[arg(long, value_name = "PATH")]
+    ics: Option<PathBuf>,
+
+    /// Embed local images referenced by `<img src>` in the HTML body as inline attachments
+    #[arg(long)]
+    attach_inline_from_html: bool,
+
+    /// Inbox preview text, added to the top of the HTML body as a hidden block
+    #[arg(long, value_name = "TEXT")]
+    preheader: Option<String>,
+
+    /// Remove tracking query parameters (utm_*, fbclid, gclid, ...) from the HTML
+    /// body's links before sending (the count is logged with --verbose)
+    #[arg(long)]
+    strip_tracking_params: bool,
+
+    /// Send even when no body (--html, --text, stdin, --edit, ...) is given
+    #[arg(long)]
+    allow_empty_body: bool,
+
+    /// Subject
+    #[arg(
+        short,
+        long,
+        required_unless_present_any = ["id", "subject_file", "template_name", "json_input"]
+    )]
+    subject: Option<String>,
+
+    /// Read the subject from the first line of a file
+    #[arg(long, value_name = "PATH", conflicts_with = "subject")]
+    subject_file: Option<PathBuf>,
+
+    /// Send the subject RFC 2047 encoded (`=?UTF-8?B?...?=`) for gateways that reject raw UTF-8
+    #[arg(long)]
+    force_encoded_subject: bool,
+
+    /// Provide HTML body inline
+    #[arg(long, conflicts_with = "id")]
+    html: Option<String>,
+
+    /// Fetch the HTML body from an http(s) URL
+    #[arg(long, value_name = "URL", conflicts_with_all = ["html", "html_stdin", "id"])]
+    html_url: Option<String>,
+
+    /// Provide plain text body inline
+    #[arg(long, conflicts_with = "id")]
+    text: Option<String>,
+
+    /// Provide the HTML body base64-encoded, decoded before sending (avoids shell escaping)
+    #[arg(
+        long,
+        value_name = "BASE64",
+        value_parser = transfer::parse_base64_body,
+        conflicts_with_all = ["html", "html_url", "id"]
+    )]
+    html_base64: Option<String>,
+
+    /// Provide the plain text body base64-encoded, decoded before sending
+    #[arg(
+        long,
+        value_name = "BASE64",
+        value_parser = transfer::parse_base64_body,
+        conflicts_with_all = ["text", "id"]
+    )]
+    text_base64: Option<String>,
+
+    /// Read body from stdin
+    #[arg(long, conflicts_with = "id")]
+    from_stdin: bool,
+
+    /// Whether the --from-stdin body is HTML or plain text
+    #[arg(
+        long,
+        visible_alias = "content-type",
+        value_enum,
+        default_value_t = BodyFormat::Text,
+        requires = "from_stdin"
+    )]
+    stdin_format: BodyFormat,
+
+    /// Send the --from-stdin body as HTML if it starts with a tag like `<html` or `<!doctype`
+    #[arg(long, requires = "from_stdin", conflicts_with = "stdin_format")]
+    detect_html: bool,
+
+    /// Read an HTML body from stdin (short for `--from-stdin --stdin-format html`)
+    #[arg(long, conflicts_with_all = ["id", "from_stdin", "edit"])]
+    html_stdin: bool,
+
+    /// Write the body in $EDITOR (prefilled with --html/--text of the same format)
+    #[arg(long, conflicts_with_all = ["id", "from_stdin"])]
+    edit: bool,
+
+    /// Whether the edited body is HTML or plain text
+    #[arg(long, value_enum, default_value_t = BodyFormat::Text, requires = "edit")]
+    edit_format: BodyFormat,
+
+    /// Send the named template: `<NAME>.html`, plus `<NAME>.txt` and `<NAME>.subject` if present
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with_all = [
+            "html", "html_url", "html_base64", "text", "text_base64", "from_stdin", "html_stdin",
+            "edit", "id"
+        ]
+    )]
+    template_name: Option<String>,
+
+    /// Value for a `{{NAME}}` placeholder in the template (repeatable)
+    #[arg(
+        long = "var",
+        value_name = "NAME=VALUE",
+        value_parser = templates::parse_var,
+        requires = "template_name"
+    )]
+    vars: Vec<(String, String)>,
+
+    /// Templates directory for this send (defaults to `config --templates-dir`)
+    #[arg(long, value_name = "PATH", requires = "template_name")]
+    templates_dir: Option<PathBuf>,
+
+    /// Forward a received email by ID
+    #[arg(long)]
+    id: Option<String>,
+
+    /// Send the message described by a JSON object in PATH (`-` for stdin), with
+    /// the API's fields: from, to, cc, bcc, reply_to, subject, html, text,
+    /// headers, tags, attachments, scheduled_at
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "from", "sender", "from_email", "from_name", "to", "cc", "cc_file", "bcc",
+            "bcc_file", "reply_to", "reply_to_file", "subject", "subject_file", "html",
+            "html_url", "html_base64", "text", "text_base64", "from_stdin", "html_stdin", "edit",
+            "template_name", "id", "attach", "attachments_dir", "attach_glob", "attach_stdin",
+            "ics", "individual", "hide_recipients", "test", "sandbox", "preheader",
+            "strip_tracking_params", "attach_inline_from_html", "allow_empty_body"
+        ]
+    )]
+    json_input: Option<PathBuf>,
+
+    /// Drop duplicate recipients (case-insensitive, first occurrence wins)
+    #[arg(long)]
+    dedupe: bool,
+
+    /// Send a separate message to each recipient so none sees the others
+    #[arg(long, conflicts_with_all = ["cc", "cc_file", "bcc", "bcc_file", "bcc_self"])]
+    individual: bool,
+
+    /// Move every To address to Bcc and address the email to the sender (or see
+    /// `config --hidden-recipients-to`), so recipients don't see each other
+    #[arg(long, conflicts_with = "individual")]
+    hide_recipients: bool,
+
+    /// After sending, wait until the email's status is final and print it
+    #[arg(long, conflicts_with_all = ["individual", "dry_run"])]
+    wait: bool,
+
+    /// How long --wait and --assert-status wait for a final status (exit code 4 when exceeded)
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = timestamp::parse_duration,
+        default_value = "2m"
+    )]
+    wait_timeout: chrono::TimeDelta,
+
+    /// Wait this long before sending, e.g. 30s or 1m30s (Ctrl-C cancels); unlike
+    /// server-side scheduling, the command stays running until the send
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = timestamp::parse_duration,
+        conflicts_with = "dry_run"
+    )]
+    delay: Option<chrono::TimeDelta>,
+
+    /// Exit with code 3 unless the final status is this one (implies --wait)
+    #[arg(long, value_enum, value_name = "STATUS", conflicts_with_all = ["individual", "dry_run"])]
+    assert_status: Option<wait::Expected>,
+
+    /// Idempotency key; Resend ignores a repeat with the same key for 24 hours
+    // resend-rs drops the key on batch requests, which --individual uses.
+    #[arg(long, value_name = "KEY", conflicts_with = "individual")]
+    idempotency_key: Option<String>,
+
+    /// Derive the idempotency key from From, To, Subject and body, making reruns safe
+    #[arg(long, alias = "auto-idempotency", conflicts_with_all = ["idempotency_key", "individual"])]
+    idempotency_from_content: bool,
+
+    /// POST a JSON summary (id, to, subject, status) here after each email sent
+    #[arg(long, value_name = "URL", value_parser = notify::parse_url)]
+    notify_url: Option<reqwest::Url>,
+
+    /// After sending, print Resend's request id and rate-limit response headers
+    /// (as a JSON object with `--output json`)
+    #[arg(long, conflicts_with_all = ["individual", "dry_run"])]
+    return_headers: bool,
+
+    /// Write the JSON request body to PATH (`-` for stdout) before sending
+    #[arg(long, value_name = "PATH")]
+    dump_request: Option<PathBuf>,
+
+    /// Print the JSON request body and stop without sending; with --verbose,
+    /// also summarize it on stderr
+    #[arg(long, conflicts_with_all = ["dump_request", "notify_url"])]
+    dry_run: bool,
+
+    /// Refuse to send to more than N recipients in total (To, Cc and Bcc)
+    #[arg(long, value_name = "N")]
+    max_recipients: Option<usize>,
+
+    /// Send even when --max-recipients (or its configured default) is exceeded
+    #[arg(long)]
+    force: bool,
+
+    /// Refuse to send unless the From domain is verified (the domain list is cached briefly)
+    #[arg(long)]
+    only_if_verified: bool,
+
+    #[command(flatten)]
+    headers: headers::HeaderArgs,
+
+    #[command(flatten)]
+    subject_tag: SubjectTagArgs,
+
+    #[command(flatten)]
+    body_limit: BodyLimitArgs,
+
+    #[command(flatten)]
+    preflight: PreflightArgs,
+}
+
+impl SendArgs {
+    fn has_body_source(&self) -> bool {
+        self.html.is_some()
+            || self.html_url.is_some()
+            || self.html_base64.is_some()
+            || self.text.is_some()
+            || self.text_base64.is_some()
+            || self.from_stdin
+            || self.html_stdin
+            || self.edit
+            || self.id.is_some()
+            || self.template_name.is_some()
+    }
+}
+
+/// An outcome `send --sandbox` simulates.
+#[derive(Clone, Copy, ValueEnum)]
+enum Sandbox {
+    Delivered,
+    Bounced,
+    Complained,
+}
+
+impl Sandbox {
+    fn outcome(self) -> &'static str {
+        match self {
+            Self::Delivered => "delivered",
+            Self::Bounced => "bounced",
+            Self::Complained => "complained",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+enum BodyFormat {
+    Html,
+    #[default]
+    Text,
+}
+
+impl BodyFormat {
+    /// `--detect-html`: HTML when the body, past any whitespace or BOM, opens
+    /// with a doctype, a comment or one of the usual top-level tags; text otherwise.
+    fn detect(body: &str) -> Self {
+        const TAGS: [&str; 7] = ["!doctype", "html", "head", "body", "div", "table", "p"];
+        let start = body
+            .trim_start_matches(|c: char| c.is_whitespace() || c == '\u{feff}')
+            .chars()
+            .take(16)
+            .collect::<String>()
+            .to_ascii_lowercase();
+        let Some(rest) = start.strip_prefix('<') else {
+            return Self::Text;
+        };
+        let opens_tag = |tag: &&str| {
+            rest.strip_prefix(*tag).is_some_and(|after| {
+                after.is_empty() || after.starts_with([' ', '\t', '\r', '\n', '>', '/'])
+            })
+        };
+        if rest.starts_with("!--") || TAGS.iter().any(opens_tag) {
+            Self::Html
+        } else {
+            Self::Text
+        }
+    }
+}
+
+#[derive(Args)]
+struct BatchArgs {
+    /// JSON file with an array of messages, or `-` to read from stdin
+    #[arg(
+        required_unless_present = "template",
+        conflicts_with_all = ["template", "data", "subject", "subject_file"]
+    )]
+    file: Option<PathBuf>,
+
+    /// HTML template rendered once per `--data` row (`{{column}}` placeholders)
+    #[arg(long, value_name = "HTML", requires = "data")]
+    template: Option<PathBuf>,
+
+    /// CSV whose header names the template variables; needs a `to` column
+    #[arg(long, value_name = "CSV", requires = "template")]
+    data: Option<PathBuf>,
+
+    /// Subject template for `--template` (defaults to the `subject` column)
+    #[arg(long, requires = "template")]
+    subject: Option<String>,
+
+    /// Read the subject template from the first line of a file
+    #[arg(long, value_name = "PATH", requires = "template", conflicts_with = "subject")]
+    subject_file: Option<PathBuf>,
+
+    /// Input format (detected from the file extension, JSON for stdin)
+    #[arg(long, value_enum)]
+    format: Option<BatchFormat>,
+
+    /// Subject for messages in FILE that don't set one
+    #[arg(long, value_name = "TEXT", conflicts_with = "template")]
+    default_subject: Option<String>,
+
+    /// HTML body for messages in FILE that don't set `html`
+    #[arg(long, value_name = "PATH", conflicts_with = "template")]
+    default_html_file: Option<PathBuf>,
+
+    /// Plain-text body for messages in FILE that don't set `text`
+    #[arg(long, value_name = "PATH", conflicts_with = "template")]
+    default_text_file: Option<PathBuf>,
+
+    /// Comma separated From addresses assigned round-robin to messages without one
+    #[arg(long, value_name = "ADDRESSES")]
+    from_pool: Option<String>,
+
+    /// Messages per API request (the API accepts at most 100)
+    #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u64).range(1..=100))]
+    chunk_size: u64,
+
+    /// Send at most N emails per second on average (a chunk may go out as one burst)
+    #[arg(long, value_name = "N", value_parser = throttle::parse_rate)]
+    rate: Option<f64>,
+
+    /// Stop at the first chunk that fails
+    #[arg(long, conflicts_with = "continue_on_error")]
+    fail_fast: bool,
+
+    /// Attempt every chunk and report all failures at the end (default)
+    #[arg(long)]
+    continue_on_error: bool,
+
+    /// Print only failed messages, with their errors, as a JSON array on stdout
+    #[arg(long, conflicts_with = "output")]
+    json_errors_only: bool,
+
+    /// Print no line per failed chunk, only the summary at the end (with --report
+    /// or --failures-file for the details)
+    #[arg(long, conflicts_with = "json_errors_only")]
+    summary_only: bool,
+
+    /// Write the failed messages, with their errors, to PATH as a JSON array
+    #[arg(long, value_name = "PATH", conflicts_with = "stagger")]
+    failures_file: Option<PathBuf>,
+
+    /// Send the messages in random order (content is unchanged)
+    #[arg(long)]
+    shuffle: bool,
+
+    /// Give every address only its first message in the file: later copies are removed,
+    /// and messages left without a To address are dropped
+    #[arg(long)]
+    global_dedupe: bool,
+
+    /// Skip the first N messages and send the rest, e.g. to resume a run that stopped
+    /// (counted after --global-dedupe, as the run's messages and report count them)
+    #[arg(long, value_name = "N", conflicts_with = "shuffle")]
+    continue_from: Option<usize>,
+
+    /// Send at most N emails to any one recipient domain in this run, holding back
+    /// the messages that would go over (reported, and kept with --overflow-file)
+    #[arg(long, value_name = "N", conflicts_with = "continue_from")]
+    max_per_domain: Option<NonZeroUsize>,
+
+    /// Write the messages --max-per-domain held back to PATH, as a batch file to send later
+    #[arg(long, value_name = "PATH", requires = "max_per_domain")]
+    overflow_file: Option<PathBuf>,
+
+    /// Seed for --shuffle, to repeat an earlier order (printed when not given)
+    #[arg(long, value_name = "N", requires = "shuffle")]
+    seed: Option<u64>,
+
+    /// Schedule each chunk this long after the previous one, e.g. `10m` or `1h30m`
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = timestamp::parse_duration,
+        conflicts_with = "json_errors_only"
+    )]
+    stagger: Option<chrono::TimeDelta>,
+
+    /// Send several chunks at once, raising the number while requests stay fast and
+    /// succeed, and lowering it on failures and rate limits (logged with --verbose)
+    #[arg(long, conflicts_with = "stagger")]
+    concurrency_auto: bool,
+
+    /// Most chunks --concurrency-auto sends at once
+    #[arg(long, value_name = "N", default_value_t = 8, requires = "concurrency_auto")]
+    max_concurrency: usize,
+
+    /// Write the JSON request bodies to PATH (`-` for stdout) before sending
+    #[arg(long, value_name = "PATH")]
+    dump_request: Option<PathBuf>,
+
+    /// Check every message (parsing, template variables, addresses, From domains)
+    /// and list all problems without sending anything
+    #[arg(long)]
+    validate_only: bool,
+
+    /// Largest request a message with attachments may make, bodies and files together
+    #[arg(long, value_name = "BYTES", default_value_t = attach::MAX_TOTAL_BYTES)]
+    attach_max_total: u64,
+
+    /// Send everything in one all-or-nothing request; fail instead of chunking past 100
+    #[arg(long, conflicts_with_all = ["chunk_size", "stagger"])]
+    single_request: bool,
+
+    /// Write a JSON report of every message (id or error, timing) and the run's metrics
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["stagger", "validate_only"])]
+    report: Option<PathBuf>,
+
+    #[command(flatten)]
+    subject_tag: SubjectTagArgs,
+
+    #[command(flatten)]
+    body_limit: BodyLimitArgs,
+
+    #[command(flatten)]
+    preflight: PreflightArgs,
+}
+
+#[derive(Args, Default)]
+struct SubjectTagArgs {
+    /// Put this before every subject, e.g. `[staging]` (overrides the configured default)
+    #[arg(long, value_name = "TEXT")]
+    subject_prefix: Option<String>,
+
+    /// Put this after every subject (overrides the configured default)
+    #[arg(long, value_name = "TEXT")]
+    subject_suffix: Option<String>,
+}
+
+impl SubjectTagArgs {
+    /// Wraps `subject` in the prefix and suffix, falling back to the configured ones.
+    ///
+    /// A flag replaces the configured value rather than adding to it, and a subject
+    /// that already carries the prefix or suffix is left alone.
+    fn apply(&self, config: &AppConfig, subject: &str) -> String {
+        let prefix = self.subject_prefix.as_ref().or(config.subject_prefix.as_ref());
+        let suffix = self.subject_suffix.as_ref().or(config.subject_suffix.as_ref());
+        let mut subject = subject.to_string();
+        let prefix = prefix.map(|p| p.trim()).filter(|p| !p.is_empty());
+        if let Some(prefix) = prefix.filter(|p| !subject.starts_with(p)) {
+            subject = format!("{prefix} {subject}");
+        }
+        let suffix = suffix.map(|s| s.trim()).filter(|s| !s.is_empty());
+        if let Some(suffix) = suffix.filter(|s| !subject.ends_with(s)) {
+            subject = format!("{subject} {suffix}");
+        }
+        subject
+    }
+}
+
+#[derive(Args)]
+struct BodyLimitArgs {
+    /// Refuse a message whose HTML and text bodies together are larger than this
+    #[arg(long, value_name = "BYTES", default_value_t = attach::MAX_TOTAL_BYTES)]
+    max_body_bytes: u64,
+}
+
+impl Default for BodyLimitArgs {
+    fn default() -> Self {
+        Self { max_body_bytes: attach::MAX_TOTAL_BYTES }
+    }
+}
+
+impl BodyLimitArgs {
+    /// Fails, naming `what` and its size, if the bodies exceed `--max-body-bytes`.
+    ///
+    /// The default is Resend's 40 MB cap on a whole message, so a body that
+    /// large would be rejected by the API anyway.
+    fn check(&self, what: &str, html: Option<&str>, text: Option<&str>) -> Result<()> {
+        let size = html.map_or(0, str::len) + text.map_or(0, str::len);
+        if size as u64 > self.max_body_bytes {
+            bail!(
+                "{what} has a {size}-byte body (HTML and text), over the limit of {} bytes \
+                 (--max-body-bytes)",
+                self.max_body_bytes
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args, Default)]
+struct PreflightArgs {
+    /// Check that the From domain is verified before sending
+    #[arg(long)]
+    check_domain: bool,
+
+    /// Warn about links through URL shorteners (bit.ly, t.co, ...) in the body
+    #[arg(long)]
+    warn_short_links: bool,
+
+    /// Fail instead of warning when a preflight check does not pass
+    #[arg(long)]
+    strict: bool,
+
+    /// Skip the From domain check that `config --require-verified-from` turns on
+    #[arg(long, conflicts_with = "check_domain")]
+    allow_unverified: bool,
+}
+
+impl PreflightArgs {
+    /// Whether to check the From domain, and if so whether a problem is fatal:
+    /// `--check-domain` (`--strict` to fail), or always and fatal with
+    /// `require_verified_from` in the config unless `--allow-unverified` is given.
+    fn domain_check(&self, config: &AppConfig) -> Option<bool> {
+        if self.check_domain {
+            Some(self.strict)
+        } else if config.require_verified_from && !self.allow_unverified {
+            Some(true)
+        } else {
+            None
+        }
+    }
+
+    /// `--warn-short-links`: warns (or fails with `--strict`) when the bodies
+    /// of `what` link through a shortener, a common spam signal.
+    fn check_links(&self, what: &str, html: Option<&str>, text: Option<&str>) -> Result<()> {
+        if !self.warn_short_links {
+            return Ok(());
+        }
+        let mut found = html.map(links::shortened).unwrap_or_default();
+        for url in text.map(links::shortened).unwrap_or_default() {
+            if !found.contains(&url) {
+                found.push(url);
+            }
+        }
+        if found.is_empty() {
+            return Ok(());
+        }
+        let msg = format!(
+            "{what} links through a URL shortener, which spam filters penalize: {}",
+            found.join(", ")
+        );
+        if self.strict {
+            bail!("{msg} (--strict)");
+        }
+        eprintln!("warning: {msg}");
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let correlation_id = cli.verbose.then(client::correlation_id);
+    if let Some(url) = cli.base_url.clone() {
+        client::set_base_url(url);
+    }
+    parallel::set_max_concurrent(cli.max_concurrent);
+    FIX_PERMISSIONS.store(cli.fix_permissions, Ordering::Relaxed);
+    if let Some(profile) = cli.profile.clone() {
+        // Set once, before anything reads the config dir.
+        let _ = PROFILE.set(profile);
+    }
+    address::set_masked(cli.mask_recipients && !cli.verbose);
+    let out = Output {
+        format: if cli.json { OutputFormat::Json } else { cli.output },
+        compact: cli.compact,
+        timezone: cli.timezone,
+    };
+
+    let opened = load_env_file(cli.env_file.as_deref())
+        .and_then(|()| {
+            if cli.trace_http { wire::install(cli.trace_http_bodies) } else { Ok(()) }
+        })
+        .and_then(|()| {
+            ApiLog::open(cli.log_file.as_deref(), cli.log_rotate, correlation_id.clone())
+        });
+    let result = match opened {
+        Ok(log) => {
+            let retry = Retry { policy: cli.retry_policy, force: cli.force_retry };
+            run(cli.command, log, retry, out).await
+        }
+        Err(e) => Err(e),
+    };
+    if let Some(id) = &correlation_id {
+        eprintln!("Correlation id: {id}");
+    }
+    if let Err(e) = result {
+        report_error(&e, cli.verbose);
+        let code = e.downcast_ref::<wait::Unexpected>().map_or(1, |u| u.code);
+        std::process::exit(code);
+    }
+}
+
+/// Loads `--env-file` into the environment before anything reads it. dotenvy
+/// leaves variables that are already set alone.
+fn load_env_file(path: Option<&Path>) -> Result<()> {
+    if let Some(path) = path {
+        dotenvy::from_path(path).with_context(|| format!("load env file {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Prints a failed run as `error: <what failed>: <why>`, followed by a hint
+/// for well-known API errors. `--verbose` adds every step of the cause chain.
+fn report_error(error: &anyhow::Error, verbose: bool) {
+    let (what, why) = (error.to_string(), error.root_cause().to_string());
+    if what == why {
+        eprintln!("error: {what}");
+    } else {
+        eprintln!("error: {what}: {why}");
+    }
+    if verbose && error.chain().len() > 1 {
+        eprintln!("\nCaused by:");
+        for (i, cause) in error.chain().skip(1).enumerate() {
+            eprintln!("    {i}: {cause}");
+        }
+    }
+    if let Some(hint) = client::hint(error) {
+        eprintln!("\nHint: {hint}");
+    }
+}
+
+async fn run(
+    command: Commands,
+    log: Option<ApiLog>,
+    retry: Retry,
+    out: Output,
+) -> Result<()> {
+    match command {
+        Commands::Completions { shell } => {
+            let mut cmd = Cli::command();
+            let name = cmd.get_name().to_string();
+            clap_complete::generate(shell, &mut cmd, name, &mut io::stdout());
+        }
+        Commands::Version => print_version(out)?,
+        Commands::Config { command: Some(ConfigCommand::Senders { command }), .. } => {
+            manage_senders(command, out)?;
+        }
+        Commands::Config {
+            command: Some(ConfigCommand::Export { out, include_keys, config_format }),
+            ..
+        } => export_config(&out, include_keys, config_format)?,
+        Commands::Config {
+            command: Some(ConfigCommand::Import { path, yes, config_format }),
+            ..
+        } => import_config(&path, yes, config_format)?,
+        Commands::Config { command: Some(ConfigCommand::Migrate), .. } => migrate_config()?,
+        Commands::Config { command: Some(ConfigCommand::Unset { all }), .. } => unset_config(all)?,
+        Commands::Config {
+            command: Some(ConfigCommand::RotateKey { name, revoke_old, yes }),
+            ..
+        } => rotate_key(name, revoke_old, yes, log, retry).await?,
+        Commands::Config {
+            key,
+            from_stdin,
+            default_from,
+            default_to,
+            test_recipient,
+            hidden_recipients_to,
+            subject_prefix,
+            subject_suffix,
+            max_recipients,
+            default_list_count,
+            templates_dir,
+            require_verified_from,
+            no_validate,
+            command: None,
+        } => {
+            let mut cfg = load_config().unwrap_or_default();
+
+            let new_key = if let Some(k) = key {
+                Some(k)
+            } else if from_stdin {
+                Some(read_key_from_stdin()?)
+            } else if cfg.api_key.is_empty() {
+                Some(prompt_key().context("failed to read api key")?)
+            } else {
+                None
+            };
+            if let Some(k) = new_key {
+                if !no_validate {
+                    validate_key(&k, log, retry).await?;
+                }
+                cfg.api_key = k;
+            }
+
+            if let Some(f) = default_from {
+                cfg.default_from = Some(f);
+            }
+            if let Some(t) = default_to {
+                cfg.default_to = Some(t);
+            }
+            if let Some(t) = test_recipient {
+                if !address::is_valid(address::mailbox(&t)) {
+                    bail!("invalid test recipient {t:?}");
+                }
+                cfg.test_recipient = Some(t);
+            }
+            if let Some(t) = hidden_recipients_to {
+                if !t.trim().is_empty() && !address::is_valid(address::mailbox(&t)) {
+                    bail!("invalid address {t:?}");
+                }
+                cfg.hidden_recipients_to = Some(t).filter(|t| !t.trim().is_empty());
+            }
+            if let Some(p) = subject_prefix {
+                cfg.subject_prefix = Some(p).filter(|p| !p.trim().is_empty());
+            }
+            if let Some(s) = subject_suffix {
+                cfg.subject_suffix = Some(s).filter(|s| !s.trim().is_empty());
+            }
+            if let Some(n) = max_recipients {
+                cfg.max_recipients = Some(n).filter(|&n| n > 0);
+            }
+            if let Some(n) = default_list_count {
+                cfg.default_list_count = NonZeroUsize::new(n);
+            }
+            if let Some(dir) = templates_dir {
+                cfg.templates_dir = Some(dir).filter(|d| !d.as_os_str().is_empty());
+            }
+            if let Some(require) = require_verified_from {
+                cfg.require_verified_from = require;
+            }
+
+            save_config(&cfg)?;
+            println!("Configuration saved.");
+        }
+        Commands::Send(args) if args.json_input.is_some() => {
+            let config = load_config()?;
+            let api = Api::new(&config.api_key, log, retry);
+            send_json(&api, out, &config, *args).await?;
+        }
+        Commands::Send(args) => {
+            if !args.has_body_source() && !args.allow_empty_body {
+                bail!(
+                    "no body given: pass --html, --html-url, --text (or their --*-base64 forms), \
+                     --from-stdin, --html-stdin, \
+                     --edit, --template-name or --id (or --allow-empty-body)"
+                );
+            }
+            let config = load_config()?;
+            let api = Api::new(&config.api_key, log, retry);
+            send(&api, out, config, *args).await?;
+        }
+        Commands::Compose => {
+            let config = load_config()?;
+            let draft = compose::run(config.default_from.as_deref(), config.default_to.as_deref())?;
+            if let Some(draft) = draft {
+                let (html, text) = if draft.html {
+                    (Some(draft.body), None)
+                } else {
+                    (None, Some(draft.body))
+                };
+                let args = SendArgs {
+                    from: Some(draft.from),
+                    to: Some(draft.to),
+                    subject: Some(draft.subject),
+                    html,
+                    text,
+                    ..Default::default()
+                };
+                let api = Api::new(&config.api_key, log, retry);
+                send(&api, out, config, args).await?;
+            }
+        }
+        Commands::Batch(args) => {
+            let config = load_config()?;
+            let api = Api::new(&config.api_key, log, retry);
+            if args.validate_only {
+                return validate_batch(&api, &args).await;
+            }
+
+            let mut batch: Vec<BatchEmailInput> = match (&args.file, &args.template, &args.data) {
+                (_, Some(template), Some(data)) => {
+                    batch::read_merge(template, data, batch_subject(&args)?.as_deref())?
+                }
+                (Some(file), _, _) => {
+                    let mut batch = batch::read_input(file, args.format)?;
+                    batch::apply_defaults(&mut batch, &batch_defaults(&args)?)?;
+                    batch
+                }
+                _ => unreachable!("clap requires a file or --template/--data"),
+            };
+            batch::require_recipients(&mut batch)?;
+            if args.global_dedupe {
+                let (recipients, messages) = batch::dedupe_across(&mut batch);
+                if recipients > 0 {
+                    eprintln!(
+                        "Removed {recipients} recipient(s) already in an earlier message; \
+                         dropped {messages} message(s) left without a To address."
+                    );
+                }
+            }
+            let first_index = args.continue_from.unwrap_or(0);
+            if first_index > 0 {
+                if first_index >= batch.len() {
+                    bail!(
+                        "--continue-from {first_index} skips every message: the batch has {}",
+                        batch.len()
+                    );
+                }
+                batch.drain(..first_index);
+                eprintln!("Skipping the first {first_index} message(s).");
+            }
+            if let Some(max) = args.max_per_domain {
+                let overflow = batch::cap_per_domain(&mut batch, max.get());
+                report_overflow(&overflow, max, args.overflow_file.as_deref())?;
+                if batch.is_empty() {
+                    bail!("--max-per-domain {max} held back every message; nothing was sent");
+                }
+            }
+            if args.single_request && batch.iter().any(BatchEmailInput::has_attachments) {
+                bail!(
+                    "--single-request can't carry attachments: the batch endpoint doesn't take \
+                     them, so messages with attachments are sent one request each"
+                );
+            }
+            if args.single_request && batch.len() > batch::MAX_BATCH_SIZE {
+                bail!(
+                    "{} messages don't fit in one request (the API accepts at most {}); \
+                     drop --single-request to send them in chunks",
+                    batch.len(),
+                    batch::MAX_BATCH_SIZE
+                );
+            }
+            let pool = args.from_pool.as_deref().map(parse_to_vec).unwrap_or_default();
+            batch::apply_from_pool(&mut batch, &pool)?;
+            for (i, b) in batch.iter_mut().enumerate() {
+                b.subject = args.subject_tag.apply(&config, &b.subject);
+                let what = format!("message {}", first_index + i + 1);
+                args.body_limit.check(&what, b.html.as_deref(), b.text.as_deref())?;
+                args.preflight.check_links(&what, b.html.as_deref(), b.text.as_deref())?;
+                b.check_request_size(args.attach_max_total)
+                    .map_err(|e| anyhow::anyhow!("{what}: {e:#}"))?;
+            }
+
+            if let Some(strict) = args.preflight.domain_check(&config) {
+                let mut preflight = DomainPreflight::default();
+                for b in &batch {
+                    preflight.check(&api, &b.from, strict).await?;
+                }
+            }
+
+            if args.shuffle {
+                let seed = args.seed.unwrap_or_else(|| {
+                    let seed = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |d| d.as_nanos() as u64);
+                    eprintln!("Shuffling with seed {seed} (pass --seed {seed} to repeat).");
+                    seed
+                });
+                batch::shuffle(&mut batch, seed);
+            }
+
+            let always_cc = address::always("RUSEND_ALWAYS_CC")?;
+            let always_bcc = address::always("RUSEND_ALWAYS_BCC")?;
+            let attached = batch
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| b.has_attachments())
+                .map(|(i, _)| i)
+                .collect();
+            let mut removed = 0;
+            let emails: Vec<CreateEmailBaseOptions> = batch
+                .into_iter()
+                .enumerate()
+                .map(|(i, mut b)| {
+                    let attachments = b.load_attachments().map_err(|e| {
+                        anyhow::anyhow!("message {}: {e:#}", first_index + i + 1)
+                    })?;
+                    removed += address::dedupe(&mut b.to);
+                    b.cc.extend(always_cc.iter().cloned());
+                    b.bcc.extend(always_bcc.iter().cloned());
+                    address::dedupe(&mut b.cc);
+                    address::dedupe(&mut b.bcc);
+                    let mut e = CreateEmailBaseOptions::new(&b.from, b.to, &b.subject);
+                    for addr in &b.cc {
+                        e = e.with_cc(addr);
+                    }
+                    for addr in &b.bcc {
+                        e = e.with_bcc(addr);
+                    }
+                    if let Some(h) = b.html {
+                        e = e.with_html(&h);
+                    }
+                    if let Some(t) = b.text {
+                        e = e.with_text(&t);
+                    }
+                    for attachment in attachments {
+                        e = e.with_attachment(attachment);
+                    }
+                    Ok(e)
+                })
+                .collect::<Result<_>>()?;
+            report_duplicates(removed);
+
+            let opts = batch::ChunkOptions {
+                chunk_size: usize::try_from(args.chunk_size).unwrap_or(batch::MAX_BATCH_SIZE),
+                rate: args.rate,
+                fail_fast: args.fail_fast,
+                errors_only: args.json_errors_only,
+                report: args.report.clone(),
+                summary_only: args.summary_only,
+                failures_file: args.failures_file.clone(),
+                max_concurrency: args.concurrency_auto.then_some(args.max_concurrency),
+                first_index,
+                attached,
+            };
+            if let Some(path) = &args.dump_request {
+                dump_request(path, &emails)?;
+            }
+            match args.stagger {
+                Some(stagger) => batch::send_staggered(&api, out, emails, opts, stagger).await?,
+                None => batch::send_chunked(&api, out, emails, opts).await?,
+            }
+        }
+        Commands::List {
+            count,
+            sort,
+            fields,
+            group_by_domain,
+            export_to_audience,
+            after,
+            bare_array,
+            failed_only,
+            bounced_only,
+            to,
+            scan,
+            flatten,
+        } => {
+            if flatten && !out.is_json() {
+                bail!("--flatten shapes JSON records; add --output json, ndjson or yaml");
+            }
+            let to = to.map(|addr| address::mailbox(&addr).to_string());
+            if let Some(addr) = to.as_deref().filter(|a| !address::is_valid(a)) {
+                bail!("invalid --to address {addr:?}");
+            }
+            let config = load_config()?;
+            let api = Api::new(&config.api_key, log, retry);
+            let limit = list_count(count, &config);
+            if let Some(audience_id) = export_to_audience {
+                let emails = listing::fetch_sent(&api, limit).await?;
+                let mut recipients: Vec<String> = emails
+                    .iter()
+                    .flat_map(|e| e.to.iter().chain(&e.cc).chain(&e.bcc))
+                    .map(|r| address::mailbox(r).to_string())
+                    .collect();
+                address::dedupe(&mut recipients);
+                println!(
+                    "Found {} unique recipient(s) in {} email(s).",
+                    recipients.len(),
+                    emails.len()
+                );
+                return contacts::add_recipients(&api, &audience_id, recipients).await;
+            }
+            if group_by_domain {
+                let emails = listing::fetch_sent(&api, limit).await?;
+                let counts = listing::count_domains(&emails);
+                if out.is_json() {
+                    return out.print_json(&counts);
+                }
+                let mut rows: Vec<_> = counts.into_iter().collect();
+                rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+                let width = rows.iter().map(|(d, _)| d.len()).max().unwrap_or(0);
+                for (domain, n) in &rows {
+                    println!("{domain:<width$}  {n}");
+                }
+                println!("{} email(s) across {} domain(s).", emails.len(), rows.len());
+                return Ok(());
+            }
+            let by_status = failed_only || bounced_only;
+            let filtered = by_status || to.is_some();
+            // Newest first is the API's own order, so items can go out as pages arrive.
+            let streamable = sort == ListSort::CreatedDesc && after.is_none() && !filtered;
+            if out.format == OutputFormat::Ndjson && streamable {
+                return listing::for_each_sent(&api, limit, |email| {
+                    if flatten {
+                        for record in output::per_recipient(&EmailView::from(&email))? {
+                            out.print_line(&record)?;
+                        }
+                        return Ok(());
+                    }
+                    out.print_line(&EmailView::from(&email))
+                })
+                .await;
+            }
+            let (mut emails, next_cursor) = if filtered {
+                // The API can't filter by status or recipient, so the scan happens here.
+                let keep = |e: &Email| {
+                    let status = match e.last_event {
+                        EmailEvent::Failed => failed_only,
+                        EmailEvent::Bounced => bounced_only,
+                        _ => false,
+                    };
+                    let sent_to = |addr: &str| {
+                        e.to.iter()
+                            .chain(&e.cc)
+                            .chain(&e.bcc)
+                            .any(|r| address::mailbox(r).eq_ignore_ascii_case(addr))
+                    };
+                    (!by_status || status) && to.as_deref().is_none_or(sent_to)
+                };
+                listing::filter_sent(&api, after.as_deref(), scan, limit, keep).await?
+            } else {
+                let list = listing::sent_page(&api, limit, after.as_deref()).await?;
+                let next = list.data.last().filter(|_| list.has_more).map(|e| e.id.to_string());
+                (list.data, next)
+            };
+            listing::arrange(&mut emails, sort, limit);
+            if out.is_json() && flatten {
+                let mut data = Vec::new();
+                for email in &emails {
+                    data.extend(output::per_recipient(&EmailView::from(email))?);
+                }
+                let page = Page { data, has_more: next_cursor.is_some(), next_cursor };
+                return out.print_page(&page, bare_array);
+            }
+            if out.is_json() {
+                let page = Page {
+                    data: emails.iter().map(EmailView::from).collect(),
+                    has_more: next_cursor.is_some(),
+                    next_cursor,
+                };
+                return out.print_page(&page, bare_array);
+            }
+            let fields = if fields.is_empty() { listing::DEFAULT_FIELDS } else { &fields };
+            for email in &emails {
+                println!("{}", listing::row(email, fields, out));
+            }
+            if filtered && emails.is_empty() {
+                println!("No matching emails in the {scan} most recent (see --scan).");
+            }
+        }
+        Commands::Get { ids, raw, download_html } => {
+            if download_html.is_some() && ids.len() > 1 {
+                bail!("--download-html saves one email, but {} ids were given", ids.len());
+            }
+            let api = Api::new(&load_config()?.api_key, log, retry);
+            let ids = if ids.is_empty() {
+                vec![resolve_sent_email_id(&api, None).await?]
+            } else {
+                ids
+            };
+            let api = &api;
+
+            if let Some(path) = download_html {
+                let id = &ids[0];
+                let email = api
+                    .call("GET /emails/{id}", || api.resend.emails.get(id))
+                    .await
+                    .context("get failed")?;
+                let html = email.html.filter(|h| !h.is_empty()).with_context(|| {
+                    format!("the API returned no stored HTML for {id}, nothing was written")
+                })?;
+                fs::write(&path, &html).with_context(|| format!("write {}", path.display()))?;
+                println!("Saved the HTML of {id} to {} ({} bytes).", path.display(), html.len());
+                return Ok(());
+            }
+
+            if raw {
+                let results = parallel::fetch_all(&ids, |id| async move {
+                    api.get_raw("GET /emails/{id}", &format!("/emails/{id}")).await
+                })
+                .await;
+                let (bodies, failed) = split_fetched(&ids, results)?;
+                for body in bodies {
+                    println!("{body}");
+                }
+                return fetch_outcome(failed, ids.len());
+            }
+
+            let results = parallel::fetch_all(&ids, |id| {
+                api.call("GET /emails/{id}", || api.resend.emails.get(id))
+            })
+            .await;
+            let (emails, failed) = split_fetched(&ids, results)?;
+            if out.is_json() {
+                match emails.as_slice() {
+                    [email] if ids.len() == 1 => out.print_json(&EmailView::from(email))?,
+                    _ => {
+                        let views: Vec<EmailView> = emails.iter().map(EmailView::from).collect();
+                        out.print_json(&views)?;
+                    }
+                }
+                return fetch_outcome(failed, ids.len());
+            }
+            for (i, email) in emails.iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                println!("ID: {}", email.id);
+                println!("Created: {}", out.time(&email.created_at));
+                println!("From: {}", email.from);
+                println!("To: {:?}", email.to);
+                println!("Subject: {}", email.subject);
+                print_email_body(email.text.as_deref(), email.html.as_deref());
+            }
+            return fetch_outcome(failed, ids.len());
+        }
+        Commands::Update { id, scheduled_at } => {
+            let api = Api::new(&load_config()?.api_key, log, retry);
+            let upd = UpdateEmailOptions::new().with_scheduled_at(&scheduled_at);
+            let email = match api
+                .call("PATCH /emails/{id}", || api.resend.emails.update(&id, upd.clone()))
+                .await
+            {
+                Ok(email) => email,
+                Err(e @ resend_rs::Error::Resend(_)) => {
+                    // The API's message for this case is vague; say why when we can.
+                    if let Ok(current) = api
+                        .call("GET /emails/{id}", || api.resend.emails.get(&id))
+                        .await
+                        && current.last_event != EmailEvent::Scheduled
+                    {
+                        bail!(
+                            "email {id} is already {} and can no longer be updated",
+                            output::event_name(current.last_event)
+                        );
+                    }
+                    return Err(e).context("update failed");
+                }
+                Err(e) => return Err(e).context("update failed"),
+            };
+            if out.is_json() {
+                return out.print_json(&serde_json::json!({ "id": email.id }));
+            }
+            println!("Updated email with ID: {}", email.id);
+        }
+        Commands::Cancel { id } => {
+            let api = Api::new(&load_config()?.api_key, log, retry);
+            let canceled = api
+                .call("POST /emails/{id}/cancel", || api.resend.emails.cancel(&id))
+                .await
+                .context("cancel failed")?;
+            if out.is_json() {
+                return out.print_json(&serde_json::json!({ "id": canceled.id }));
+            }
+            println!("Canceled: {}", canceled.id);
+        }
+        Commands::Scheduled { scan } => {
+            let api = Api::new(&load_config()?.api_key, log, retry);
+            scheduled::run(&api, out, scan).await?;
+        }
+        Commands::ReceivedList {
+            count,
+            sort,
+            since_last,
+            reset_bookmark,
+            after,
+            bare_array,
+            json_lines,
+        } => {
+            let bookmark_path = profile_path("received-bookmark.json")?;
+            if reset_bookmark {
+                if Bookmark::clear(&bookmark_path)? {
+                    eprintln!("Bookmark cleared.");
+                }
+                if !since_last {
+                    return Ok(());
+                }
+            }
+            let mut json_lines = json_lines.as_deref().map(JsonLines::open).transpose()?;
+            let config = load_config()?;
+            let api = Api::new(&config.api_key, log, retry);
+            let limit = list_count(count, &config);
+            let bookmark = if since_last { Bookmark::load(&bookmark_path)? } else { None };
+            // Everything since the bookmark comes back in one go, so there's no next page.
+            let (mut emails, next_cursor) = match &bookmark {
+                Some(bookmark) => (listing::fetch_received_since(&api, bookmark).await?, None),
+                None => {
+                    let list = listing::received_page(&api, limit, after.as_deref()).await?;
+                    let next = list.data.last().filter(|_| list.has_more).map(|e| e.id.to_string());
+                    (list.data, next)
+                }
+            };
+            let keep = if bookmark.is_some() { emails.len() } else { limit };
+            listing::arrange(&mut emails, sort, keep);
+            if out.is_json() {
+                let page = Page {
+                    data: emails.iter().map(InboundEmailView::from).collect(),
+                    has_more: next_cursor.is_some(),
+                    next_cursor,
+                };
+                out.print_page(&page, bare_array)?;
+            } else {
+                for email in &emails {
+                    println!(
+                        "ID: {}, Created: {}, From: {}, To: {:?}",
+                        email.id,
+                        out.time(&email.created_at),
+                        email.from,
+                        email.to
+                    );
+                }
+            }
+            if let Some(sink) = &mut json_lines {
+                for email in &emails {
+                    sink.write("received-list", email)?;
+                }
+            }
+            // Moved only once the emails have been printed.
+            if since_last && let Some(newest) = Bookmark::newest(&emails) {
+                newest.save(&bookmark_path)?;
+            }
+        }
+        Commands::ReceivedGet { id, raw, follow_thread, scan, headers, json_lines } => {
+            let mut json_lines = json_lines.as_deref().map(JsonLines::open).transpose()?;
+            let api = Api::new(&load_config()?.api_key, log, retry);
+            let email_id = resolve_received_email_id(&api, id).await?;
+            if raw {
+                let path = format!("/emails/receiving/{email_id}");
+                println!("{}", api.get_raw("GET /emails/receiving/{id}", &path).await?);
+                return Ok(());
+            }
+            let mut r = api
+                .call("GET /emails/receiving/{id}", || api.resend.receiving.get(&email_id))
+                .await
+                .context("get receiving failed")?;
+            transfer::decode_bodies(&mut r);
+            let view = |email| {
+                let view = InboundEmailView::from(email);
+                if headers { view.with_headers(email) } else { view }
+            };
+            if follow_thread {
+                let referenced = thread::has_references(&r);
+                let emails = thread::conversation(&api, r, scan).await?;
+                if emails.len() == 1 && referenced {
+                    eprintln!("No other message of this thread is among the emails searched.");
+                } else if emails.len() == 1 {
+                    eprintln!("This email has no threading headers and no replies were found.");
+                }
+                if let Some(sink) = &mut json_lines {
+                    for email in &emails {
+                        sink.write("received-get", email)?;
+                    }
+                }
+                if out.is_json() {
+                    let views: Vec<InboundEmailView> = emails.iter().map(view).collect();
+                    return out.print_json(&views);
+                }
+                for (i, email) in emails.iter().enumerate() {
+                    if i > 0 {
+                        println!();
+                    }
+                    print_received(email, out, headers);
+                }
+                return Ok(());
+            }
+            if let Some(sink) = &mut json_lines {
+                sink.write("received-get", &r)?;
+            }
+            if out.is_json() {
+                return out.print_json(&view(&r));
+            }
+            print_received(&r, out, headers);
+        }
+        Commands::ReceivedForward { id, to, from, html, text } => {
+            let config = load_config()?;
+            let api = Api::new(&config.api_key, log, retry);
+            let from = from
+                .or(config.default_from)
+                .context("From address not provided and no default set")?;
+            let to = parse_to_vec(&to);
+            if to.is_empty() {
+                bail!("--to has no addresses");
+            }
+            let email_id = resolve_received_email_id(&api, id).await?;
+            let mut r = api
+                .call("GET /emails/receiving/{id}", || api.resend.receiving.get(&email_id))
+                .await
+                .context("get receiving failed")?;
+            transfer::decode_bodies(&mut r);
+            let email =
+                forward::message(&api, &r, &from, to, html.as_deref(), text.as_deref()).await?;
+            let (res, _) = api.send_email(&email, None).await.context("forward failed")?;
+            if out.is_json() {
+                return out.print_json(&serde_json::json!({ "id": res.id }));
+            }
+            println!("Forwarded {email_id} as {}.", res.id);
+        }
+        Commands::Domains { command: DomainsCommand::List { check } } => {
+            let api = Api::new(&load_config()?.api_key, log, retry);
+            dns::list(&api, check, out).await?;
+        }
+        Commands::Domains { command: DomainsCommand::Verify { ids, wait, wait_timeout } } => {
+            let api = Api::new(&load_config()?.api_key, log, retry);
+            for id in &ids {
+                api.call("POST /domains/{id}/verify", || api.resend.domains.verify(id))
+                    .await
+                    .with_context(|| format!("verify domain {id} failed"))?;
+                println!("Verification of {id} started.");
+            }
+            if wait {
+                // Several domains are polled side by side, under --max-concurrent.
+                let results =
+                    parallel::fetch_all(&ids, |id| wait::until_verified(&api, id, wait_timeout))
+                        .await;
+                let mut errors = results.into_iter().filter_map(Result::err);
+                if let Some(first) = errors.next() {
+                    for e in errors {
+                        eprintln!("error: {e:#}");
+                    }
+                    return Err(first);
+                }
+            }
+        }
+        Commands::Domains { command: DomainsCommand::DnsCheck { id } } => {
+            let api = Api::new(&load_config()?.api_key, log, retry);
+            dns::check(&api, &id, out).await?;
+        }
+        Commands::Templates { command: TemplatesCommand::List } => {
+            let dir = templates_dir(&load_config()?)?;
+            let listed = templates::list(&dir)?;
+            if out.is_json() {
+                return out.print_json(&listed);
+            }
+            if listed.is_empty() {
+                println!("No templates in {}.", dir.display());
+            }
+            for t in &listed {
+                let mut parts = vec!["html"];
+                parts.extend(t.text.then_some("txt"));
+                parts.extend(t.subject.then_some("subject"));
+                println!("{}  ({})", t.name, parts.join(", "));
+            }
+        }
+        Commands::Contacts { command } => {
+            let api = Api::new(&load_config()?.api_key, log, retry);
+            contacts::run(&api, command).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends one email (or one per recipient with `--individual`) as described by `args`.
+async fn send(api: &Api, out: Output, config: AppConfig, args: SendArgs) -> Result<()> {
+    let to_addr = args.to.or(config.default_to.clone()).context("To address not provided and no default set")?;
+    // Checked before anything else, so no API call is made for a send that can't go out.
+    if parse_to_vec(&to_addr).is_empty() {
+        bail!("no valid recipients: --to {to_addr:?} has no addresses");
+    }
+
+    let sender = match &args.sender {
+        Some(name) => Some(config.senders.get(name).cloned().with_context(|| {
+            format!("no sender named {name:?}, see `rusend config senders list`")
+        })?),
+        None => None,
+    };
+    let composed = match &args.from_email {
+        Some(email) => {
+            let email = email.trim();
+            if !address::is_valid(email) {
+                bail!("invalid --from-email {email:?}");
+            }
+            Some(address::format_mailbox(args.from_name.as_deref().unwrap_or_default(), email))
+        }
+        None => None,
+    };
+    let from_addr = match args.from.or(composed).or(sender).or(config.default_from.clone()) {
+        Some(from) => from,
+        None if compose::interactive() => {
+            compose::pick_from(&config.senders, &domains::verified(api).await?)?
+        }
+        None => bail!("From address not provided and no default set"),
+    };
+
+    if args.only_if_verified {
+        domains::require_verified(api, &from_addr, &profile_path("verified-domains.json")?).await?;
+    }
+    if let Some(strict) = args.preflight.domain_check(&config) {
+        DomainPreflight::default().check(api, &from_addr, strict).await?;
+    }
+
+    let subject_arg = match &args.subject_file {
+        Some(path) => Some(read_subject_file(path)?),
+        None => args.subject.clone(),
+    };
+    let (subject, body_html, body_text) = if let Some(ref id) = args.id {
+        let email_id = resolve_received_email_id(api, Some(id.clone())).await?;
+        let mut r = api
+            .call("GET /emails/receiving/{id}", || api.resend.receiving.get(&email_id))
+            .await
+            .context("get received email for forwarding failed")?;
+        transfer::decode_bodies(&mut r);
+        
+        let subject = subject_arg.unwrap_or_else(|| format!("Fwd: {}", r.subject));
+        (subject, r.html, r.text)
+    } else if let Some(name) = &args.template_name {
+        let dir = match &args.templates_dir {
+            Some(dir) => dir.clone(),
+            None => templates_dir(&config)?,
+        };
+        let vars = args.vars.iter().cloned().collect();
+        let template = templates::Template::load(&dir, name)?.render(name, &vars)?;
+        let subject = subject_arg.or(template.subject).with_context(|| {
+            format!("template {name:?} has no {name}.subject file, pass --subject")
+        })?;
+        (subject, Some(template.html), template.text)
+    } else {
+        let mut body_html = match &args.html_url {
+            Some(url) => Some(fetch::html(url).await?),
+            None => args.html.clone().or_else(|| args.html_base64.clone()),
+        };
+        let mut body_text = args.text.clone().or_else(|| args.text_base64.clone());
+        if args.from_stdin || args.html_stdin {
+            let mut s = String::new();
+            io::stdin().read_to_string(&mut s).context("stdin read")?;
+            let format = if args.detect_html {
+                let format = BodyFormat::detect(&s);
+                let detected = if matches!(format, BodyFormat::Html) { "HTML" } else { "text" };
+                api.trace(&format!("--detect-html: sending the stdin body as {detected}"));
+                format
+            } else {
+                args.stdin_format
+            };
+            match (args.html_stdin, format) {
+                (true, _) | (_, BodyFormat::Html) => body_html = Some(s),
+                (false, BodyFormat::Text) => body_text = Some(s),
+            }
+        }
+        if args.edit {
+            let (body, extension) = match args.edit_format {
+                BodyFormat::Html => (&mut body_html, "html"),
+                BodyFormat::Text => (&mut body_text, "txt"),
+            };
+            let edited = editor::edit(body.as_deref().unwrap_or_default(), extension)?;
+            if edited.trim().is_empty() {
+                bail!("editor buffer is empty, not sending");
+            }
+            *body = Some(edited);
+        }
+        // Safety: clap ensures a subject (or subject file) is present if id is missing
+        (subject_arg.unwrap(), body_html, body_text)
+    };
+
+    let mut to = parse_to_vec(&to_addr);
+    if args.dedupe {
+        report_duplicates(address::dedupe(&mut to));
+    }
+    let mut cc = args.cc.as_deref().map(parse_to_vec).unwrap_or_default();
+    if let Some(path) = &args.cc_file {
+        cc.extend(address::read_list(path)?);
+    }
+    cc.extend(address::always("RUSEND_ALWAYS_CC")?);
+    address::dedupe(&mut cc);
+    let mut bcc = args.bcc.as_deref().map(parse_to_vec).unwrap_or_default();
+    if let Some(path) = &args.bcc_file {
+        bcc.extend(address::read_list(path)?);
+    }
+    bcc.extend(address::always("RUSEND_ALWAYS_BCC")?);
+    if args.bcc_self {
+        bcc.push(address::mailbox(&from_addr).to_string());
+    }
+    address::dedupe(&mut bcc);
+    // An address gets one copy, in the most visible field it appears in.
+    let moved = address::remove_present(&mut cc, &to)
+        + address::remove_present(&mut bcc, &to)
+        + address::remove_present(&mut bcc, &cc);
+    if moved > 0 {
+        eprintln!("Dropped {moved} Cc/Bcc address(es) that are already To or Cc recipients.");
+    }
+    if args.hide_recipients {
+        let visible = config
+            .hidden_recipients_to
+            .clone()
+            .unwrap_or_else(|| address::mailbox(&from_addr).to_string());
+        bcc.splice(0..0, to.drain(..));
+        address::dedupe(&mut bcc);
+        address::remove_present(&mut bcc, std::slice::from_ref(&visible));
+        eprintln!(
+            "warning: --hide-recipients: {} recipient(s) are in Bcc; \
+             the visible To is {visible}.",
+            bcc.len()
+        );
+        to = vec![visible];
+    }
+    let mut reply_to = args.reply_to.as_deref().map(parse_to_vec).unwrap_or_default();
+    if let Some(path) = &args.reply_to_file {
+        reply_to.extend(address::read_list(path)?);
+    }
+    address::dedupe(&mut reply_to);
+    check_reply_to_domains(&from_addr, &reply_to, args.strict_reply_to)?;
+    // Checked before --test so a rehearsal trips the same guard as the real send.
+    let limit = args.max_recipients.or(config.max_recipients);
+    check_recipient_limit(limit, args.force, to.len() + cc.len() + bcc.len())?;
+    let mut subject = args.subject_tag.apply(&config, &subject);
+    if args.test {
+        let sink = config.test_recipient.clone().context(
+            "--test needs a test recipient, set one with `rusend config --test-recipient <ADDRESS>`",
+        )?;
+        let real: Vec<&str> = to.iter().chain(&cc).chain(&bcc).map(String::as_str).collect();
+        eprintln!(
+            "WARNING: --test is on. Sending only to {sink}; {} real recipient(s) replaced: {}",
+            real.len(),
+            address::preview(real.iter().copied())
+        );
+        to = vec![sink];
+        cc.clear();
+        bcc.clear();
+        subject = format!("[TEST] {subject}");
+    }
+    if let Some(sandbox) = args.sandbox {
+        // Resend reserves these addresses for simulating each outcome.
+        let outcome = sandbox.outcome();
+        let sink = format!("{outcome}@resend.dev");
+        eprintln!(
+            "Sandbox: simulating a {outcome} email by sending only to {sink}; \
+             {} real recipient(s) left out.",
+            to.len() + cc.len() + bcc.len()
+        );
+        to = vec![sink];
+        cc.clear();
+        bcc.clear();
+    }
+    let mut specs = args.attach.clone();
+    if let Some(dir) = &args.attachments_dir {
+        let files = attach::dir_files(dir, args.recursive)?;
+        if files.is_empty() {
+            eprintln!("No files to attach in {}.", dir.display());
+        } else {
+            let names: Vec<String> = files.iter().map(|f| f.display().to_string()).collect();
+            eprintln!("Attaching {} file(s): {}", names.len(), names.join(", "));
+        }
+        specs.extend(files.into_iter().map(attach::AttachSpec::from));
+    }
+    for pattern in &args.attach_glob {
+        let files = attach::glob_files(pattern)?;
+        if files.is_empty() {
+            if !args.allow_empty_glob {
+                bail!(
+                    "--attach-glob {pattern:?} matches no files \
+                     (pass --allow-empty-glob to send without them)"
+                );
+            }
+            eprintln!("No files match {pattern:?}.");
+            continue;
+        }
+        let names: Vec<String> = files.iter().map(|f| f.display().to_string()).collect();
+        eprintln!("Attaching {} file(s) matching {pattern:?}: {}", names.len(), names.join(", "));
+        specs.extend(files.into_iter().map(attach::AttachSpec::from));
+    }
+    let piped = args.attach_stdin.as_deref().map(attach::from_stdin).transpose()?;
+    let invite = args.ics.as_deref().map(ics::load).transpose()?;
+    let extra: u64 = piped.iter().chain(&invite).map(|(_, size)| size).sum();
+    let attachment_bytes = attach::check_total_size(&specs, extra)?;
+    let mut attachments = specs
+        .iter()
+        .map(attach::AttachSpec::load)
+        .collect::<Result<Vec<_>>>()?;
+    attachments.extend(piped.into_iter().chain(invite).map(|(attachment, _)| attachment));
+    let mut body_html = body_html;
+    if let Some(text) = &args.preheader {
+        match &body_html {
+            Some(html) => body_html = Some(preheader::inject(html, text)),
+            None => eprintln!("warning: --preheader ignored, the email has no HTML body"),
+        }
+    }
+    if args.strip_tracking_params {
+        match &body_html {
+            Some(html) => {
+                let (clean, changed) = links::strip_tracking(html);
+                api.trace(&format!("--strip-tracking-params cleaned {changed} link(s)"));
+                body_html = Some(clean);
+            }
+            None => {
+                eprintln!("warning: --strip-tracking-params ignored, the email has no HTML body");
+            }
+        }
+    }
+    let mut inline_images = 0;
+    if args.attach_inline_from_html {
+        let html = body_html
+            .as_deref()
+            .context("--attach-inline-from-html needs an HTML body")?;
+        let base = std::env::current_dir().context("determine current directory")?;
+        let inlined = attach::inline_images(html, &base)?;
+        if inlined.embedded.is_empty() {
+            eprintln!("No local images to embed.");
+        } else {
+            eprintln!(
+                "Embedded {} image(s): {}",
+                inlined.embedded.len(),
+                inlined.embedded.join(", ")
+            );
+        }
+        // The limit covers the embedded images too, which only now have a size.
+        attach::check_total_size(&specs, extra + inlined.bytes)?;
+        inline_images = inlined.attachments.len();
+        attachments.extend(inlined.attachments);
+        body_html = Some(inlined.html);
+    }
+    args.body_limit.check("the email", body_html.as_deref(), body_text.as_deref())?;
+    args.preflight.check_links("the email", body_html.as_deref(), body_text.as_deref())?;
+    let headers = args.headers.resolve();
+    let wire_subject = if args.force_encoded_subject {
+        transfer::encode_header(&subject)
+    } else {
+        subject.clone()
+    };
+
+    // With --individual only the first message carries the Cc/Bcc copies, so
+    // each of those addresses gets one.
+    let build = |to: Vec<String>, copies: bool| {
+        let mut email = CreateEmailBaseOptions::new(&from_addr, to, &wire_subject);
+        let (cc, bcc) = if copies { (&cc[..], &bcc[..]) } else { (&[][..], &[][..]) };
+        for addr in cc {
+            email = email.with_cc(addr);
+        }
+        for addr in bcc {
+            email = email.with_bcc(addr);
+        }
+        if !reply_to.is_empty() {
+            email = email.with_reply_multiple(&reply_to);
+        }
+        for attachment in &attachments {
+            email = email.with_attachment(attachment.clone());
+        }
+        for (name, value) in &headers {
+            email = email.with_header(name, value);
+        }
+        if let Some(h) = &body_html {
+            email = email.with_html(h);
+        }
+        if let Some(t) = &body_text {
+            email = email.with_text(t);
+        }
+        email
+    };
+
+    let idempotency_key = if args.idempotency_from_content {
+        let key = idempotency::from_content(
+            &from_addr,
+            &to,
+            &subject,
+            body_html.as_deref(),
+            body_text.as_deref(),
+        );
+        api.trace(&format!("idempotency key {key}"));
+        Some(key)
+    } else {
+        args.idempotency_key.clone()
+    };
+
+    if args.dry_run && api.verbose() {
+        let summary = DryRun {
+            from: &from_addr,
+            to: &to,
+            cc: &cc,
+            bcc: &bcc,
+            subject: &subject,
+            html: body_html.as_deref(),
+            text: body_text.as_deref(),
+            attachments: attachments.len() - inline_images,
+            attachment_bytes,
+            inline_images,
+            headers: &headers,
+            individual: args.individual,
+            idempotency_key: idempotency_key.as_deref(),
+        };
+        summary.print();
+    }
+    if let Some(delay) = args.delay {
+        wait::delay(api, delay).await?;
+    }
+    if args.individual {
+        let emails: Vec<_> =
+            to.iter().enumerate().map(|(i, addr)| build(vec![addr.clone()], i == 0)).collect();
+        if args.dry_run {
+            return dump_request(Path::new("-"), &emails);
+        }
+        if let Some(path) = &args.dump_request {
+            dump_request(path, &emails)?;
+        }
+        let attached = !attachments.is_empty();
+        let outcome = batch::send_individually(api, out, &to, emails, attached).await?;
+        if let Some(url) = &args.notify_url {
+            for (addr, id) in &outcome.sent {
+                let to = std::slice::from_ref(addr);
+                notify::post(url, &notify::Summary { id, to, subject: &subject, status: "sent" })
+                    .await;
+            }
+        }
+        outcome.check()?;
+    } else {
+        let email: Idempotent<_> = match &idempotency_key {
+            Some(key) => build(to.clone(), true).with_idempotency_key(key),
+            None => build(to.clone(), true).into(),
+        };
+        if args.dry_run {
+            return dump_request(Path::new("-"), &email);
+        }
+        if let Some(path) = &args.dump_request {
+            dump_request(path, &email)?;
+        }
+        let (res, headers) = api
+            .send_email(&build(to.clone(), true), idempotency_key.as_deref())
+            .await
+            .context("send failed")?;
+        if args.return_headers {
+            print_response_headers(out, &res.id, &headers)?;
+        } else {
+            print_submitted(out, &res.id)?;
+        }
+        if let Some(url) = &args.notify_url {
+            let summary = notify::Summary {
+                id: &res.id,
+                to: &to,
+                subject: &subject,
+                status: "sent",
+            };
+            notify::post(url, &summary).await;
+        }
+        if args.wait || args.assert_status.is_some() {
+            wait::until_final(api, &res.id, args.wait_timeout, args.assert_status).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Fails when `total` recipients are over `limit` (`--max-recipients` or its
+/// configured default; 0 means none), unless `force`.
+fn check_recipient_limit(limit: Option<usize>, force: bool, total: usize) -> Result<()> {
+    if let Some(limit) = limit.filter(|&n| n > 0)
+        && total > limit
+    {
+        if !force {
+            bail!(
+                "{total} recipients (To, Cc and Bcc) is over the limit of {limit}; \
+                 pass --force to send anyway"
+            );
+        }
+        eprintln!("Sending to {total} recipients, over the limit of {limit} (--force).");
+    }
+    Ok(())
+}
+
+/// `send --json-input`: sends the message in the file. The options that adjust
+/// recipients, subject and headers, the preflight checks and the options about
+/// how it goes out apply as they do to `send`; those that build a body are
+/// refused by clap.
+async fn send_json(api: &Api, out: Output, config: &AppConfig, args: SendArgs) -> Result<()> {
+    let path = args.json_input.as_deref().unwrap_or(Path::new("-"));
+    let mut message = message::read(path)?;
+    if args.only_if_verified {
+        let cache = profile_path("verified-domains.json")?;
+        domains::require_verified(api, &message.from, &cache).await?;
+    }
+    if let Some(strict) = args.preflight.domain_check(config) {
+        DomainPreflight::default().check(api, &message.from, strict).await?;
+    }
+
+    if args.dedupe {
+        report_duplicates(address::dedupe(&mut message.to));
+    }
+    message.cc.extend(address::always("RUSEND_ALWAYS_CC")?);
+    address::dedupe(&mut message.cc);
+    message.bcc.extend(address::always("RUSEND_ALWAYS_BCC")?);
+    if args.bcc_self {
+        message.bcc.push(address::mailbox(&message.from).to_string());
+    }
+    address::dedupe(&mut message.bcc);
+    let moved = address::remove_present(&mut message.cc, &message.to)
+        + address::remove_present(&mut message.bcc, &message.to)
+        + address::remove_present(&mut message.bcc, &message.cc);
+    if moved > 0 {
+        eprintln!("Dropped {moved} Cc/Bcc address(es) that are already To or Cc recipients.");
+    }
+    check_reply_to_domains(&message.from, &message.reply_to, args.strict_reply_to)?;
+    let total = message.to.len() + message.cc.len() + message.bcc.len();
+    check_recipient_limit(args.max_recipients.or(config.max_recipients), args.force, total)?;
+    message.subject = args.subject_tag.apply(config, &message.subject);
+    let (html, text) = (message.html.as_deref(), message.text.as_deref());
+    args.body_limit.check("the email", html, text)?;
+    args.preflight.check_links("the email", html, text)?;
+    for (name, value) in args.headers.resolve() {
+        message.headers.retain(|(existing, _)| !existing.eq_ignore_ascii_case(&name));
+        message.headers.push((name, value));
+    }
 
-    /// Get a received email (defaults to newest when omitted)
-    ReceivedGet {
-        /// Email id (omit to show the newest email)
-        #[arg(value_name = "ID")]
-        id: Option<String>,
-    },
+    let idempotency_key = if args.idempotency_from_content {
+        let key = idempotency::from_content(
+            &message.from,
+            &message.to,
+            &message.subject,
+            message.html.as_deref(),
+            message.text.as_deref(),
+        );
+        api.trace(&format!("idempotency key {key}"));
+        Some(key)
+    } else {
+        args.idempotency_key.clone()
+    };
+    let (to, subject) = (message.to.clone(), message.subject.clone());
+    let wire_subject = if args.force_encoded_subject {
+        transfer::encode_header(&subject)
+    } else {
+        subject.clone()
+    };
+    let plain = message.build(&wire_subject);
+    let email: Idempotent<_> = match &idempotency_key {
+        Some(key) => plain.clone().with_idempotency_key(key),
+        None => plain.clone().into(),
+    };
+    if args.dry_run {
+        return dump_request(Path::new("-"), &email);
+    }
+    if let Some(path) = &args.dump_request {
+        dump_request(path, &email)?;
+    }
+    if let Some(delay) = args.delay {
+        wait::delay(api, delay).await?;
+    }
+    let (res, headers) =
+        api.send_email(&plain, idempotency_key.as_deref()).await.context("send failed")?;
+    if args.return_headers {
+        print_response_headers(out, &res.id, &headers)?;
+    } else {
+        print_submitted(out, &res.id)?;
+    }
+    if let Some(url) = &args.notify_url {
+        let summary = notify::Summary { id: &res.id, to: &to, subject: &subject, status: "sent" };
+        notify::post(url, &summary).await;
+    }
+    if args.wait || args.assert_status.is_some() {
+        wait::until_final(api, &res.id, args.wait_timeout, args.assert_status).await?;
+    }
+    Ok(())
+}
 
-    /// Generate shell completions
-    #[command(hide = true)]
-    Completions {
-        /// The shell to generate completions for
-        #[arg(value_enum)]
-        shell: clap_complete::Shell,
-    },
+/// Reports a sent email: `{"id": ...}` with `--output json`, otherwise the
+/// usual line.
+fn print_submitted(out: Output, id: &str) -> Result<()> {
+    if out.is_json() {
+        return out.print_json(&serde_json::json!({ "id": id }));
+    }
+    println!("Send request submitted.");
+    Ok(())
 }
 
-#[derive(Args)]
-struct SendArgs {
-    /// From header, e.g. "Acme <no-reply@acme.com>"
-    #[arg(short, long)]
-    from: Option<String>,
+/// `send --return-headers`: the new email's id and the headers worth keeping.
+fn print_response_headers(out: Output, id: &str, headers: &ResponseHeaders) -> Result<()> {
+    if out.is_json() {
+        return out.print_json(&serde_json::json!({ "id": id, "headers": headers }));
+    }
+    println!("Send request submitted.");
+    let show = |value: &Option<String>| value.as_deref().unwrap_or("-").to_string();
+    println!("ID: {id}");
+    println!("Request ID: {}", show(&headers.request_id));
+    println!(
+        "Rate limit: {} of {} left, resets in {}s",
+        show(&headers.ratelimit_remaining),
+        show(&headers.ratelimit_limit),
+        show(&headers.ratelimit_reset)
+    );
+    Ok(())
+}
 
-    /// To recipients, comma separated
-    #[arg(short, long)]
-    to: Option<String>,
+/// Warns (or fails when `strict`) about Reply-To addresses outside the From
+/// domain, which DMARC-minded receivers may treat as a spoofing signal.
+fn check_reply_to_domains(from: &str, reply_to: &[String], strict: bool) -> Result<()> {
+    let Some(from_domain) = address::domain(from) else {
+        return Ok(());
+    };
+    for addr in reply_to {
+        let Some(domain) = address::domain(addr) else {
+            continue;
+        };
+        if address::domains_aligned(domain, from_domain) {
+            continue;
+        }
+        let msg = format!("Reply-To {addr} is on {domain}, not the From domain {from_domain}");
+        if strict {
+            bail!("{msg} (--strict-reply-to)");
+        }
+        eprintln!("warning: {msg}");
+    }
+    Ok(())
+}
 
-    /// Subject
-    #[arg(short, long, required_unless_present = "id")]
-    subject: Option<String>,
+fn parse_to_vec(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
 
-    /// Provide HTML body inline
-    #[arg(long, conflicts_with = "id")]
-    html: Option<String>,
+fn report_duplicates(removed: usize) {
+    if removed > 0 {
+        eprintln!("Removed {removed} duplicate recipient(s).");
+    }
+}
 
-    /// Provide plain text body inline
-    #[arg(long, conflicts_with = "id")]
-    text: Option<String>,
+#[derive(Serialize)]
+struct VersionInfo {
+    rusend: &'static str,
+    resend_rs: String,
+    base_url: String,
+    config_file: Option<PathBuf>,
+    api_key_set: bool,
+}
 
-    /// Read body from stdin
-    #[arg(long, conflicts_with = "id")]
-    from_stdin: bool,
+fn print_version(out: Output) -> Result<()> {
+    // resend-rs doesn't export its version, but puts it in the User-Agent.
+    let client = client::resend("", None);
+    let user_agent = client.user_agent();
+    let info = VersionInfo {
+        rusend: env!("CARGO_PKG_VERSION"),
+        resend_rs: user_agent.rsplit('/').next().unwrap_or(user_agent).to_string(),
+        base_url: client.base_url().to_string(),
+        config_file: credentials_path().ok(),
+        api_key_set: load_config()
+            .is_ok_and(|cfg| !client::effective_api_key(&cfg.api_key).is_empty()),
+    };
+    if out.is_json() {
+        return out.print_json(&info);
+    }
+    println!("rusend {}", info.rusend);
+    println!("resend-rs {}", info.resend_rs);
+    println!("Base URL: {}", info.base_url);
+    match &info.config_file {
+        Some(path) => println!("Config file: {}", path.display()),
+        None => println!("Config file: <unavailable>"),
+    }
+    println!("API key: {}", if info.api_key_set { "set" } else { "not set" });
+    Ok(())
+}
 
-    /// Forward a received email by ID
-    #[arg(long)]
-    id: Option<String>,
+/// Writes the configuration to `path`, without the API key unless `include_keys`.
+fn export_config(path: &Path, include_keys: bool, format: Option<ConfigFormat>) -> Result<()> {
+    let mut cfg = load_config()?;
+    if !include_keys {
+        cfg.api_key.clear();
+    }
+    let content = format.unwrap_or_else(|| ConfigFormat::from_path(path)).render(&cfg)?;
+    if include_keys {
+        // It holds the key, so it gets the credentials file's mode.
+        write_private(path, &content).with_context(|| format!("write {}", path.display()))?;
+        eprintln!("Note: {} contains your API key, keep it private.", path.display());
+        println!("Exported configuration to {}.", path.display());
+    } else {
+        fs::write(path, content).with_context(|| format!("write {}", path.display()))?;
+        println!(
+            "Exported configuration to {} (without the API key, pass --include-keys for it).",
+            path.display()
+        );
+    }
+    Ok(())
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct BatchEmailInput {
-    from: String,
-    to: Vec<String>,
-    subject: String,
-    html: Option<String>,
-    text: Option<String>,
+/// Replaces the configuration with the exported one at `path`, asking first
+/// unless `yes` when one is already saved. An export without an API key
+/// keeps the current one.
+fn import_config(path: &Path, yes: bool, format: Option<ConfigFormat>) -> Result<()> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+    let mut imported: AppConfig = format
+        .unwrap_or_else(|| ConfigFormat::from_path(path))
+        .parse(&content)
+        .with_context(|| format!("{} is not an exported rusend configuration", path.display()))?;
+    let existing = credentials_path()?.exists();
+    if existing
+        && !yes
+        && !compose::confirm("Overwrite the existing configuration and credentials?")?
+    {
+        bail!("import cancelled");
+    }
+    if imported.api_key.is_empty() && existing {
+        imported.api_key = load_config()?.api_key;
+    }
+    save_config(&imported)?;
+    println!("Imported configuration from {}.", path.display());
+    Ok(())
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
+/// Set once `load_config` has offered to migrate, so it asks once per run.
+static MIGRATE_ASKED: AtomicBool = AtomicBool::new(false);
 
-    match cli.command {
-        Commands::Completions { shell } => {
-            let mut cmd = Cli::command();
-            let name = cmd.get_name().to_string();
-            clap_complete::generate(shell, &mut cmd, name, &mut io::stdout());
+/// `config migrate`: rewrites a plain-key credentials file as JSON, after
+/// copying it to the first free `credentials.bak[.N]`.
+fn migrate_config() -> Result<()> {
+    let path = credentials_path()?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            println!("Nothing to migrate: {} doesn't exist.", path.display());
+            return Ok(());
         }
-        Commands::Config { key, default_from, default_to } => {
-            let mut cfg = load_config().unwrap_or_default();
+        Err(e) => return Err(e).context("read config file"),
+    };
+    let key = content.trim();
+    if key.is_empty() || serde_json::from_str::<AppConfig>(key).is_ok() {
+        println!("{} already uses the current layout.", path.display());
+        return Ok(());
+    }
+    // Appended rather than set as the extension, which would drop a profile's name.
+    let name = path.file_name().context("config file has no name")?.to_string_lossy();
+    let backup = (0..)
+        .map(|n| match n {
+            0 => path.with_file_name(format!("{name}.bak")),
+            n => path.with_file_name(format!("{name}.bak.{n}")),
+        })
+        .find(|candidate| !candidate.exists())
+        .context("no free backup file name")?;
+    fs::copy(&path, &backup).with_context(|| format!("back up to {}", backup.display()))?;
+    save_config(&AppConfig {
+        api_key: key.to_string(),
+        ..Default::default()
+    })?;
+    println!("Backed up {} to {}.", path.display(), backup.display());
+    println!("Moved the API key into the JSON settings in {}.", path.display());
+    Ok(())
+}
 
-            if let Some(k) = key {
-                cfg.api_key = k;
-            } else if cfg.api_key.is_empty() {
-                println!("Enter your resend API key (starts with re_):");
-                cfg.api_key = rpassword::read_password().context("failed to read api key")?;
+/// `config unset`: forgets the API key, or with `all` the whole configuration.
+fn unset_config(all: bool) -> Result<()> {
+    if !all && PROFILE.get().is_none() {
+        let mut saved = saved_profiles()?;
+        if config_path("credentials")?.exists() {
+            saved.insert(0, "the default".to_string());
+        }
+        if saved.len() > 1 {
+            bail!(
+                "several profiles are saved ({}); say which key to remove with \
+                 `rusend --profile <NAME> config unset`, or pass --all",
+                saved.join(", ")
+            );
+        }
+    }
+    let path = credentials_path()?;
+    if all {
+        let mut removed = 0;
+        for name in ["credentials", "received-bookmark.json", "verified-domains.json"] {
+            let file = profile_path(name)?;
+            match fs::remove_file(&file) {
+                Ok(()) => {
+                    println!("Removed {}.", file.display());
+                    removed += 1;
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e).with_context(|| format!("remove {}", file.display())),
             }
+        }
+        if removed == 0 {
+            println!("Nothing stored.");
+        }
+        return Ok(());
+    }
+    let mut cfg = load_config()?;
+    if cfg.api_key.is_empty() {
+        println!("No API key stored.");
+        return Ok(());
+    }
+    cfg.api_key.clear();
+    save_config(&cfg)?;
+    println!("API key removed from {}.", path.display());
+    Ok(())
+}
 
-            if let Some(f) = default_from {
-                cfg.default_from = Some(f);
+fn manage_senders(command: SendersCommand, out: Output) -> Result<()> {
+    let mut cfg = load_config()?;
+    match command {
+        SendersCommand::Add { name, from } => {
+            if !address::is_valid(address::mailbox(&from)) {
+                bail!("invalid From address {from:?}");
             }
-            if let Some(t) = default_to {
-                cfg.default_to = Some(t);
+            cfg.senders.insert(name.clone(), from);
+            save_config(&cfg)?;
+            println!("Saved sender {name}.");
+        }
+        SendersCommand::Remove { name } => {
+            if cfg.senders.remove(&name).is_none() {
+                bail!("no sender named {name:?}");
             }
-
             save_config(&cfg)?;
-            println!("Configuration saved.");
+            println!("Removed sender {name}.");
         }
-        Commands::Send(args) => {
-            let config = load_config()?;
-            let api_key = config.api_key;
-            let resend = Resend::new(&api_key);
-
-            let from_addr = args.from.or(config.default_from).context("From address not provided and no default set")?;
-            let to_addr = args.to.or(config.default_to).context("To address not provided and no default set")?;
-
-            let (subject, body_html, body_text) = if let Some(ref id) = args.id {
-                let email_id = resolve_received_email_id(&resend, Some(id.clone())).await?;
-                let r = resend
-                    .receiving
-                    .get(&email_id)
-                    .await
-                    .context("get received email for forwarding failed")?;
-                
-                let subject = args.subject.clone().unwrap_or_else(|| format!("Fwd: {}", r.subject));
-                (subject, r.html, r.text)
-            } else {
-                let body_html = if args.from_stdin {
-                    let mut s = String::new();
-                    io::stdin().read_to_string(&mut s).context("stdin read")?;
-                    Some(s)
-                } else {
-                    args.html.clone()
-                };
-                // Safety: clap ensures subject is present if id is missing
-                (args.subject.clone().unwrap(), body_html, args.text.clone())
-            };
-
-            let mut email =
-                CreateEmailBaseOptions::new(&from_addr, parse_to_vec(&to_addr), &subject);
-            
-            if let Some(h) = body_html {
-                email = email.with_html(&h);
+        SendersCommand::List if out.is_json() => out.print_json(&cfg.senders)?,
+        SendersCommand::List => {
+            if cfg.senders.is_empty() {
+                println!("No senders configured.");
             }
-            if let Some(t) = body_text {
-                email = email.with_text(&t);
+            for (name, from) in &cfg.senders {
+                println!("{name}: {from}");
             }
-
-            let _res = resend.emails.send(email).await.context("send failed")?;
-            println!("Send request submitted.");
         }
-        Commands::Batch { file } => {
-            let api_key = load_config()?.api_key;
-            let resend = Resend::new(&api_key);
+    }
+    Ok(())
+}
 
-            let content = fs::read_to_string(&file).context("read batch file")?;
-            let batch: Vec<BatchEmailInput> =
-                serde_json::from_str(&content).context("parse json")?;
+/// Makes a cheap authenticated call so a mistyped key is caught before it's saved.
+async fn validate_key(key: &str, log: Option<ApiLog>, retry: Retry) -> Result<()> {
+    let api = Api::with_key(key, log, retry);
+    let opts = ListOptions::default().with_limit(1);
+    match api.call("GET /domains", || api.resend.domains.list(opts.clone())).await {
+        Ok(_) => Ok(()),
+        // Sending-only keys authenticate but may not list domains.
+        Err(resend_rs::Error::Resend(e)) if e.kind() == ErrorKind::RestrictedApiKey => Ok(()),
+        Err(resend_rs::Error::Resend(e))
+            if matches!(e.kind(), ErrorKind::InvalidApiKey | ErrorKind::MissingApiKey) =>
+        {
+            bail!("the API key was rejected ({e}); configuration not saved")
+        }
+        Err(e) => {
+            Err(e).context("could not validate the API key (pass --no-validate when offline)")
+        }
+    }
+}
 
-            let emails: Vec<CreateEmailBaseOptions> = batch
-                .into_iter()
-                .map(|b| {
-                    let mut e = CreateEmailBaseOptions::new(&b.from, b.to, &b.subject);
-                    if let Some(h) = b.html {
-                        e = e.with_html(&h);
-                    }
-                    if let Some(t) = b.text {
-                        e = e.with_text(&t);
-                    }
-                    e
-                })
-                .collect();
+/// `config rotate-key`: creates a key with the stored one, validates and saves
+/// it, then optionally revokes the old key, printing each step.
+async fn rotate_key(
+    name: Option<String>,
+    revoke_old: Option<String>,
+    yes: bool,
+    log: Option<ApiLog>,
+    retry: Retry,
+) -> Result<()> {
+    let mut cfg = load_config()?;
+    if cfg.api_key.is_empty() {
+        bail!("no API key stored to rotate (save one with `rusend config --key`)");
+    }
+    let api = Api::with_key(&cfg.api_key, log, retry);
 
-            let _res = resend
-                .batch
-                .send(emails)
-                .await
-                .context("batch send failed")?;
-            println!("Batch send request submitted.");
-        }
-        Commands::List { count } => {
-            let api_key = load_config()?.api_key;
-            let resend = Resend::new(&api_key);
-            let limit = count.map(NonZeroUsize::get).unwrap_or(10);
-            let emails = resend
-                .emails
-                .list(Default::default())
+    // Looked up first, so a wrong ID fails before anything changes.
+    let old = match &revoke_old {
+        Some(id) => {
+            let keys = api
+                .call("GET /api-keys", || api.resend.api_keys.list(ListOptions::default()))
                 .await
-                .context("list failed")?;
-            for email in emails.data.into_iter().take(limit) {
-                println!(
-                    "ID: {}, Created: {}, From: {}, To: {:?}",
-                    email.id, email.created_at, email.from, email.to
-                );
+                .context("list API keys failed")?;
+            let old = keys.data.into_iter().find(|k| k.id.as_ref() == id.as_str());
+            Some(old.with_context(|| format!("no API key with ID {id}"))?)
+        }
+        None => None,
+    };
+
+    let name =
+        name.unwrap_or_else(|| format!("rusend-{}", chrono::Local::now().format("%Y-%m-%d")));
+    // Not retried when it may have gone through, or one rotation could create several keys.
+    let created = api
+        .send("POST /api-keys", false, || {
+            api.resend.api_keys.create(CreateApiKeyOptions::new(&name).with_full_access())
+        })
+        .await
+        .context("create API key failed; nothing was saved or revoked")?;
+    println!("Created API key {name} ({}).", created.id);
+
+    validate_key(&created.token, None, retry).await.with_context(|| {
+        format!(
+            "the new key {} was created but not saved; delete it in the dashboard",
+            created.id
+        )
+    })?;
+    println!("Validated the new key.");
+
+    cfg.api_key = created.token;
+    save_config(&cfg)?;
+    println!("Saved it to {}.", credentials_path()?.display());
+
+    let Some(old) = old else {
+        println!("The old key still works; revoke it in the dashboard once nothing uses it.");
+        return Ok(());
+    };
+    if !yes && !compose::confirm(&format!("Revoke the old key {} ({})?", old.name, old.id))? {
+        println!("Kept the old key {} ({}).", old.name, old.id);
+        return Ok(());
+    }
+    api.call("DELETE /api-keys/{id}", || api.resend.api_keys.delete(&old.id))
+        .await
+        .context("revoke old API key failed")?;
+    println!("Revoked the old key {} ({}).", old.name, old.id);
+    Ok(())
+}
+
+/// What `send --dry-run --verbose` reports about the request it didn't send.
+struct DryRun<'a> {
+    from: &'a str,
+    to: &'a [String],
+    cc: &'a [String],
+    bcc: &'a [String],
+    subject: &'a str,
+    html: Option<&'a str>,
+    text: Option<&'a str>,
+    /// Attachments other than the inline images, and their size.
+    attachments: usize,
+    attachment_bytes: u64,
+    inline_images: usize,
+    headers: &'a [(String, String)],
+    individual: bool,
+    idempotency_key: Option<&'a str>,
+}
+
+impl DryRun<'_> {
+    /// Prints the summary to stderr, leaving stdout to the JSON body.
+    fn print(&self) {
+        let recipients = self.to.len() + self.cc.len() + self.bcc.len();
+        let mut lines = vec![
+            ("From", self.from.to_string()),
+            (
+                "Recipients",
+                format!(
+                    "{recipients} ({} To, {} Cc, {} Bcc)",
+                    self.to.len(),
+                    self.cc.len(),
+                    self.bcc.len()
+                ),
+            ),
+        ];
+        for (label, addrs) in [("To", self.to), ("Cc", self.cc), ("Bcc", self.bcc)] {
+            if !addrs.is_empty() {
+                lines.push((label, address::preview(addrs.iter().map(String::as_str))));
             }
         }
-        Commands::Get { id } => {
-            let api_key = load_config()?.api_key;
-            let resend = Resend::new(&api_key);
-            let email_id = resolve_sent_email_id(&resend, id).await?;
-            let email = resend.emails.get(&email_id).await.context("get failed")?;
-            println!("ID: {}", email.id);
-            println!("Created: {}", email.created_at);
-            println!("From: {}", email.from);
-            println!("To: {:?}", email.to);
-            println!("Subject: {}", email.subject);
-            print_email_body(email.text.as_deref(), email.html.as_deref());
+        lines.push(("Subject", self.subject.to_string()));
+        if self.individual {
+            lines.push(("Messages", format!("{}, one per To address", self.to.len())));
         }
-        Commands::Update { id, scheduled_at } => {
-            let api_key = load_config()?.api_key;
-            let resend = Resend::new(&api_key);
-            let mut upd = UpdateEmailOptions::new();
-            if let Some(s) = scheduled_at {
-                upd = upd.with_scheduled_at(&s);
-            }
-            let email = resend
-                .emails
-                .update(&id, upd)
-                .await
-                .context("update failed")?;
-            println!("Updated email with ID: {}", email.id);
+        let size = self.html.map_or(0, str::len) + self.text.map_or(0, str::len);
+        let body = match (self.html, self.text) {
+            (Some(_), Some(_)) => format!("HTML and text, {size} bytes"),
+            (Some(_), None) => format!("HTML only, {size} bytes"),
+            (None, Some(_)) => format!("text only, {size} bytes"),
+            (None, None) => "none".to_string(),
+        };
+        lines.push(("Body", body));
+        let mut attachments = if self.attachments == 0 {
+            "none".to_string()
+        } else {
+            format!("{}, {} bytes", self.attachments, self.attachment_bytes)
+        };
+        if self.inline_images > 0 {
+            attachments.push_str(&format!(", plus {} inline image(s)", self.inline_images));
         }
-        Commands::Cancel { id } => {
-            let api_key = load_config()?.api_key;
-            let resend = Resend::new(&api_key);
-            let canceled = resend.emails.cancel(&id).await.context("cancel failed")?;
-            println!("Canceled: {}", canceled.id);
+        lines.push(("Attachments", attachments));
+        if !self.headers.is_empty() {
+            let names: Vec<&str> = self.headers.iter().map(|(name, _)| name.as_str()).collect();
+            lines.push(("Headers", names.join(", ")));
         }
-        Commands::ReceivedList { count } => {
-            let api_key = load_config()?.api_key;
-            let resend = Resend::new(&api_key);
-            let limit = count.map(NonZeroUsize::get).unwrap_or(10);
-            let list = resend
-                .receiving
-                .list(Default::default())
-                .await
-                .context("list receiving failed")?;
-            for email in list.data.into_iter().take(limit) {
-                println!(
-                    "ID: {}, Created: {}, From: {}, To: {:?}",
-                    email.id, email.created_at, email.from, email.to
-                );
-            }
+        if let Some(key) = self.idempotency_key {
+            lines.push(("Idempotency key", key.to_string()));
         }
-        Commands::ReceivedGet { id } => {
-            let api_key = load_config()?.api_key;
-            let resend = Resend::new(&api_key);
-            let email_id = resolve_received_email_id(&resend, id).await?;
-            let r = resend
-                .receiving
-                .get(&email_id)
-                .await
-                .context("get receiving failed")?;
-            println!("ID: {}", r.id);
-            println!("Created: {}", r.created_at);
-            println!("From: {}", r.from);
-            println!("To: {:?}", r.to);
-            println!("Subject: {}", r.subject);
-            print_email_body(r.text.as_deref(), r.html.as_deref());
+        eprintln!("Dry run, nothing was sent:");
+        for (label, value) in lines {
+            eprintln!("  {:<16} {value}", format!("{label}:"));
         }
     }
+}
 
+/// `batch --max-per-domain`: reports the held-back messages on stderr, and
+/// writes them to the `--overflow-file` if one is given.
+fn report_overflow(
+    overflow: &batch::Overflow,
+    max: NonZeroUsize,
+    file: Option<&Path>,
+) -> Result<()> {
+    if overflow.held.is_empty() {
+        if let Some(path) = file {
+            // Emptied, so a stale file from an earlier run isn't sent again.
+            fs::write(path, "[]\n")
+                .with_context(|| format!("write --overflow-file {}", path.display()))?;
+        }
+        return Ok(());
+    }
+    let domains: Vec<String> = overflow
+        .domains
+        .iter()
+        .map(|(domain, n)| format!("{domain} ({n} over)"))
+        .collect();
+    eprintln!(
+        "Holding back {} message(s) over --max-per-domain {max}: {}",
+        overflow.held.len(),
+        domains.join(", ")
+    );
+    match file {
+        Some(path) => {
+            let mut json = serde_json::to_string_pretty(&overflow.held)?;
+            json.push('\n');
+            fs::write(path, json)
+                .with_context(|| format!("write --overflow-file {}", path.display()))?;
+            eprintln!(
+                "Wrote them to {}; send them in a later run with `rusend batch {}`.",
+                path.display(),
+                path.display()
+            );
+        }
+        None => eprintln!("They are not sent (pass --overflow-file to keep them)."),
+    }
     Ok(())
 }
 
-fn parse_to_vec(s: &str) -> Vec<String> {
-    s.split(',')
-        .map(|p| p.trim().to_string())
-        .filter(|p| !p.is_empty())
-        .collect()
+/// Writes `body`, the JSON that is about to be sent, to `path` (`-` for stdout).
+///
+/// On stdout the recipients are masked with `--mask-recipients`; a file keeps them.
+fn dump_request(path: &Path, body: &impl Serialize) -> Result<()> {
+    if path == Path::new("-") {
+        let mut body = serde_json::to_value(body)?;
+        address::mask_request(&mut body);
+        println!("{}", serde_json::to_string_pretty(&body)?);
+        return Ok(());
+    }
+    let mut json = serde_json::to_string_pretty(body)?;
+    json.push('\n');
+    fs::write(path, json).with_context(|| format!("write request dump {}", path.display()))
+}
+
+/// How many emails a list command shows: COUNT, else the configured default, else 10.
+fn list_count(count: Option<NonZeroUsize>, config: &AppConfig) -> usize {
+    count.or(config.default_list_count).map_or(10, NonZeroUsize::get)
+}
+
+/// The subject template for a `--template` batch.
+fn batch_subject(args: &BatchArgs) -> Result<Option<String>> {
+    match &args.subject_file {
+        Some(path) => read_subject_file(path).map(Some),
+        None => Ok(args.subject.clone()),
+    }
+}
+
+/// The `--default-*` subject and bodies of a file batch, with the files read.
+fn batch_defaults(args: &BatchArgs) -> Result<batch::Defaults> {
+    let read = |path: &Option<PathBuf>| {
+        path.as_ref()
+            .map(|p| fs::read_to_string(p).with_context(|| format!("read {}", p.display())))
+            .transpose()
+    };
+    Ok(batch::Defaults {
+        subject: args.default_subject.clone(),
+        html: read(&args.default_html_file)?,
+        text: read(&args.default_text_file)?,
+    })
+}
+
+/// `batch --validate-only`: runs every preflight check and lists each problem.
+async fn validate_batch(api: &Api, args: &BatchArgs) -> Result<()> {
+    let entries = match (&args.file, &args.template, &args.data) {
+        (_, Some(template), Some(data)) => {
+            batch::merge_entries(template, data, batch_subject(args)?.as_deref())?
+        }
+        (Some(file), _, _) => batch::read_entries(file, args.format)?,
+        _ => unreachable!("clap requires a file or --template/--data"),
+    };
+    let total = entries.len();
+    let pool = args.from_pool.as_deref().map(parse_to_vec).unwrap_or_default();
+    let defaults = batch_defaults(args)?;
+    let problems =
+        batch::validate(api, entries, &pool, &defaults, args.attach_max_total).await?;
+    if problems.is_empty() {
+        println!("All {total} message(s) passed validation.");
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("{problem}");
+    }
+    bail!("{} problem(s) found in {total} message(s), nothing was sent", problems.len())
+}
+
+/// Reads a subject from the first line of `path`, trimmed.
+fn read_subject_file(path: &Path) -> Result<String> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("read subject file {}", path.display()))?;
+    let subject = content.lines().next().unwrap_or_default().trim();
+    if subject.is_empty() {
+        bail!("subject file {} is empty", path.display());
+    }
+    Ok(subject.to_string())
+}
+
+fn print_received(email: &resend_rs::types::InboundEmail, out: Output, headers: bool) {
+    println!("ID: {}", email.id);
+    println!("Created: {}", out.time(&email.created_at));
+    println!("From: {}", email.from);
+    println!("To: {:?}", email.to);
+    println!("Subject: {}", email.subject);
+    if headers {
+        // The API returns a map, so headers come sorted by name rather than as sent.
+        let mut sorted: Vec<_> = email.headers.iter().collect();
+        sorted.sort_by_key(|(name, _)| name.to_ascii_lowercase());
+        println!("Headers:");
+        for (name, value) in sorted {
+            println!("  {name}: {value}");
+        }
+    }
+    print_email_body(email.text.as_deref(), email.html.as_deref());
 }
 
 fn print_email_body(text: Option<&str>, html: Option<&str>) {
@@ -344,27 +3097,163 @@ fn project_dirs() -> Result<ProjectDirs> {
     ProjectDirs::from("com", "resend", "rusend").context("cannot determine configuration directory")
 }
 
+/// The directory `send --template-name` reads from.
+fn templates_dir(config: &AppConfig) -> Result<PathBuf> {
+    match &config.templates_dir {
+        Some(dir) => Ok(dir.clone()),
+        None => config_path("templates"),
+    }
+}
+
 fn credentials_path() -> Result<PathBuf> {
+    profile_path("credentials")
+}
+
+/// Set by the global `--profile`.
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+/// `name` inside the config dir, for the `--profile` in use: `credentials.prod`
+/// or `received-bookmark.prod.json` with `--profile prod`, `name` itself without one.
+fn profile_path(name: &str) -> Result<PathBuf> {
+    let Some(profile) = PROFILE.get() else {
+        return config_path(name);
+    };
+    match name.split_once('.') {
+        Some((stem, ext)) => config_path(&format!("{stem}.{profile}.{ext}")),
+        None => config_path(&format!("{name}.{profile}")),
+    }
+}
+
+/// The names of the profiles saved besides the default one, sorted.
+fn saved_profiles() -> Result<Vec<String>> {
+    let dir = project_dirs()?.config_dir().to_path_buf();
+    let entries = match fs::read_dir(&dir) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        entries => entries.with_context(|| format!("read {}", dir.display()))?,
+    };
+    let mut profiles = Vec::new();
+    for entry in entries {
+        let name = entry.with_context(|| format!("read {}", dir.display()))?.file_name();
+        // `credentials.bak` is the backup `config migrate` keeps, not a profile.
+        if let Some(profile) = name.to_str().and_then(|n| n.strip_prefix("credentials."))
+            && profile != "bak"
+            && parse_profile(profile).is_ok()
+        {
+            profiles.push(profile.to_string());
+        }
+    }
+    profiles.sort();
+    Ok(profiles)
+}
+
+/// Parses a `--profile` name, which becomes part of file names: letters,
+/// digits, `-` and `_`.
+fn parse_profile(s: &str) -> Result<String, String> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("invalid profile name {s:?}: use letters, digits, - and _"));
+    }
+    Ok(s.to_string())
+}
+
+/// `name` inside the config dir, which is created if needed.
+fn config_path(name: &str) -> Result<PathBuf> {
     let pd = project_dirs()?;
     let cfg = pd.config_dir();
     fs::create_dir_all(cfg).context("create config dir")?;
-    Ok(cfg.join("credentials"))
+    Ok(cfg.join(name))
 }
 
+/// Writes a temp file next to the credentials and renames it over them, so a
+/// concurrent `load_config` sees the old file or the new one, never half of one.
 fn save_config(cfg: &AppConfig) -> Result<()> {
-    let path = credentials_path()?;
     let content = serde_json::to_string_pretty(cfg)?;
-    fs::write(path, content).context("write config file")?;
+    write_private(&credentials_path()?, &content).context("write config file")
+}
+
+/// Writes `content` to `path` through a temp file next to it, so readers never
+/// see half a file and the result is readable by its owner only (mode 600).
+fn write_private(path: &Path, content: &str) -> Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut file = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("create a temp file in {}", dir.display()))?;
+    file.write_all(content.as_bytes())?;
+    file.persist(path).with_context(|| format!("replace {}", path.display()))?;
+    Ok(())
+}
+
+/// Set by the global `--fix-permissions`.
+static FIX_PERMISSIONS: AtomicBool = AtomicBool::new(false);
+
+/// Set once `load_config` has checked the credentials file's mode, so it warns once per run.
+static PERMISSIONS_CHECKED: AtomicBool = AtomicBool::new(false);
+
+/// Warns when the credentials file at `path` can be read by other users,
+/// and restricts it to its owner with `--fix-permissions` or when the user
+/// agrees to at the prompt.
+#[cfg(unix)]
+fn check_permissions(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if PERMISSIONS_CHECKED.swap(true, Ordering::Relaxed) {
+        return Ok(());
+    }
+    let mode = fs::metadata(path).context("read config file")?.permissions().mode() & 0o777;
+    if mode & 0o077 == 0 {
+        return Ok(());
+    }
+    eprintln!(
+        "warning: {} is accessible to other users (mode {mode:o}), so your API key may be exposed.",
+        path.display()
+    );
+    let fix = FIX_PERMISSIONS.load(Ordering::Relaxed)
+        || (compose::interactive() && compose::confirm("Restrict it to your user (mode 600)?")?);
+    if fix {
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("restrict {}", path.display()))?;
+        eprintln!("Restricted {} to mode 600.", path.display());
+    } else {
+        eprintln!("Run `chmod 600 {}` or pass --fix-permissions.", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_permissions(_path: &Path) -> Result<()> {
     Ok(())
 }
 
 fn load_config() -> Result<AppConfig> {
     let path = credentials_path()?;
     if !path.exists() {
+        // RESEND_API_KEY stands in for a missing profile, just as it wins over a saved one.
+        let env_key = std::env::var("RESEND_API_KEY").is_ok_and(|key| !key.is_empty());
+        if let Some(profile) = PROFILE.get().filter(|_| !env_key) {
+            bail!(
+                "no profile {profile:?} is saved ({} doesn't exist); \
+                 create it with `rusend config --profile {profile}`",
+                path.display()
+            );
+        }
         // If no file exists, return default (empty API key) so we can prompt or fail gracefully
         return Ok(AppConfig::default());
     }
-    let content = fs::read_to_string(path).context("read config file")?;
+    check_permissions(&path)?;
+    // The file can vanish between the check and the read while another process
+    // replaces it (or on platforms where the rename isn't atomic); try again briefly.
+    let mut attempts = 0;
+    let content = loop {
+        match fs::read_to_string(&path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound && attempts < 3 => {
+                attempts += 1;
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(AppConfig::default()),
+            result => break result.context("read config file")?,
+        }
+    };
     let content = content.trim();
 
     // Try parsing as JSON first
@@ -374,6 +3263,12 @@ fn load_config() -> Result<AppConfig> {
 
     // Fallback: assume it is just the API key (legacy format)
     if !content.is_empty() {
+        if compose::interactive() && !MIGRATE_ASKED.swap(true, Ordering::Relaxed) {
+            eprintln!("{} holds only an API key, the layout of older releases.", path.display());
+            if compose::confirm("Convert it now (`rusend config migrate`)?")? {
+                migrate_config()?;
+            }
+        }
         return Ok(AppConfig {
             api_key: content.to_string(),
             ..Default::default()
@@ -390,13 +3285,40 @@ fn load_config() -> Result<AppConfig> {
     Ok(AppConfig::default())
 }
 
-async fn resolve_sent_email_id(resend: &Resend, provided: Option<String>) -> Result<String> {
+/// Separates the results of fetching `ids`. With a single id its error is
+/// returned as is; with several, failures are reported and counted.
+fn split_fetched<T, E: Into<anyhow::Error>>(
+    ids: &[String],
+    results: Vec<Result<T, E>>,
+) -> Result<(Vec<T>, usize)> {
+    let mut fetched = Vec::new();
+    let mut failed = 0;
+    for (id, result) in ids.iter().zip(results) {
+        match result {
+            Ok(item) => fetched.push(item),
+            Err(e) if ids.len() == 1 => return Err(e.into()).context("get failed"),
+            Err(e) => {
+                eprintln!("get {id} failed: {:#}", e.into());
+                failed += 1;
+            }
+        }
+    }
+    Ok((fetched, failed))
+}
+
+fn fetch_outcome(failed: usize, total: usize) -> Result<()> {
+    if failed > 0 {
+        bail!("{failed} of {total} email(s) could not be fetched");
+    }
+    Ok(())
+}
+
+async fn resolve_sent_email_id(api: &Api, provided: Option<String>) -> Result<String> {
     if let Some(id) = provided {
         return Ok(id);
     }
-    let emails = resend
-        .emails
-        .list(Default::default())
+    let emails = api
+        .call("GET /emails", || api.resend.emails.list(Default::default()))
         .await
         .context("list sent emails to find newest")?;
     if let Some(email) = emails.data.into_iter().next() {
@@ -406,13 +3328,15 @@ async fn resolve_sent_email_id(resend: &Resend, provided: Option<String>) -> Res
     }
 }
 
-async fn resolve_received_email_id(resend: &Resend, provided: Option<String>) -> Result<String> {
+async fn resolve_received_email_id(api: &Api, provided: Option<String>) -> Result<String> {
     if let Some(id) = provided {
         return Ok(id);
     }
-    let emails = resend
-        .receiving
-        .list(Default::default())
+    let emails = api
+        .call(
+            "GET /emails/receiving",
+            || api.resend.receiving.list(Default::default()),
+        )
         .await
         .context("list received emails to find newest")?;
     if let Some(email) = emails.data.into_iter().next() {
@@ -422,16 +3346,32 @@ async fn resolve_received_email_id(resend: &Resend, provided: Option<String>) ->
     }
 }
 
-// Note: This small CLI focuses on covering the common resend endpoints. Attachments,
-// advanced send options, and OAuth-style flows are left as future improvements.
+/// `config --from-stdin`: all of stdin, trimmed, which must be a single key.
+fn read_key_from_stdin() -> Result<String> {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input).context("failed to read api key from stdin")?;
+    let key = input.trim();
+    if key.is_empty() {
+        bail!("--from-stdin read no API key");
+    }
+    if key.contains(char::is_whitespace) {
+        bail!("--from-stdin expects only the API key, but stdin has more than one word");
+    }
+    Ok(key.to_string())
+}
 
-// Add minimal helper to allow rpassword to be used
-mod rpassword {
-    use std::io;
-    pub fn read_password() -> io::Result<String> {
-        // very simple: read from stdin (works when user types and presses enter)
-        let mut s = String::new();
-        io::stdin().read_line(&mut s)?;
-        Ok(s.trim().to_string())
+/// Asks for the API key without echoing it. When stdin isn't a terminal,
+/// e.g. `echo $KEY | rusend config`, the first line of it is taken instead.
+fn prompt_key() -> io::Result<String> {
+    if io::stdin().is_terminal() {
+        return rpassword::prompt_password("Enter your resend API key (starts with re_): ")
+            .map(|key| key.trim().to_string());
     }
+    println!("Enter your resend API key (starts with re_):");
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    Ok(line.trim().to_string())
 }
+
+// Note: This small CLI focuses on covering the common resend endpoints. Advanced
+// send options and OAuth-style flows are left as future improvements.
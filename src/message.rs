@@ -0,0 +1,329 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use resend_rs::types::{CreateAttachment, CreateEmailBaseOptions, Tag};
+use serde_json::{Map, Value};
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::address;
+
+/// The fields `send --json-input` accepts, the names the API uses for them.
+const FIELDS: [&str; 12] = [
+    "from", "to", "cc", "bcc", "reply_to", "subject", "html", "text", "headers", "tags",
+    "attachments", "scheduled_at",
+];
+
+/// A message read by `send --json-input`, checked but not built yet, so the
+/// send options can still adjust its recipients, subject and headers.
+pub struct Message {
+    pub from: String,
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub bcc: Vec<String>,
+    pub reply_to: Vec<String>,
+    pub subject: String,
+    pub html: Option<String>,
+    pub text: Option<String>,
+    pub headers: Vec<(String, String)>,
+    tags: Vec<Tag>,
+    attachments: Vec<CreateAttachment>,
+    scheduled_at: Option<String>,
+}
+
+/// `send --json-input`: reads one message object from `path` (`-` for stdin).
+///
+/// The object is checked field by field before anything is built, and every
+/// problem is reported with its path, like `to[1]` or `tags[0].name`.
+pub fn read(path: &Path) -> Result<Message> {
+    let content = if path.as_os_str() == "-" {
+        let mut s = String::new();
+        io::stdin().read_to_string(&mut s).context("read --json-input from stdin")?;
+        s
+    } else {
+        fs::read_to_string(path)
+            .with_context(|| format!("read --json-input file {}", path.display()))?
+    };
+    let value: Value = serde_json::from_str(&content).context("parse --json-input")?;
+    parse(&value).map_err(|problems| {
+        anyhow::anyhow!("invalid --json-input message:\n  {}", problems.join("\n  "))
+    })
+}
+
+/// Reads the message, or lists what is wrong with `value`.
+fn parse(value: &Value) -> Result<Message, Vec<String>> {
+    let Some(object) = value.as_object() else {
+        return Err(vec![format!("expected a JSON object, got {}", kind(value))]);
+    };
+    let mut problems = Vec::new();
+    for name in object.keys().filter(|name| !FIELDS.contains(&name.as_str())) {
+        problems.push(format!("{name}: unknown field"));
+    }
+
+    let from = required_string(object, "from", &mut problems);
+    if let Some(from) = &from
+        && !address::is_valid(address::mailbox(from))
+    {
+        problems.push(format!("from: invalid address {from:?}"));
+    }
+    let to = addresses(object, "to", &mut problems);
+    if object.get("to").is_none() {
+        problems.push("to: missing".to_string());
+    } else if to.is_empty() && object["to"].is_array() {
+        problems.push("to: needs at least one address".to_string());
+    }
+    let cc = addresses(object, "cc", &mut problems);
+    let bcc = addresses(object, "bcc", &mut problems);
+    let reply_to = addresses(object, "reply_to", &mut problems);
+    let subject = required_string(object, "subject", &mut problems);
+    let html = optional_string(object, "html", &mut problems);
+    let text = optional_string(object, "text", &mut problems);
+    if object.get("html").is_none() && object.get("text").is_none() {
+        problems.push("html, text: at least one body is required".to_string());
+    }
+    let scheduled_at = optional_string(object, "scheduled_at", &mut problems);
+
+    let mut headers = Vec::new();
+    match object.get("headers") {
+        None | Some(Value::Null) => {}
+        Some(Value::Object(map)) => {
+            for (name, value) in map {
+                match value.as_str() {
+                    Some(v) if v.contains(['\r', '\n']) => {
+                        problems.push(format!("headers.{name}: line break in the value"));
+                    }
+                    Some(v) => headers.push((name.clone(), v.to_string())),
+                    None => problems.push(format!("headers.{name}: expected a string")),
+                }
+            }
+        }
+        Some(other) => problems.push(format!("headers: expected an object, got {}", kind(other))),
+    }
+
+    let mut tags = Vec::new();
+    for (i, tag) in array(object, "tags", &mut problems).iter().enumerate() {
+        let at = format!("tags[{i}]");
+        let Some(tag) = expect_object(tag, &at, &mut problems) else { continue };
+        let mut nested = Vec::new();
+        let name = required_string(tag, "name", &mut nested);
+        let value = required_string(tag, "value", &mut nested);
+        if let (Some(name), Some(value)) = (name, value) {
+            tags.push(Tag::new(&name, &value));
+        }
+        problems.extend(nested.into_iter().map(|p| format!("{at}.{p}")));
+    }
+
+    let mut attachments = Vec::new();
+    for (i, attachment) in array(object, "attachments", &mut problems).iter().enumerate() {
+        let at = format!("attachments[{i}]");
+        let Some(attachment) = expect_object(attachment, &at, &mut problems) else { continue };
+        let mut nested = Vec::new();
+        if let Some(built) = parse_attachment(attachment, &mut nested) {
+            attachments.push(built);
+        }
+        problems.extend(nested.into_iter().map(|p| format!("{at}.{p}")));
+    }
+
+    let (Some(from), Some(subject)) = (from, subject) else {
+        return Err(problems);
+    };
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+    Ok(Message {
+        from,
+        to,
+        cc,
+        bcc,
+        reply_to,
+        subject,
+        html,
+        text,
+        headers,
+        tags,
+        attachments,
+        scheduled_at,
+    })
+}
+
+impl Message {
+    /// The request to send, with `subject` as it goes on the wire.
+    pub fn build(self, subject: &str) -> CreateEmailBaseOptions {
+        let mut email = CreateEmailBaseOptions::new(&self.from, self.to, subject);
+        for addr in &self.cc {
+            email = email.with_cc(addr);
+        }
+        for addr in &self.bcc {
+            email = email.with_bcc(addr);
+        }
+        if !self.reply_to.is_empty() {
+            email = email.with_reply_multiple(&self.reply_to);
+        }
+        if let Some(html) = &self.html {
+            email = email.with_html(html);
+        }
+        if let Some(text) = &self.text {
+            email = email.with_text(text);
+        }
+        for (name, value) in &self.headers {
+            email = email.with_header(name, value);
+        }
+        for tag in self.tags {
+            email = email.with_tag(tag);
+        }
+        for attachment in self.attachments {
+            email = email.with_attachment(attachment);
+        }
+        if let Some(at) = &self.scheduled_at {
+            email = email.with_scheduled_at(at);
+        }
+        email
+    }
+}
+
+/// An attachment as the API takes it: base64 `content` (with a `filename`),
+/// or a `path` URL for Resend to fetch, plus an optional `content_type` and `content_id`.
+fn parse_attachment(
+    object: &Map<String, Value>,
+    problems: &mut Vec<String>,
+) -> Option<CreateAttachment> {
+    const KEYS: [&str; 5] = ["filename", "content", "path", "content_type", "content_id"];
+    for name in object.keys().filter(|name| !KEYS.contains(&name.as_str())) {
+        problems.push(format!("{name}: unknown field"));
+    }
+    let filename = optional_string(object, "filename", problems);
+    let content = optional_string(object, "content", problems);
+    let path = optional_string(object, "path", problems);
+    let content_type = optional_string(object, "content_type", problems);
+    let content_id = optional_string(object, "content_id", problems);
+    let mut attachment = match (content, path) {
+        (Some(content), None) => {
+            if filename.is_none() {
+                problems.push("filename: required with content".to_string());
+            }
+            match STANDARD.decode(content.trim()) {
+                Ok(bytes) => CreateAttachment::from_content(bytes),
+                Err(e) => {
+                    problems.push(format!("content: not valid base64: {e}"));
+                    return None;
+                }
+            }
+        }
+        (None, Some(path)) => CreateAttachment::from_path(&path),
+        (Some(_), Some(_)) => {
+            problems.push("content, path: give one, not both".to_string());
+            return None;
+        }
+        (None, None) => {
+            if !object.contains_key("content") && !object.contains_key("path") {
+                problems.push("content, path: one of them is required".to_string());
+            }
+            return None;
+        }
+    };
+    if let Some(filename) = &filename {
+        attachment = attachment.with_filename(filename);
+    }
+    if let Some(content_type) = &content_type {
+        attachment = attachment.with_content_type(content_type);
+    }
+    if let Some(content_id) = &content_id {
+        attachment = attachment.with_content_id(content_id);
+    }
+    Some(attachment)
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+fn optional_string(
+    object: &Map<String, Value>,
+    name: &str,
+    problems: &mut Vec<String>,
+) -> Option<String> {
+    match object.get(name) {
+        None | Some(Value::Null) => None,
+        Some(Value::String(s)) => Some(s.clone()),
+        Some(other) => {
+            problems.push(format!("{name}: expected a string, got {}", kind(other)));
+            None
+        }
+    }
+}
+
+fn required_string(
+    object: &Map<String, Value>,
+    name: &str,
+    problems: &mut Vec<String>,
+) -> Option<String> {
+    if object.get(name).is_none_or(Value::is_null) {
+        problems.push(format!("{name}: missing"));
+        return None;
+    }
+    let value = optional_string(object, name, problems)?;
+    if value.trim().is_empty() {
+        problems.push(format!("{name}: empty"));
+        return None;
+    }
+    Some(value)
+}
+
+/// A list of addresses, given as one string or an array of strings, each checked.
+fn addresses(object: &Map<String, Value>, name: &str, problems: &mut Vec<String>) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut check = |at: String, value: &Value| match value.as_str() {
+        Some(addr) if address::is_valid(address::mailbox(addr)) => found.push(addr.to_string()),
+        Some(addr) => problems.push(format!("{at}: invalid address {addr:?}")),
+        None => problems.push(format!("{at}: expected a string, got {}", kind(value))),
+    };
+    match object.get(name) {
+        None | Some(Value::Null) => {}
+        Some(Value::Array(items)) => {
+            for (i, item) in items.iter().enumerate() {
+                check(format!("{name}[{i}]"), item);
+            }
+        }
+        Some(value @ Value::String(_)) => check(name.to_string(), value),
+        Some(other) => problems.push(format!(
+            "{name}: expected an address or an array of them, got {}",
+            kind(other)
+        )),
+    }
+    found
+}
+
+fn array<'a>(
+    object: &'a Map<String, Value>,
+    name: &str,
+    problems: &mut Vec<String>,
+) -> &'a [Value] {
+    match object.get(name) {
+        None | Some(Value::Null) => &[],
+        Some(Value::Array(items)) => items,
+        Some(other) => {
+            problems.push(format!("{name}: expected an array, got {}", kind(other)));
+            &[]
+        }
+    }
+}
+
+fn expect_object<'a>(
+    value: &'a Value,
+    at: &str,
+    problems: &mut Vec<String>,
+) -> Option<&'a Map<String, Value>> {
+    let object = value.as_object();
+    if object.is_none() {
+        problems.push(format!("{at}: expected an object, got {}", kind(value)));
+    }
+    object
+}
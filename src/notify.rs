@@ -0,0 +1,44 @@
+use reqwest::Url;
+use serde::Serialize;
+use std::time::Duration;
+
+/// How long a `--notify-url` endpoint gets to answer.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The JSON body POSTed to `--notify-url` after a successful send.
+#[derive(Serialize)]
+pub struct Summary<'a> {
+    pub id: &'a str,
+    pub to: &'a [String],
+    pub subject: &'a str,
+    /// Always `sent`: only accepted emails are reported.
+    pub status: &'static str,
+}
+
+/// Parses a `--notify-url`, accepting only `http` and `https`.
+pub fn parse_url(s: &str) -> Result<Url, String> {
+    let url = Url::parse(s).map_err(|e| e.to_string())?;
+    match url.scheme() {
+        "http" | "https" => Ok(url),
+        other => Err(format!("unsupported scheme {other:?}, use http or https")),
+    }
+}
+
+/// POSTs `summary` to `url`. Failures are reported on stderr and otherwise
+/// ignored, since the email itself has already gone out.
+pub async fn post(url: &Url, summary: &Summary<'_>) {
+    let result = async {
+        reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .build()?
+            .post(url.clone())
+            .json(summary)
+            .send()
+            .await?
+            .error_for_status()
+    }
+    .await;
+    if let Err(e) = result {
+        eprintln!("warning: notifying {url} about {} failed: {e}", summary.id);
+    }
+}
@@ -0,0 +1,229 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use resend_rs::types::{Email, EmailEvent, InboundAttachment, InboundEmail};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::timestamp::{self, Zone};
+
+/// How commands print their results.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Readable text
+    #[default]
+    Human,
+    /// JSON on stdout, for scripts
+    Json,
+    /// One compact JSON document per line; lists print one item per line
+    Ndjson,
+    /// YAML on stdout, with the same fields as JSON
+    Yaml,
+}
+
+/// The output settings chosen on the command line.
+#[derive(Clone, Copy, Default)]
+pub struct Output {
+    pub format: OutputFormat,
+    /// Print JSON on a single line instead of pretty-printing it.
+    pub compact: bool,
+    /// Timezone for timestamps in human output (JSON keeps the API's values).
+    pub timezone: Zone,
+}
+
+impl Output {
+    /// Whether results are printed as data for scripts: JSON in either layout,
+    /// or YAML, which is the JSON document rendered differently.
+    pub fn is_json(self) -> bool {
+        matches!(self.format, OutputFormat::Json | OutputFormat::Ndjson | OutputFormat::Yaml)
+    }
+
+    /// Formats an API timestamp for human output.
+    pub fn time(self, raw: &str) -> String {
+        timestamp::display(raw, self.timezone)
+    }
+
+    pub fn print_json<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        if self.format == OutputFormat::Ndjson {
+            return match serde_json::to_value(value)? {
+                serde_json::Value::Array(items) => {
+                    items.iter().try_for_each(|item| self.print_line(item))
+                }
+                other => self.print_line(&other),
+            };
+        }
+        if self.format == OutputFormat::Yaml {
+            print!("{}", serde_yaml::to_string(value)?);
+            return Ok(());
+        }
+        let json = if self.compact {
+            serde_json::to_string(value)?
+        } else {
+            serde_json::to_string_pretty(value)?
+        };
+        println!("{json}");
+        Ok(())
+    }
+
+    /// Prints one page of a list: as a [`Page`] envelope with `--output json`
+    /// (the bare array with `bare`), one item per line with `--output ndjson`.
+    pub fn print_page<T: Serialize>(self, page: &Page<T>, bare: bool) -> Result<()> {
+        if bare || self.format == OutputFormat::Ndjson {
+            self.print_json(&page.data)
+        } else {
+            self.print_json(page)
+        }
+    }
+
+    /// Prints `value` as a single compact line, as `--output ndjson` streams items.
+    pub fn print_line<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        println!("{}", serde_json::to_string(value)?);
+        Ok(())
+    }
+}
+
+/// The JSON envelope of `list` and `received-list`, mirroring the API's own.
+///
+/// `next_cursor` is the ID to pass to `--after` for the following page.
+#[derive(Serialize)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub has_more: bool,
+    pub next_cursor: Option<String>,
+}
+
+/// `list --flatten`: `view` once per To recipient, `to` holding just that
+/// address. A view with no recipient stays a single record.
+pub fn per_recipient<T: Serialize>(view: &T) -> Result<Vec<serde_json::Value>> {
+    let value = serde_json::to_value(view)?;
+    let Some(to) = value["to"].as_array().filter(|to| !to.is_empty()) else {
+        return Ok(vec![value]);
+    };
+    Ok(to
+        .iter()
+        .map(|recipient| {
+            let mut record = value.clone();
+            record["to"] = recipient.clone();
+            record
+        })
+        .collect())
+}
+
+/// Serializable view of a sent email (resend-rs only deserializes `Email`).
+#[derive(Serialize)]
+pub struct EmailView<'a> {
+    id: &'a str,
+    created_at: &'a str,
+    from: &'a str,
+    to: &'a [String],
+    cc: &'a [String],
+    bcc: &'a [String],
+    reply_to: Option<&'a [String]>,
+    subject: &'a str,
+    last_event: &'static str,
+    scheduled_at: Option<&'a str>,
+    html: Option<&'a str>,
+    text: Option<&'a str>,
+}
+
+impl<'a> From<&'a Email> for EmailView<'a> {
+    fn from(email: &'a Email) -> Self {
+        Self {
+            id: &email.id,
+            created_at: &email.created_at,
+            from: &email.from,
+            to: &email.to,
+            cc: &email.cc,
+            bcc: &email.bcc,
+            reply_to: email.reply_to.as_deref(),
+            subject: &email.subject,
+            last_event: event_name(email.last_event),
+            scheduled_at: email.scheduled_at.as_deref(),
+            html: email.html.as_deref(),
+            text: email.text.as_deref(),
+        }
+    }
+}
+
+/// Serializable view of a received email.
+#[derive(Serialize)]
+pub struct InboundEmailView<'a> {
+    id: &'a str,
+    created_at: &'a str,
+    from: &'a str,
+    to: &'a [String],
+    cc: &'a [String],
+    bcc: &'a [String],
+    reply_to: &'a [String],
+    subject: &'a str,
+    message_id: &'a str,
+    html: Option<&'a str>,
+    text: Option<&'a str>,
+    attachments: Vec<AttachmentView<'a>>,
+    /// Only with `received-get --headers`, see [`InboundEmailView::with_headers`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    headers: Option<BTreeMap<&'a str, &'a str>>,
+}
+
+impl<'a> InboundEmailView<'a> {
+    /// Adds the message's headers, sorted by name.
+    pub fn with_headers(mut self, email: &'a InboundEmail) -> Self {
+        self.headers = Some(email.headers.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect());
+        self
+    }
+}
+
+#[derive(Serialize)]
+struct AttachmentView<'a> {
+    id: &'a str,
+    filename: &'a str,
+    content_type: &'a str,
+    size: u32,
+}
+
+impl<'a> From<&'a InboundEmail> for InboundEmailView<'a> {
+    fn from(email: &'a InboundEmail) -> Self {
+        Self {
+            id: &email.id,
+            created_at: &email.created_at,
+            from: &email.from,
+            to: &email.to,
+            cc: &email.cc,
+            bcc: &email.bcc,
+            reply_to: &email.reply_to,
+            subject: &email.subject,
+            message_id: &email.message_id,
+            html: email.html.as_deref(),
+            text: email.text.as_deref(),
+            attachments: email.attachments.iter().map(AttachmentView::from).collect(),
+            headers: None,
+        }
+    }
+}
+
+impl<'a> From<&'a InboundAttachment> for AttachmentView<'a> {
+    fn from(attachment: &'a InboundAttachment) -> Self {
+        Self {
+            id: &attachment.id,
+            filename: &attachment.filename,
+            content_type: &attachment.content_type,
+            size: attachment.size,
+        }
+    }
+}
+
+/// The API's snake_case name for an email event.
+pub fn event_name(event: EmailEvent) -> &'static str {
+    match event {
+        EmailEvent::Bounced => "bounced",
+        EmailEvent::Canceled => "canceled",
+        EmailEvent::Clicked => "clicked",
+        EmailEvent::Complained => "complained",
+        EmailEvent::Delivered => "delivered",
+        EmailEvent::DeliveryDelayed => "delivery_delayed",
+        EmailEvent::Failed => "failed",
+        EmailEvent::Opened => "opened",
+        EmailEvent::Queued => "queued",
+        EmailEvent::Scheduled => "scheduled",
+        EmailEvent::Sent => "sent",
+    }
+}
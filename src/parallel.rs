@@ -0,0 +1,49 @@
+use futures::stream::{self, StreamExt};
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The global `--max-concurrent` default, kept low since reads count against
+/// the same rate limit as sends.
+pub const DEFAULT_MAX_CONCURRENT: usize = 4;
+
+static MAX_CONCURRENT: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_CONCURRENT);
+
+/// Sets `--max-concurrent` for every later [`fetch_all`].
+pub fn set_max_concurrent(limit: NonZeroUsize) {
+    MAX_CONCURRENT.store(limit.get(), Ordering::Relaxed);
+}
+
+/// [`ordered`] for commands that fetch many things at once (several `get`
+/// ids, `received-get --follow-thread`), limited by `--max-concurrent`.
+pub async fn fetch_all<T, Fut>(
+    items: impl IntoIterator<Item = T>,
+    f: impl FnMut(T) -> Fut,
+) -> Vec<Fut::Output>
+where
+    Fut: Future,
+{
+    ordered(items, MAX_CONCURRENT.load(Ordering::Relaxed), f).await
+}
+
+/// Runs `f` on every item with at most `limit` calls in flight and returns
+/// the results in input order, however the calls happen to finish.
+pub async fn ordered<T, Fut>(
+    items: impl IntoIterator<Item = T>,
+    limit: usize,
+    mut f: impl FnMut(T) -> Fut,
+) -> Vec<Fut::Output>
+where
+    Fut: Future,
+{
+    let mut results: Vec<(usize, Fut::Output)> = stream::iter(items.into_iter().enumerate())
+        .map(|(i, item)| {
+            let call = f(item);
+            async move { (i, call.await) }
+        })
+        .buffer_unordered(limit.max(1))
+        .collect()
+        .await;
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
@@ -0,0 +1,23 @@
+use crate::template;
+
+/// Filler after the preheader so clients don't pad the preview with the
+/// start of the body: zero-width non-joiners and non-breaking spaces.
+const FILLER: &str = "&zwnj;&nbsp;";
+
+/// Adds `text` to `html` as a preheader: a hidden block right after the
+/// `<body>` tag (or at the very start when there is none) that inboxes show as
+/// the preview next to the subject.
+pub fn inject(html: &str, text: &str) -> String {
+    let block = format!(
+        "<div style=\"display:none;font-size:1px;line-height:1px;max-height:0;max-width:0;\
+         opacity:0;overflow:hidden;mso-hide:all;\">{}{}</div>",
+        template::escape(text.trim()),
+        FILLER.repeat(60)
+    );
+    let lower = html.to_ascii_lowercase();
+    let at = lower
+        .find("<body")
+        .and_then(|start| lower[start..].find('>').map(|end| start + end + 1))
+        .unwrap_or(0);
+    format!("{}{block}{}", &html[..at], &html[at..])
+}
@@ -0,0 +1,72 @@
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use resend_rs::types::{Email, EmailEvent};
+use serde::Serialize;
+
+use crate::client::Api;
+use crate::listing;
+use crate::output::{EmailView, Output};
+use crate::timestamp;
+
+/// Scheduled emails due within this window are flagged as coming up soon.
+const SOON: TimeDelta = TimeDelta::hours(1);
+
+#[derive(Serialize)]
+struct ScheduledView<'a> {
+    #[serde(flatten)]
+    email: EmailView<'a>,
+    /// `overdue`, `soon` or `pending`.
+    state: &'static str,
+}
+
+/// Lists emails still waiting to be sent, soonest first.
+///
+/// The API can't filter by status, so the newest `scan` sent emails are paged
+/// through and those whose last event is `scheduled` are kept.
+pub async fn run(api: &Api, out: Output, scan: usize) -> Result<()> {
+    let emails = listing::fetch_sent(api, scan).await?;
+    let mut queued: Vec<(Option<DateTime<Utc>>, Email)> = emails
+        .into_iter()
+        .filter(|e| e.last_event == EmailEvent::Scheduled)
+        .map(|e| (e.scheduled_at.as_deref().and_then(timestamp::parse), e))
+        .collect();
+    // Unparseable times sort last rather than being dropped.
+    queued.sort_by_key(|(at, _)| (at.is_none(), *at));
+
+    let now = Utc::now();
+    let state = |at: Option<DateTime<Utc>>| match at {
+        Some(at) if at <= now => "overdue",
+        Some(at) if at - now <= SOON => "soon",
+        _ => "pending",
+    };
+
+    if out.is_json() {
+        let views: Vec<ScheduledView> = queued
+            .iter()
+            .map(|(at, e)| ScheduledView {
+                email: EmailView::from(e),
+                state: state(*at),
+            })
+            .collect();
+        return out.print_json(&views);
+    }
+
+    if queued.is_empty() {
+        println!("No scheduled emails.");
+    }
+    for (at, e) in &queued {
+        let flag = match state(*at) {
+            "overdue" => "  [OVERDUE]",
+            "soon" => "  [soon]",
+            _ => "",
+        };
+        println!(
+            "{}  ID: {}, To: {:?}, Subject: {}{flag}",
+            e.scheduled_at.as_deref().map_or("?".to_string(), |at| out.time(at)),
+            e.id,
+            e.to,
+            e.subject
+        );
+    }
+    Ok(())
+}
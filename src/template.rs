@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+/// Substitutes `{{name}}` placeholders (surrounding spaces allowed) in `template`.
+///
+/// Values are HTML-escaped when `escape_html` is set. Placeholders without a
+/// value, or with an empty one, are collected and returned as the error.
+pub fn render(
+    template: &str,
+    vars: &HashMap<String, String>,
+    escape_html: bool,
+) -> Result<String, Vec<String>> {
+    let mut out = String::with_capacity(template.len());
+    let mut missing = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let name = rest[start + 2..start + 2 + len].trim();
+        match vars.get(name).filter(|v| !v.is_empty()) {
+            Some(value) if escape_html => push_escaped(&mut out, value),
+            Some(value) => out.push_str(value),
+            None if !missing.iter().any(|m| m == name) => missing.push(name.to_string()),
+            None => {}
+        }
+        rest = &rest[start + 2 + len + 2..];
+    }
+    out.push_str(rest);
+
+    if missing.is_empty() { Ok(out) } else { Err(missing) }
+}
+
+/// `value` with the characters that are special in HTML escaped.
+pub fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    push_escaped(&mut out, value);
+    out
+}
+
+fn push_escaped(out: &mut String, value: &str) {
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+}
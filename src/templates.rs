@@ -0,0 +1,126 @@
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use crate::template;
+
+/// A named email in the templates directory: `<name>.html`, with optional
+/// `<name>.txt` and `<name>.subject` next to it.
+pub struct Template {
+    pub html: String,
+    pub text: Option<String>,
+    /// The first line of `<name>.subject`.
+    pub subject: Option<String>,
+}
+
+/// One template as `templates list` shows it.
+#[derive(Serialize)]
+pub struct Listed {
+    pub name: String,
+    pub text: bool,
+    pub subject: bool,
+}
+
+/// Parses a `--var NAME=VALUE`.
+pub fn parse_var(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!("expected NAME=VALUE, got {s:?}")),
+    }
+}
+
+impl Template {
+    /// Reads the template `name` from `dir`.
+    pub fn load(dir: &Path, name: &str) -> Result<Self> {
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            bail!("invalid template name {name:?}");
+        }
+        let html_path = dir.join(format!("{name}.html"));
+        let html = match fs::read_to_string(&html_path) {
+            Ok(html) => html,
+            Err(e) if e.kind() == ErrorKind::NotFound => bail!(
+                "no template {name:?} in {} (see `rusend templates list`)",
+                dir.display()
+            ),
+            Err(e) => return Err(e).with_context(|| format!("read {}", html_path.display())),
+        };
+        let text = read_optional(&dir.join(format!("{name}.txt")))?;
+        let subject = read_optional(&dir.join(format!("{name}.subject")))?
+            .and_then(|s| s.lines().next().map(|line| line.trim().to_string()))
+            .filter(|s| !s.is_empty());
+        Ok(Self { html, text, subject })
+    }
+
+    /// Fills in `{{name}}` placeholders in every part (HTML-escaped in the HTML).
+    ///
+    /// Fails listing every variable the template uses but `vars` doesn't set.
+    pub fn render(self, name: &str, vars: &HashMap<String, String>) -> Result<Self> {
+        let mut missing = Vec::new();
+        let mut render = |part: &str, escape: bool| {
+            template::render(part, vars, escape).unwrap_or_else(|names| {
+                for var in names {
+                    if !missing.contains(&var) {
+                        missing.push(var);
+                    }
+                }
+                String::new()
+            })
+        };
+        let rendered = Self {
+            html: render(&self.html, true),
+            text: self.text.as_deref().map(|text| render(text, false)),
+            subject: self.subject.as_deref().map(|subject| render(subject, false)),
+        };
+        if !missing.is_empty() {
+            bail!("template {name:?} needs --var for: {}", missing.join(", "));
+        }
+        Ok(rendered)
+    }
+}
+
+fn read_optional(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("read {}", path.display())),
+    }
+}
+
+/// The templates in `dir`, by name. A missing directory has none.
+pub fn list(dir: &Path) -> Result<Vec<Listed>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("read templates dir {}", dir.display())),
+    };
+    let mut parts: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for entry in entries {
+        let path = entry
+            .with_context(|| format!("read templates dir {}", dir.display()))?
+            .path();
+        let (Some(stem), Some(ext)) = (path.file_stem(), path.extension()) else {
+            continue;
+        };
+        let stem = stem.to_string_lossy();
+        if !stem.starts_with('.') {
+            parts
+                .entry(stem.into_owned())
+                .or_default()
+                .push(ext.to_string_lossy().into_owned());
+        }
+    }
+    Ok(parts
+        .into_iter()
+        .filter(|(_, exts)| exts.iter().any(|e| e == "html"))
+        .map(|(name, exts)| Listed {
+            name,
+            text: exts.iter().any(|e| e == "txt"),
+            subject: exts.iter().any(|e| e == "subject"),
+        })
+        .collect())
+}
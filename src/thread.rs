@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use resend_rs::types::InboundEmail;
+use std::collections::HashSet;
+
+use crate::client::Api;
+use crate::listing::{self, ListSort};
+use crate::parallel;
+use crate::transfer;
+
+/// The received emails in the same conversation as `email`, oldest first and
+/// `email` included.
+///
+/// Messages are linked through their Message-ID and the References and
+/// In-Reply-To headers. Only the newest `scan` received emails are searched:
+/// the ones `email` refers to, and the ones sharing its subject (ignoring
+/// `Re:`/`Fwd:`), are fetched (see [`parallel::fetch_all`]) and kept when
+/// they belong to the thread.
+pub async fn conversation(
+    api: &Api,
+    email: InboundEmail,
+    scan: usize,
+) -> Result<Vec<InboundEmail>> {
+    let mut ids: HashSet<String> = references(&email).into_iter().collect();
+    ids.insert(message_id(&email.message_id));
+    let topic = topic(&email.subject);
+
+    let candidates: Vec<String> = listing::fetch_received(api, scan)
+        .await?
+        .into_iter()
+        .filter(|e| e.id != email.id)
+        .filter(|e| ids.contains(&message_id(&e.message_id)) || self::topic(&e.subject) == topic)
+        .map(|e| e.id.to_string())
+        .collect();
+    let results = parallel::fetch_all(&candidates, |id| {
+        api.call("GET /emails/receiving/{id}", move || api.resend.receiving.get(id))
+    })
+    .await;
+
+    let mut thread = vec![email];
+    for result in results {
+        let mut e = result.context("get receiving failed")?;
+        let linked = ids.contains(&message_id(&e.message_id))
+            || references(&e).iter().any(|r| ids.contains(r));
+        if linked {
+            transfer::decode_bodies(&mut e);
+            thread.push(e);
+        }
+    }
+    listing::arrange(&mut thread, ListSort::CreatedAsc, usize::MAX);
+    Ok(thread)
+}
+
+/// Whether `email` names any earlier message in its headers.
+pub fn has_references(email: &InboundEmail) -> bool {
+    !references(email).is_empty()
+}
+
+/// The Message-IDs in the References and In-Reply-To headers.
+fn references(email: &InboundEmail) -> Vec<String> {
+    email
+        .headers
+        .iter()
+        .filter(|(name, _)| {
+            name.eq_ignore_ascii_case("references") || name.eq_ignore_ascii_case("in-reply-to")
+        })
+        .flat_map(|(_, value)| value.split('>'))
+        .filter_map(|part| part.split_once('<').map(|(_, id)| id.trim().to_string()))
+        .filter(|id| !id.is_empty())
+        .collect()
+}
+
+/// A Message-ID without its angle brackets.
+fn message_id(raw: &str) -> String {
+    raw.trim().trim_start_matches('<').trim_end_matches('>').to_string()
+}
+
+/// The subject with reply and forward prefixes removed, lowercased.
+fn topic(subject: &str) -> String {
+    let mut rest = subject.trim();
+    loop {
+        let lower = rest.to_ascii_lowercase();
+        let Some(len) = ["re:", "fwd:", "fw:"]
+            .iter()
+            .find(|prefix| lower.starts_with(*prefix))
+            .map(|prefix| prefix.len())
+        else {
+            return rest.to_lowercase();
+        };
+        rest = rest[len..].trim_start();
+    }
+}
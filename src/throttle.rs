@@ -0,0 +1,51 @@
+use std::time::Duration;
+use tokio::time::{Instant, sleep};
+
+/// Token bucket capping how many messages per second are dispatched.
+///
+/// The bucket starts full, so the first `burst` messages go out at once and
+/// the rest follow at `rate`, however they are grouped into requests.
+pub struct Throttle {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl Throttle {
+    pub fn new(rate: f64, burst: usize) -> Self {
+        let burst = (burst as f64).max(rate);
+        Self {
+            rate,
+            burst,
+            tokens: burst,
+            last: Instant::now(),
+        }
+    }
+
+    /// Waits until `n` messages may be sent and takes their tokens.
+    pub async fn acquire(&mut self, n: usize) {
+        let n = n as f64;
+        self.refill();
+        if self.tokens < n {
+            sleep(Duration::from_secs_f64((n - self.tokens) / self.rate)).await;
+            self.refill();
+        }
+        self.tokens -= n;
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let earned = now.duration_since(self.last).as_secs_f64() * self.rate;
+        self.tokens = (self.tokens + earned).min(self.burst);
+        self.last = now;
+    }
+}
+
+/// Parses a `--rate` value: a positive number of messages per second.
+pub fn parse_rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Ok(rate),
+        _ => Err(format!("expected a positive number of emails per second, got {s:?}")),
+    }
+}
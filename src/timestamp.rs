@@ -0,0 +1,98 @@
+use chrono::{DateTime, Local, TimeDelta, Utc};
+use chrono_tz::Tz;
+
+/// Parses the API's timestamps, which come either as RFC 3339 or in Postgres'
+/// `2024-08-05 11:52:01.858+00` form.
+pub fn parse(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s)
+        .or_else(|_| DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f%#z"))
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Parses a duration such as `90s`, `15m`, `1h30m` or `2d`: whole numbers,
+/// each followed by a unit out of `s`, `m`, `h` and `d`.
+pub fn parse_duration(s: &str) -> Result<TimeDelta, String> {
+    let invalid = || format!("expected a duration like 30s, 15m or 1h30m, got {s:?}");
+    let mut total = TimeDelta::zero();
+    let mut digits = String::new();
+    for c in s.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let n: i64 = digits.parse().map_err(|_| invalid())?;
+        let part = match c {
+            's' => TimeDelta::try_seconds(n),
+            'm' => TimeDelta::try_minutes(n),
+            'h' => TimeDelta::try_hours(n),
+            'd' => TimeDelta::try_days(n),
+            _ => None,
+        };
+        total = part.and_then(|p| total.checked_add(&p)).ok_or_else(invalid)?;
+        digits.clear();
+    }
+    if !digits.is_empty() || total <= TimeDelta::zero() {
+        return Err(invalid());
+    }
+    Ok(total)
+}
+
+/// Timezone that human-readable output shows timestamps in.
+#[derive(Clone, Copy, Default)]
+pub enum Zone {
+    /// The system's local timezone
+    #[default]
+    Local,
+    Named(Tz),
+}
+
+/// Parses a `--timezone` value: `local` or an IANA name such as `Europe/Berlin`.
+pub fn parse_zone(s: &str) -> Result<Zone, String> {
+    if s.eq_ignore_ascii_case("local") {
+        return Ok(Zone::Local);
+    }
+    s.parse::<Tz>()
+        .map(Zone::Named)
+        .map_err(|_| format!("unknown timezone {s:?}, use `local` or an IANA name"))
+}
+
+/// Renders an API timestamp in `zone` followed by a relative form, e.g.
+/// `2024-08-05 13:52:01 CEST (3 hours ago)`. Unparseable input is returned as is.
+pub fn display(raw: &str, zone: Zone) -> String {
+    let Some(at) = parse(raw) else {
+        return raw.to_string();
+    };
+    let absolute = match zone {
+        Zone::Local => at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S %:z").to_string(),
+        Zone::Named(tz) => at.with_timezone(&tz).format("%Y-%m-%d %H:%M:%S %Z").to_string(),
+    };
+    format!("{absolute} ({})", relative(at - Utc::now()))
+}
+
+/// `3 hours ago` / `in 2 days` for a signed offset from now.
+fn relative(delta: TimeDelta) -> String {
+    let secs = delta.num_seconds();
+    let abs = secs.unsigned_abs();
+    if abs < 60 {
+        return "just now".to_string();
+    }
+    const UNITS: [(u64, &str); 5] = [
+        (31_536_000, "year"),
+        (2_592_000, "month"),
+        (86_400, "day"),
+        (3_600, "hour"),
+        (60, "minute"),
+    ];
+    let (size, unit) = UNITS
+        .into_iter()
+        .find(|(size, _)| abs >= *size)
+        .unwrap_or(UNITS[4]);
+    let n = abs / size;
+    let plural = if n == 1 { "" } else { "s" };
+    if secs < 0 {
+        format!("{n} {unit}{plural} ago")
+    } else {
+        format!("in {n} {unit}{plural}")
+    }
+}
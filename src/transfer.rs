@@ -0,0 +1,125 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use resend_rs::types::InboundEmail;
+
+/// A `Content-Transfer-Encoding` that leaves a body unreadable as is.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Base64,
+    QuotedPrintable,
+}
+
+impl Encoding {
+    fn from_header(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "base64" => Some(Self::Base64),
+            "quoted-printable" => Some(Self::QuotedPrintable),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a `--html-base64`/`--text-base64` value: standard base64, line
+/// breaks allowed (as `base64` wraps its output), decoding to UTF-8 text.
+pub fn parse_base64_body(s: &str) -> Result<String, String> {
+    let compact: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+    let bytes = STANDARD
+        .decode(compact)
+        .map_err(|e| format!("not valid base64: {e}"))?;
+    String::from_utf8(bytes).map_err(|_| "the decoded body is not UTF-8 text".to_string())
+}
+
+/// Decodes the text and HTML bodies of a received email in place, using the
+/// `Content-Transfer-Encoding` from its headers. Bodies that don't decode
+/// cleanly are left as they are.
+pub fn decode_bodies(email: &mut InboundEmail) {
+    let encoding = email
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-transfer-encoding"))
+        .and_then(|(_, value)| Encoding::from_header(value));
+    let Some(encoding) = encoding else {
+        return;
+    };
+    for body in [&mut email.text, &mut email.html].into_iter().flatten() {
+        if let Some(decoded) = decode(body, encoding) {
+            *body = decoded;
+        }
+    }
+}
+
+/// `body` decoded, or `None` when it isn't valid in `encoding`.
+fn decode(body: &str, encoding: Encoding) -> Option<String> {
+    match encoding {
+        Encoding::Base64 => {
+            let compact: String = body.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+            let bytes = STANDARD.decode(compact).ok()?;
+            String::from_utf8(bytes).ok()
+        }
+        Encoding::QuotedPrintable => Some(decode_quoted_printable(body)),
+    }
+}
+
+fn decode_quoted_printable(body: &str) -> String {
+    let bytes = body.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'=' {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        let rest = &bytes[i + 1..];
+        if rest.starts_with(b"\r\n") {
+            // Soft line break.
+            i += 3;
+        } else if rest.starts_with(b"\n") {
+            i += 2;
+        } else if let Some(byte) = rest.get(..2).and_then(hex_byte) {
+            out.push(byte);
+            i += 3;
+        } else {
+            // A stray `=` is kept, as decoders are told to be lenient.
+            out.push(b'=');
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn hex_byte(pair: &[u8]) -> Option<u8> {
+    let digits = std::str::from_utf8(pair).ok()?;
+    u8::from_str_radix(digits, 16).ok()
+}
+
+/// Longest RFC 2047 encoded-word allowed, `=?UTF-8?B?...?=` included.
+const MAX_ENCODED_WORD: usize = 75;
+
+/// Encodes a header value as RFC 2047 `B` encoded-words, for
+/// `send --force-encoded-subject`.
+///
+/// Words are split on character boundaries so each decodes on its own, and
+/// joined with spaces, which decoders drop between adjacent encoded-words.
+pub fn encode_header(value: &str) -> String {
+    let overhead = "=?UTF-8?B??=".len();
+    // Base64 turns every 3 bytes into 4 characters.
+    let max_bytes = (MAX_ENCODED_WORD - overhead) / 4 * 3;
+    let mut words = Vec::new();
+    let mut chunk = String::new();
+    for c in value.chars() {
+        if chunk.len() + c.len_utf8() > max_bytes {
+            words.push(encoded_word(&chunk));
+            chunk.clear();
+        }
+        chunk.push(c);
+    }
+    if !chunk.is_empty() || words.is_empty() {
+        words.push(encoded_word(&chunk));
+    }
+    words.join(" ")
+}
+
+fn encoded_word(text: &str) -> String {
+    format!("=?UTF-8?B?{}?=", STANDARD.encode(text))
+}
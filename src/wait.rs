@@ -0,0 +1,198 @@
+use anyhow::{Context, Result, bail};
+use chrono::TimeDelta;
+use clap::ValueEnum;
+use resend_rs::types::EmailEvent;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::client::Api;
+use crate::output;
+
+/// How often `send --wait` asks for the email's status.
+const POLL: Duration = Duration::from_secs(2);
+
+/// `domains verify --wait` first checks again after this long, then doubles
+/// the pause up to [`DOMAIN_POLL_MAX`]; DNS changes take minutes to show up.
+const DOMAIN_POLL_FIRST: Duration = Duration::from_secs(5);
+const DOMAIN_POLL_MAX: Duration = Duration::from_secs(60);
+
+/// Exit code when the final status isn't the one `--assert-status` expects.
+pub const EXIT_MISMATCH: i32 = 3;
+/// Exit code when `--wait-timeout` passes before a final status.
+pub const EXIT_TIMEOUT: i32 = 4;
+
+/// A final status `send --assert-status` can require.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Expected {
+    /// Delivered; opened and clicked count too, since they imply delivery
+    Delivered,
+    Bounced,
+    Complained,
+    Failed,
+}
+
+impl Expected {
+    fn matches(self, event: EmailEvent) -> bool {
+        match self {
+            Self::Delivered => matches!(
+                event,
+                EmailEvent::Delivered | EmailEvent::Opened | EmailEvent::Clicked
+            ),
+            Self::Bounced => event == EmailEvent::Bounced,
+            Self::Complained => event == EmailEvent::Complained,
+            Self::Failed => event == EmailEvent::Failed,
+        }
+    }
+}
+
+/// An outcome of `--wait` that fails the command with its own exit code.
+#[derive(Debug)]
+pub struct Unexpected {
+    pub code: i32,
+    message: String,
+}
+
+impl fmt::Display for Unexpected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Unexpected {}
+
+/// Whether an email can still change status on its own.
+fn pending(event: EmailEvent) -> bool {
+    matches!(
+        event,
+        EmailEvent::Queued | EmailEvent::Sent | EmailEvent::Scheduled | EmailEvent::DeliveryDelayed
+    )
+}
+
+/// `send --wait`: polls email `id` until its latest event is final (no longer
+/// queued, sent, scheduled or delayed), prints it, and checks it against
+/// `expected`.
+///
+/// Fails with [`EXIT_TIMEOUT`] when `timeout` passes first, and with
+/// [`EXIT_MISMATCH`] when the final status isn't the expected one.
+pub async fn until_final(
+    api: &Api,
+    id: &str,
+    timeout: TimeDelta,
+    expected: Option<Expected>,
+) -> Result<()> {
+    let timeout = timeout.to_std().unwrap_or_default();
+    let started = Instant::now();
+    eprintln!("Waiting up to {}s for the final status of {id}...", timeout.as_secs());
+    let event = loop {
+        let email = api
+            .call("GET /emails/{id}", || api.resend.emails.get(id))
+            .await
+            .context("get status failed")?;
+        if !pending(email.last_event) {
+            break email.last_event;
+        }
+        if started.elapsed() + POLL > timeout {
+            return Err(Unexpected {
+                code: EXIT_TIMEOUT,
+                message: format!(
+                    "{id} is still {} after {}s (--wait-timeout)",
+                    output::event_name(email.last_event),
+                    timeout.as_secs()
+                ),
+            }
+            .into());
+        }
+        tokio::time::sleep(POLL).await;
+    };
+    let name = output::event_name(event);
+    println!("Final status: {name}");
+    if let Some(expected) = expected.filter(|e| !e.matches(event)) {
+        let wanted = expected.to_possible_value().map(|v| v.get_name().to_string());
+        return Err(Unexpected {
+            code: EXIT_MISMATCH,
+            message: format!(
+                "{id} ended {name}, not {} (--assert-status)",
+                wanted.unwrap_or_default()
+            ),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// `domains verify --wait`: polls domain `id` until Resend reports it
+/// verified, backing off between checks and printing each status change.
+///
+/// Fails when the domain's verification fails, and with [`EXIT_TIMEOUT`] when
+/// `timeout` passes first.
+pub async fn until_verified(api: &Api, id: &str, timeout: TimeDelta) -> Result<()> {
+    let timeout = timeout.to_std().unwrap_or_default();
+    let started = Instant::now();
+    let mut pause = DOMAIN_POLL_FIRST;
+    let mut last_status = String::new();
+    loop {
+        let domain = api
+            .call("GET /domains/{id}", || api.resend.domains.get(id))
+            .await
+            .context("get domain failed")?;
+        match domain.status.as_str() {
+            "verified" => {
+                println!("{} is verified.", domain.name);
+                return Ok(());
+            }
+            "failed" => bail!(
+                "verification of {} failed; check its records with `domains dns-check`",
+                domain.name
+            ),
+            _ => {}
+        }
+        let elapsed = started.elapsed();
+        if elapsed >= timeout {
+            return Err(Unexpected {
+                code: EXIT_TIMEOUT,
+                message: format!(
+                    "{} is still {} after {}s (--wait-timeout)",
+                    domain.name,
+                    domain.status,
+                    timeout.as_secs()
+                ),
+            }
+            .into());
+        }
+        let wait = pause.min(timeout - elapsed);
+        let wait_secs = wait.as_millis().div_ceil(1000);
+        if domain.status != last_status {
+            eprintln!("{}: {} (checking again in {wait_secs}s)", domain.name, domain.status);
+            last_status = domain.status;
+        } else {
+            api.trace(&format!(
+                "{}: still {last_status}, checking again in {wait_secs}s",
+                domain.name
+            ));
+        }
+        tokio::time::sleep(wait).await;
+        pause = (pause * 2).min(DOMAIN_POLL_MAX);
+    }
+}
+
+/// `send --delay`: sleeps for `delay` before the send goes out, counting down
+/// under `--verbose`. Ctrl-C during the wait fails the command with nothing sent.
+pub async fn delay(api: &Api, delay: TimeDelta) -> Result<()> {
+    let delay = delay.to_std().unwrap_or_default();
+    let deadline = tokio::time::Instant::now() + delay;
+    eprintln!("Sending in {}s (Ctrl-C to cancel)...", delay.as_secs());
+    let countdown = async {
+        let mut left = delay.as_secs();
+        while left > 0 {
+            if left <= 5 || left.is_multiple_of(10) {
+                api.trace(&format!("sending in {left}s"));
+            }
+            tokio::time::sleep_until(deadline - Duration::from_secs(left - 1)).await;
+            left -= 1;
+        }
+    };
+    tokio::select! {
+        () = countdown => Ok(()),
+        _ = tokio::signal::ctrl_c() => bail!("cancelled during --delay; nothing was sent"),
+    }
+}
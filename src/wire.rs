@@ -0,0 +1,97 @@
+use anyhow::{Result, bail};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::compose;
+
+/// The `log` target reqwest writes its per-connection byte dumps under.
+const TARGET: &str = "reqwest::connect::verbose";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether `--trace-http` is on, so [`crate::client::Api`] asks reqwest for
+/// connection-level logging.
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Turns on `--trace-http`: every byte reqwest reads or writes is printed to
+/// stderr, with the Authorization header redacted.
+///
+/// Bodies are left out unless `bodies` is set and confirmed on a terminal,
+/// since they carry message content and recipients.
+pub fn install(bodies: bool) -> Result<()> {
+    eprintln!(
+        "warning: --trace-http prints raw HTTP traffic to stderr. The API key is redacted, \
+         but headers can still be sensitive."
+    );
+    let bodies = bodies && confirm_bodies()?;
+    log::set_boxed_logger(Box::new(WireLogger { bodies }))
+        .map_err(|e| anyhow::anyhow!("install --trace-http logger: {e}"))?;
+    log::set_max_level(LevelFilter::Trace);
+    ENABLED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+fn confirm_bodies() -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        bail!("--trace-http-bodies asks for confirmation, which needs a terminal");
+    }
+    compose::confirm("Also print request and response bodies (email content, recipients)?")
+}
+
+struct WireLogger {
+    bodies: bool,
+}
+
+impl Log for WireLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Trace && metadata.target() == TARGET
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = redact(&record.args().to_string());
+        let line = if self.bodies { line } else { hide_body(&line) };
+        eprintln!("http: {line}");
+    }
+
+    fn flush(&self) {}
+}
+
+/// Replaces the value of an `authorization` header in an escaped dump line.
+fn redact(line: &str) -> String {
+    let lower = line.to_ascii_lowercase();
+    let Some(start) = lower.find("authorization: ") else {
+        return line.to_string();
+    };
+    let value = start + "authorization: ".len();
+    let end = line[value..].find("\\r\\n").map_or(line.len(), |i| value + i);
+    format!("{}[redacted]{}", &line[..value], &line[end..])
+}
+
+/// Keeps the request or status line and headers of a dump line, dropping
+/// what follows the blank line that ends them. Lines that only continue a
+/// body are dropped whole.
+fn hide_body(line: &str) -> String {
+    // `<conn id> read: b"..."`, or `write` / `write (vectored)`.
+    let Some((prefix, data)) = line.split_once(": b\"") else {
+        return line.to_string();
+    };
+    let head = data.starts_with("HTTP/")
+        || ["GET ", "POST ", "PATCH ", "PUT ", "DELETE "]
+            .iter()
+            .any(|method| data.starts_with(method));
+    if !head {
+        return format!("{prefix}: [body hidden]");
+    }
+    match data.find("\\r\\n\\r\\n") {
+        Some(end) if end + 8 < data.len() - 1 => {
+            format!("{prefix}: b\"{}\" [body hidden]", &data[..end + 8])
+        }
+        _ => line.to_string(),
+    }
+}
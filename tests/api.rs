@@ -0,0 +1,967 @@
+//! Runs commands end to end against a wiremock server standing in for the
+//! Resend API, reached through `--base-url`.
+
+use base64::Engine;
+use serde_json::{Value, json};
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use tempfile::TempDir;
+use wiremock::matchers::{body_string_contains, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// A mock API plus an empty config dir to run the binary against.
+struct Harness {
+    server: MockServer,
+    config: TempDir,
+}
+
+impl Harness {
+    async fn start() -> Self {
+        Self {
+            server: MockServer::start().await,
+            config: tempfile::tempdir().unwrap(),
+        }
+    }
+
+    /// Answers `verb path` with `body` (status 200) for as long as the test runs.
+    async fn reply(&self, verb: &str, route: &str, body: Value) {
+        Mock::given(method(verb))
+            .and(path(route))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(&self.server)
+            .await;
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_rusend"));
+        command
+            .arg("--base-url")
+            .arg(self.server.uri())
+            .args(args)
+            .env("XDG_CONFIG_HOME", self.config.path())
+            .env("RESEND_API_KEY", "re_test")
+            .env("RUST_BACKTRACE", "0");
+        command
+    }
+
+    /// Runs the binary against the mock, whether or not it succeeds.
+    fn output(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+
+    /// Like [`Harness::output`], with `stdin` piped in.
+    fn output_with_stdin(&self, args: &[&str], stdin: &str) -> Output {
+        let mut child = self
+            .command(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+        child.wait_with_output().unwrap()
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        let out = self.output(args);
+        assert!(out.status.success(), "stderr: {}", String::from_utf8_lossy(&out.stderr));
+        out
+    }
+
+    /// The JSON bodies of the requests made to `route`, in order.
+    async fn bodies(&self, route: &str) -> Vec<Value> {
+        let requests = self.server.received_requests().await.unwrap();
+        requests
+            .iter()
+            .filter(|r| r.url.path() == route)
+            .map(|r| serde_json::from_slice(&r.body).unwrap())
+            .collect()
+    }
+}
+
+fn email(id: &str, subject: &str) -> Value {
+    json!({
+        "object": "email",
+        "id": id,
+        "to": ["b@example.com"],
+        "from": "a@example.com",
+        "created_at": "2024-01-02 03:04:05.000000+00",
+        "subject": subject,
+        "html": "<p>Hi</p>",
+        "text": "Hi",
+        "bcc": null,
+        "cc": null,
+        "reply_to": null,
+        "last_event": "delivered",
+        "scheduled_at": null
+    })
+}
+
+#[tokio::test]
+async fn send_posts_the_email() {
+    let api = Harness::start().await;
+    api.reply("POST", "/emails", json!({"id": "e1"})).await;
+    let out = api.run(&[
+        "send", "-f", "a@example.com", "-t", "b@example.com", "-s", "hi", "--text", "x",
+    ]);
+    assert!(String::from_utf8_lossy(&out.stdout).contains("Send request submitted."));
+    let sent = api.bodies("/emails").await;
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0]["to"], json!(["b@example.com"]));
+    assert_eq!(sent[0]["text"], "x");
+}
+
+#[tokio::test]
+async fn json_flag_prints_the_ids_of_sent_emails() {
+    let api = Harness::start().await;
+    api.reply("POST", "/emails", json!({"id": "e1"})).await;
+    api.reply("POST", "/emails/batch", json!({"data": [{"id": "b1"}, {"id": "b2"}]})).await;
+    let stdout = |out: Output| serde_json::from_slice::<Value>(&out.stdout).unwrap();
+    let out = api.run(&[
+        "--json", "send", "-f", "a@example.com", "-t", "b@example.com", "-s", "hi", "--text", "x",
+    ]);
+    assert_eq!(stdout(out), json!({"id": "e1"}));
+
+    let file = batch_file(&api, 2);
+    let summary = stdout(api.run(&["batch", &file, "--json"]));
+    assert_eq!(summary["ids"], json!(["b1", "b2"]));
+    assert_eq!(summary["succeeded"], 2);
+
+    let out = api.output(&["--json", "--output", "yaml", "list"]);
+    assert!(!out.status.success());
+}
+
+#[tokio::test]
+async fn resend_api_key_wins_over_the_saved_key() {
+    let api = Harness::start().await;
+    api.reply("GET", "/emails", json!({"object": "list", "has_more": false, "data": []})).await;
+    api.run(&["config", "-k", "re_saved", "--no-validate"]);
+    api.run(&["list"]);
+    let requests = api.server.received_requests().await.unwrap();
+    assert_eq!(requests[0].headers["authorization"], "Bearer re_test");
+}
+
+#[tokio::test]
+async fn resend_api_key_stands_in_for_a_missing_profile() {
+    let api = Harness::start().await;
+    api.reply("GET", "/emails", json!({"object": "list", "has_more": false, "data": []})).await;
+    api.run(&["--profile", "missing", "list"]);
+    let requests = api.server.received_requests().await.unwrap();
+    assert_eq!(requests[0].headers["authorization"], "Bearer re_test");
+}
+
+#[tokio::test]
+async fn sends_log_the_request_id_of_their_response() {
+    let api = Harness::start().await;
+    for (route, body, id) in [
+        ("/emails", json!({"id": "e1"}), "req_send"),
+        ("/emails/batch", json!({"data": [{"id": "b1"}, {"id": "b2"}]}), "req_batch"),
+    ] {
+        let response =
+            ResponseTemplate::new(200).set_body_json(body).insert_header("x-request-id", id);
+        Mock::given(path(route)).respond_with(response).mount(&api.server).await;
+    }
+    let log = api.config.path().join("api.log");
+    let log = log.to_str().unwrap();
+    let out = api.run(&[
+        "-v", "--log-file", log, "send", "-f", "a@example.com", "-t", "b@example.com", "-s",
+        "hi", "--text", "x",
+    ]);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("POST /emails 200 "), "{stderr}");
+    assert!(stderr.contains("request req_send"), "{stderr}");
+    api.run(&["--log-file", log, "batch", &batch_file(&api, 2)]);
+
+    let records: Vec<Value> = std::fs::read_to_string(log)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let ids: Vec<_> = records.iter().map(|r| r["request_id"].as_str().unwrap()).collect();
+    assert_eq!(ids, ["req_send", "req_batch"]);
+}
+
+#[tokio::test]
+async fn send_return_headers_reports_the_request_id_and_rate_limit() {
+    let api = Harness::start().await;
+    let response = ResponseTemplate::new(200)
+        .set_body_json(json!({"id": "e1"}))
+        .insert_header("x-request-id", "req_123")
+        .insert_header("ratelimit-limit", "10")
+        .insert_header("ratelimit-remaining", "9")
+        .insert_header("ratelimit-reset", "1");
+    Mock::given(method("POST"))
+        .and(path("/emails"))
+        .respond_with(response)
+        .mount(&api.server)
+        .await;
+    let send = [
+        "send", "-f", "a@example.com", "-t", "b@example.com", "-s", "hi", "--text", "x",
+        "--return-headers", "--idempotency-key", "k1",
+    ];
+    let human = String::from_utf8(api.run(&send).stdout).unwrap();
+    assert!(human.contains("Request ID: req_123"), "{human}");
+    assert!(human.contains("Rate limit: 9 of 10 left, resets in 1s"), "{human}");
+
+    let out = api.run(&[&send[..], &["--output", "json"]].concat());
+    let json: Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(json["id"], "e1");
+    assert_eq!(json["headers"]["request_id"], "req_123");
+    assert_eq!(json["headers"]["ratelimit_remaining"], "9");
+
+    let requests = api.server.received_requests().await.unwrap();
+    assert_eq!(requests[0].headers.get("idempotency-key").unwrap(), "k1");
+    let sent: Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(sent["to"], json!(["b@example.com"]));
+}
+
+#[tokio::test]
+async fn send_delay_waits_before_sending() {
+    let api = Harness::start().await;
+    api.reply("POST", "/emails", json!({"id": "e1"})).await;
+    let started = std::time::Instant::now();
+    let out = api.run(&[
+        "send", "-f", "a@example.com", "-t", "b@example.com", "-s", "hi", "--text", "x",
+        "--delay", "1s",
+    ]);
+    assert!(started.elapsed() >= std::time::Duration::from_secs(1));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Sending in 1s"));
+    assert_eq!(api.bodies("/emails").await.len(), 1);
+}
+
+#[tokio::test]
+async fn list_shows_the_returned_emails() {
+    let api = Harness::start().await;
+    let page = json!({
+        "object": "list",
+        "has_more": false,
+        "data": [email("e1", "One"), email("e2", "Two")]
+    });
+    api.reply("GET", "/emails", page).await;
+    let out = api.run(&["list", "--fields", "id,subject"]);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines, ["ID: e1, Subject: One", "ID: e2, Subject: Two"]);
+}
+
+#[tokio::test]
+async fn list_to_keeps_emails_sent_to_the_address() {
+    let api = Harness::start().await;
+    let mut cc = email("e2", "Cc'd");
+    cc["to"] = json!(["other@example.com"]);
+    cc["cc"] = json!(["Customer <Customer@Example.com>"]);
+    let mut unrelated = email("e3", "Unrelated");
+    unrelated["to"] = json!(["other@example.com"]);
+    let mut direct = email("e1", "Direct");
+    direct["to"] = json!(["customer@example.com"]);
+    let page = json!({"object": "list", "has_more": false, "data": [direct, cc, unrelated]});
+    api.reply("GET", "/emails", page).await;
+    let out = api.run(&["list", "--to", "customer@example.com", "--output", "json"]);
+    let listed: Value = serde_json::from_slice(&out.stdout).unwrap();
+    let ids: Vec<_> = listed["data"].as_array().unwrap().iter().map(|e| &e["id"]).collect();
+    assert_eq!(ids, ["e1", "e2"]);
+}
+
+#[tokio::test]
+async fn list_flatten_gives_one_record_per_recipient() {
+    let api = Harness::start().await;
+    let mut two = email("e1", "Two");
+    two["to"] = json!(["x@example.com", "y@example.com"]);
+    let page = json!({"object": "list", "has_more": false, "data": [two, email("e2", "One")]});
+    api.reply("GET", "/emails", page).await;
+    let out = api.run(&["list", "--flatten", "--output", "json"]);
+    let listed: Value = serde_json::from_slice(&out.stdout).unwrap();
+    let records: Vec<_> = listed["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| (r["id"].as_str().unwrap(), r["to"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        records,
+        [("e1", "x@example.com"), ("e1", "y@example.com"), ("e2", "b@example.com")]
+    );
+
+    let out = api.run(&["list", "--flatten", "--output", "ndjson"]);
+    assert_eq!(String::from_utf8_lossy(&out.stdout).lines().count(), 3);
+    assert!(!api.output(&["list", "--flatten"]).status.success());
+}
+
+#[tokio::test]
+async fn get_prints_the_email() {
+    let api = Harness::start().await;
+    api.reply("GET", "/emails/e1", email("e1", "Hello")).await;
+    let out = api.run(&["get", "e1", "--output", "json"]);
+    let shown: Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(shown["id"], "e1");
+    assert_eq!(shown["subject"], "Hello");
+    assert_eq!(shown["last_event"], "delivered");
+}
+
+#[tokio::test]
+async fn get_fetches_several_ids_within_max_concurrent() {
+    let api = Harness::start().await;
+    for id in ["e1", "e2", "e3"] {
+        api.reply("GET", &format!("/emails/{id}"), email(id, id)).await;
+    }
+    let out = api.run(&["--max-concurrent", "1", "get", "e1", "e2", "e3", "--output", "json"]);
+    let shown: Vec<Value> = serde_json::from_slice(&out.stdout).unwrap();
+    let ids: Vec<_> = shown.iter().map(|e| e["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, ["e1", "e2", "e3"]);
+}
+
+/// Writes a batch file of `count` messages to `u1@example.com`, `u2@...` and so on.
+fn batch_file(api: &Harness, count: usize) -> String {
+    let file = api.config.path().join("batch.json");
+    let messages: Vec<Value> = (1..=count)
+        .map(|i| {
+            let to = format!("u{i}@example.com");
+            json!({"from": "a@example.com", "to": [to], "subject": "s", "text": "t"})
+        })
+        .collect();
+    std::fs::write(&file, serde_json::to_string(&messages).unwrap()).unwrap();
+    file.to_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn batch_sends_the_messages_in_chunks() {
+    let api = Harness::start().await;
+    api.reply("POST", "/emails/batch", json!({"data": [{"id": "b1"}, {"id": "b2"}]})).await;
+    let file = batch_file(&api, 3);
+    api.run(&["batch", &file, "--chunk-size", "2"]);
+    let requests = api.bodies("/emails/batch").await;
+    let sizes: Vec<usize> = requests.iter().map(|r| r.as_array().unwrap().len()).collect();
+    assert_eq!(sizes, [2, 1]);
+    assert_eq!(requests[1][0]["to"], json!(["u3@example.com"]));
+}
+
+fn inbound(id: &str, subject: &str) -> Value {
+    json!({
+        "id": id,
+        "to": ["inbox@example.com"],
+        "from": "sender@example.com",
+        "created_at": "2024-01-02 03:04:05.000000+00",
+        "subject": subject,
+        "html": null,
+        "text": "hello",
+        "message_id": format!("<{id}@example.com>")
+    })
+}
+
+#[tokio::test]
+async fn received_json_lines_appends_each_email_shown() {
+    let api = Harness::start().await;
+    let page = json!({
+        "object": "list",
+        "has_more": false,
+        "data": [inbound("r1", "One"), inbound("r2", "Two")]
+    });
+    api.reply("GET", "/emails/receiving", page).await;
+    api.reply("GET", "/emails/receiving/r1", inbound("r1", "One")).await;
+    let sink = api.config.path().join("inbound.jsonl");
+    let sink_arg = sink.to_str().unwrap();
+    let out = api.run(&["received-list", "--json-lines", sink_arg]);
+    assert!(String::from_utf8_lossy(&out.stdout).contains("ID: r1"));
+    api.run(&["received-get", "r1", "--json-lines", sink_arg]);
+
+    let lines: Vec<Value> = std::fs::read_to_string(&sink)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let seen: Vec<_> = lines.iter().map(|l| (&l["command"], &l["email"]["id"])).collect();
+    assert_eq!(
+        seen,
+        [
+            (&json!("received-list"), &json!("r1")),
+            (&json!("received-list"), &json!("r2")),
+            (&json!("received-get"), &json!("r1")),
+        ]
+    );
+    assert_eq!(lines[2]["email"]["text"], "hello");
+}
+
+#[tokio::test]
+async fn batch_continue_from_resumes_where_fail_fast_stopped() {
+    let api = Harness::start().await;
+    let ids = json!({"data": [{"id": "b1"}, {"id": "b2"}]});
+    let ok = ResponseTemplate::new(200).set_body_json(ids);
+    Mock::given(path("/emails/batch"))
+        .respond_with(ok)
+        .up_to_n_times(1)
+        .mount(&api.server)
+        .await;
+    let error = json!({"statusCode": 500, "name": "application_error", "message": "boom"});
+    Mock::given(path("/emails/batch"))
+        .respond_with(ResponseTemplate::new(500).set_body_json(error))
+        .mount(&api.server)
+        .await;
+    let file = batch_file(&api, 5);
+    let out = api.output(&["batch", &file, "--chunk-size", "2", "--fail-fast"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("messages 3-4 failed"), "{stderr}");
+    assert!(stderr.contains("rerun with --continue-from 2"), "{stderr}");
+
+    api.server.reset().await;
+    api.reply("POST", "/emails/batch", json!({"data": [{"id": "b3"}, {"id": "b4"}]})).await;
+    let out = api.run(&["batch", &file, "--chunk-size", "2", "--continue-from", "2"]);
+    assert!(String::from_utf8_lossy(&out.stdout).contains("3 sent"));
+    let requests = api.bodies("/emails/batch").await;
+    let to: Vec<_> = requests
+        .iter()
+        .flat_map(|r| r.as_array().unwrap())
+        .map(|m| &m["to"][0])
+        .collect();
+    assert_eq!(to, ["u3@example.com", "u4@example.com", "u5@example.com"]);
+
+    let out = api.output(&["batch", &file, "--continue-from", "5"]);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("the batch has 5"));
+}
+
+#[tokio::test]
+async fn fail_fast_gives_no_resume_point_when_a_later_chunk_of_the_wave_went_out() {
+    let api = Harness::start().await;
+    let error = json!({"statusCode": 500, "name": "application_error", "message": "boom"});
+    Mock::given(path("/emails/batch"))
+        .and(body_string_contains("u5@example.com"))
+        .respond_with(ResponseTemplate::new(500).set_body_json(error))
+        .with_priority(1)
+        .mount(&api.server)
+        .await;
+    api.reply("POST", "/emails/batch", json!({"data": [{"id": "b1"}]})).await;
+    let file = batch_file(&api, 8);
+    // Waves of 1, 2 and 3 chunks: messages 4-6 go together and 5 fails.
+    let out = api.output(&[
+        "batch", &file, "--chunk-size", "1", "--concurrency-auto", "--fail-fast",
+    ]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(String::from_utf8_lossy(&out.stdout).contains("5 sent, 1 failed"));
+    assert!(!stderr.contains("rerun with"), "{stderr}");
+    assert!(stderr.contains("Resend only messages 5-5"), "{stderr}");
+    assert!(stderr.contains("--continue-from 6 for the rest"), "{stderr}");
+}
+
+#[tokio::test]
+async fn batch_summary_only_leaves_the_failures_to_the_file() {
+    let api = Harness::start().await;
+    let error = json!({"statusCode": 500, "name": "application_error", "message": "boom"});
+    Mock::given(path("/emails/batch"))
+        .respond_with(ResponseTemplate::new(500).set_body_json(error))
+        .mount(&api.server)
+        .await;
+    let file = batch_file(&api, 3);
+    let failures = api.config.path().join("failures.json");
+    let out = api.output(&[
+        "batch", &file, "--chunk-size", "2", "--summary-only",
+        "--failures-file", failures.to_str().unwrap(),
+    ]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(!stderr.contains("messages 1-2 failed"), "{stderr}");
+    assert!(stderr.contains("Wrote 3 failed message(s)"), "{stderr}");
+    assert!(String::from_utf8_lossy(&out.stdout).contains("0 sent, 3 failed"));
+    let written: Vec<Value> =
+        serde_json::from_str(&std::fs::read_to_string(&failures).unwrap()).unwrap();
+    let indexes: Vec<_> = written.iter().map(|f| f["index"].as_u64().unwrap()).collect();
+    assert_eq!(indexes, [0, 1, 2]);
+}
+
+#[tokio::test]
+async fn network_only_leaves_http_errors_alone() {
+    let api = Harness::start().await;
+    let error = json!({"statusCode": 503, "name": "application_error", "message": "busy"});
+    Mock::given(path("/emails"))
+        .respond_with(ResponseTemplate::new(503).set_body_json(error))
+        .mount(&api.server)
+        .await;
+    let out = api.output(&[
+        "--retry-policy", "network-only", "send", "-f", "a@example.com", "-t", "b@example.com",
+        "-s", "hi", "--text", "x", "--idempotency-key", "k1",
+    ]);
+    assert!(!out.status.success());
+    assert_eq!(api.bodies("/emails").await.len(), 1);
+}
+
+#[tokio::test]
+async fn rotate_key_never_retries_a_create_that_may_have_gone_through() {
+    let api = Harness::start().await;
+    let error = json!({"statusCode": 500, "name": "application_error", "message": "boom"});
+    Mock::given(path("/api-keys"))
+        .respond_with(ResponseTemplate::new(500).set_body_json(error))
+        .mount(&api.server)
+        .await;
+    api.run(&["config", "-k", "re_old", "--no-validate"]);
+    let out = api.output(&["--retry-policy", "full", "config", "rotate-key"]);
+    assert!(!out.status.success());
+    assert_eq!(api.server.received_requests().await.unwrap().len(), 1);
+    let saved = std::fs::read_to_string(api.config.path().join("rusend/credentials")).unwrap();
+    assert!(saved.contains("re_old"), "credentials: {saved}");
+}
+
+#[tokio::test]
+async fn domains_list_check_summarizes_the_records_of_each_domain() {
+    let api = Harness::start().await;
+    let domain = |id: &str, name: &str, dkim: &str| {
+        json!({
+            "object": "domain", "id": id, "name": name, "status": "verified",
+            "created_at": "2024-01-02 03:04:05.000000+00", "region": "us-east-1",
+            "records": [
+                {"record": "SPF", "name": "send", "type": "TXT", "ttl": "Auto",
+                 "status": "Verified", "value": "v=spf1 include:amazonses.com ~all"},
+                {"record": "DKIM", "name": "resend._domainkey", "type": "TXT", "ttl": "Auto",
+                 "status": dkim, "value": "p=abc"},
+            ],
+        })
+    };
+    let one = domain("d1", "one.invalid", "Verified");
+    let two = domain("d2", "two.invalid", "Failed");
+    api.reply("GET", "/domains", json!({"object": "list", "has_more": false, "data": [one, two]})).await;
+    api.reply("GET", "/domains/d1", one).await;
+    api.reply("GET", "/domains/d2", two).await;
+
+    let out = api.run(&["--output", "json", "domains", "list", "--check"]);
+    let listed: Vec<Value> = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(listed[0]["name"], "one.invalid");
+    assert_eq!((&listed[0]["spf"], &listed[0]["dkim"]), (&json!("ok"), &json!("ok")));
+    assert_eq!(listed[1]["dkim"], "failed");
+    assert!(listed[1]["dmarc"].is_string());
+
+    let out = api.run(&["--output", "json", "domains", "list"]);
+    let listed: Vec<Value> = serde_json::from_slice(&out.stdout).unwrap();
+    assert!(listed[0].get("spf").is_none());
+}
+
+#[tokio::test]
+async fn require_verified_from_blocks_sends_unless_allowed() {
+    let api = Harness::start().await;
+    let pending = json!({
+        "object": "domain", "id": "d1", "name": "example.com", "status": "pending",
+        "created_at": "2024-01-02 03:04:05.000000+00", "region": "us-east-1",
+    });
+    api.reply("GET", "/domains", json!({"object": "list", "has_more": false, "data": [pending]}))
+        .await;
+    api.reply("POST", "/emails", json!({"id": "e1"})).await;
+    api.run(&["config", "--key", "re_test", "--no-validate", "--require-verified-from", "true"]);
+
+    let send = ["send", "-f", "a@example.com", "-t", "b@example.com", "-s", "hi", "--text", "x"];
+    let out = api.output(&send);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("example.com is not verified"), "{stderr}");
+    assert!(api.bodies("/emails").await.is_empty());
+
+    api.run(&[&send[..], &["--allow-unverified"]].concat());
+    assert_eq!(api.bodies("/emails").await.len(), 1);
+}
+
+#[tokio::test]
+async fn received_forward_sends_the_message_on_with_its_attachments() {
+    let api = Harness::start().await;
+    api.reply("GET", "/emails/receiving/r1", inbound("r1", "Invoice")).await;
+    let listed = json!({"object": "list", "has_more": false, "data": [{
+        "id": "a1", "filename": "invoice.pdf", "content_type": "application/pdf",
+        "content_disposition": "attachment", "size": 3,
+        "download_url": format!("{}/download/a1", api.server.uri()),
+    }]});
+    api.reply("GET", "/emails/receiving/r1/attachments", listed).await;
+    Mock::given(path("/download/a1"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"PDF".to_vec()))
+        .mount(&api.server)
+        .await;
+    api.reply("POST", "/emails", json!({"id": "e1"})).await;
+
+    let out = api.run(&[
+        "received-forward", "r1", "--to", "team@example.com", "--from", "me@example.com",
+        "--text", "FYI",
+    ]);
+    assert!(String::from_utf8_lossy(&out.stdout).contains("Forwarded r1 as e1"));
+    let sent = api.bodies("/emails").await;
+    assert_eq!(sent[0]["subject"], "Fwd: Invoice");
+    assert_eq!(sent[0]["to"], json!(["team@example.com"]));
+    let text = sent[0]["text"].as_str().unwrap();
+    assert!(text.starts_with("FYI\n\n---------- Forwarded message ----------\n"), "{text}");
+    assert!(text.contains("From: sender@example.com\n") && text.ends_with("\nhello"), "{text}");
+    assert_eq!(sent[0]["attachments"][0]["filename"], "invoice.pdf");
+    assert_eq!(sent[0]["attachments"][0]["content"], json!(b"PDF"));
+}
+
+#[tokio::test]
+async fn domains_verify_wait_polls_until_verified_or_times_out() {
+    let api = Harness::start().await;
+    let domain = |id: &str, status: &str| {
+        json!({
+            "object": "domain", "id": id, "name": format!("{id}.example.com"), "status": status,
+            "created_at": "2024-01-02 03:04:05.000000+00", "region": "us-east-1",
+        })
+    };
+    for id in ["ok", "slow"] {
+        api.reply("POST", &format!("/domains/{id}/verify"), json!({"object": "domain", "id": id}))
+            .await;
+    }
+    api.reply("GET", "/domains/ok", domain("ok", "verified")).await;
+    api.reply("GET", "/domains/slow", domain("slow", "pending")).await;
+
+    let out = api.run(&["domains", "verify", "ok", "--wait"]);
+    assert!(String::from_utf8_lossy(&out.stdout).contains("ok.example.com is verified."));
+
+    let out = api.output(&["domains", "verify", "slow", "--wait", "--wait-timeout", "1s"]);
+    assert_eq!(out.status.code(), Some(4));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("slow.example.com: pending (checking again in 1s)"), "{stderr}");
+    assert!(stderr.contains("still pending after 1s"), "{stderr}");
+}
+
+#[tokio::test]
+async fn batch_max_per_domain_holds_back_the_overflow() {
+    let api = Harness::start().await;
+    let ids = json!({"data": [{"id": "e1"}, {"id": "e2"}, {"id": "e3"}]});
+    api.reply("POST", "/emails/batch", ids).await;
+    let message =
+        |to: &str| json!({"from": "a@example.com", "to": [to], "subject": "hi", "text": "x"});
+    let messages = json!([
+        message("one@gmail.com"), message("two@gmail.com"), message("three@gmail.com"),
+        message("one@yahoo.com"),
+    ]);
+    let file = api.config.path().join("batch.json");
+    std::fs::write(&file, messages.to_string()).unwrap();
+    let overflow = api.config.path().join("held.json");
+    let out = api.run(&[
+        "batch", file.to_str().unwrap(), "--max-per-domain", "2",
+        "--overflow-file", overflow.to_str().unwrap(),
+    ]);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let held_back = "Holding back 1 message(s) over --max-per-domain 2: gmail.com (1 over)";
+    assert!(stderr.contains(held_back), "{stderr}");
+    let sent = api.bodies("/emails/batch").await;
+    let to: Vec<_> = sent[0].as_array().unwrap().iter().map(|m| m["to"][0].clone()).collect();
+    assert_eq!(to, [json!("one@gmail.com"), json!("two@gmail.com"), json!("one@yahoo.com")]);
+    let held: Value = serde_json::from_str(&std::fs::read_to_string(&overflow).unwrap()).unwrap();
+    assert_eq!(held[0]["to"], json!(["three@gmail.com"]));
+}
+
+#[tokio::test]
+async fn batch_messages_with_attachments_go_out_on_their_own() {
+    let api = Harness::start().await;
+    api.reply("POST", "/emails/batch", json!({"data": [{"id": "b1"}]})).await;
+    api.reply("POST", "/emails", json!({"id": "e1"})).await;
+    let report = api.config.path().join("report.csv");
+    std::fs::write(&report, "a,b\n1,2\n").unwrap();
+    let report = report.to_str().unwrap();
+    let message = json!({"from": "a@example.com", "subject": "hi", "text": "x"});
+    let with = |to: &str, attachments: Value| {
+        let mut m = message.clone();
+        m["to"] = json!([to]);
+        m["attachments"] = attachments;
+        m
+    };
+    let file = api.config.path().join("batch.json");
+    let file = file.to_str().unwrap();
+    let messages = json!([
+        with("u1@example.com", Value::Null),
+        with("u2@example.com", json!([report])),
+        with("u3@example.com", Value::Null),
+    ]);
+    std::fs::write(file, messages.to_string()).unwrap();
+    let summary: Value =
+        serde_json::from_slice(&api.run(&["batch", file, "--output", "json"]).stdout).unwrap();
+    assert_eq!(summary["requests"], 3);
+    let batched = api.bodies("/emails/batch").await;
+    let to: Vec<_> = batched.iter().map(|chunk| chunk[0]["to"][0].clone()).collect();
+    assert_eq!(to, [json!("u1@example.com"), json!("u3@example.com")]);
+    assert!(batched.iter().all(|chunk| chunk[0].get("attachments").is_none_or(Value::is_null)));
+    let single = api.bodies("/emails").await;
+    assert_eq!(single.len(), 1);
+    assert_eq!(single[0]["to"], json!(["u2@example.com"]));
+    let attachment = &single[0]["attachments"][0];
+    assert_eq!(attachment["filename"], "report.csv");
+    assert_eq!(attachment["contentType"], "text/csv");
+    assert_eq!(attachment["content"], json!(b"a,b\n1,2\n"));
+
+    let failing = |args: &[&str]| {
+        let out = api.output(&[&["batch", file], args].concat());
+        assert!(!out.status.success());
+        String::from_utf8_lossy(&out.stderr).into_owned()
+    };
+    let stderr = failing(&["--attach-max-total", "5"]);
+    assert!(stderr.contains("message 2") && stderr.contains("--attach-max-total"), "{stderr}");
+    assert!(failing(&["--single-request"]).contains("can't carry attachments"));
+    let missing = json!([with("u1@example.com", json!(["nope.pdf"]))]);
+    std::fs::write(file, missing.to_string()).unwrap();
+    let stderr = failing(&[]);
+    assert!(stderr.contains("message 1") && stderr.contains("nope.pdf"), "{stderr}");
+    assert_eq!(api.bodies("/emails").await.len(), 1);
+    assert_eq!(api.bodies("/emails/batch").await.len(), 2);
+}
+
+/// Sends with `args` (From, To and Subject already given), piping `stdin`, and
+/// returns the posted email.
+async fn sent(args: &[&str], stdin: &str) -> Value {
+    let send = ["send", "-f", "a@example.com", "-t", "b@example.com", "-s", "hi"];
+    posted(&[&send[..], args].concat(), stdin).await
+}
+
+/// Runs `args` against a fresh mock, piping `stdin`, and returns the one email
+/// posted to `/emails`.
+async fn posted(args: &[&str], stdin: &str) -> Value {
+    let api = Harness::start().await;
+    api.reply("POST", "/emails", json!({"id": "test"})).await;
+    let out = api.output_with_stdin(args, stdin);
+    assert!(out.status.success(), "stderr: {}", String::from_utf8_lossy(&out.stderr));
+    let mut bodies = api.bodies("/emails").await;
+    assert_eq!(bodies.len(), 1);
+    bodies.remove(0)
+}
+
+#[tokio::test]
+async fn stdin_body_is_sent_as_text_by_default() {
+    let email = sent(&["--from-stdin"], "plain words").await;
+    assert_eq!(email["text"], "plain words");
+    assert!(email.get("html").is_none_or(|h| h.is_null()), "email: {email}");
+}
+
+#[tokio::test]
+async fn base64_bodies_are_decoded_before_sending() {
+    // "<p>caf\u{e9} & \"quotes\"</p>" and "line one\nline two", wrapped as `base64` does.
+    let email = sent(
+        &[
+            "--html-base64", "PHA+Y2Fmw6kgJiAicXVvdGVzIjwv\ncD4=",
+            "--text-base64", "bGluZSBvbmUKbGluZSB0d28=",
+        ],
+        "",
+    )
+    .await;
+    assert_eq!(email["html"], "<p>caf\u{e9} & \"quotes\"</p>");
+    assert_eq!(email["text"], "line one\nline two");
+}
+
+#[tokio::test]
+async fn stdin_format_html_sends_an_html_body() {
+    let email = sent(&["--from-stdin", "--stdin-format", "html"], "<p>hi</p>").await;
+    assert_eq!(email["html"], "<p>hi</p>");
+    assert!(email.get("text").is_none_or(|t| t.is_null()), "email: {email}");
+}
+
+#[tokio::test]
+async fn detect_html_sends_markup_as_html() {
+    let page = "\n  <!DOCTYPE html>\n<html><body><p>hi</p></body></html>";
+    let email = sent(&["--from-stdin", "--detect-html"], page).await;
+    assert_eq!(email["html"], page);
+    assert!(email.get("text").is_none_or(|t| t.is_null()), "email: {email}");
+}
+
+#[tokio::test]
+async fn detect_html_falls_back_to_text() {
+    let email = sent(&["--from-stdin", "--detect-html"], "<paths> are fine in text").await;
+    assert_eq!(email["text"], "<paths> are fine in text");
+    assert!(email.get("html").is_none_or(|h| h.is_null()), "email: {email}");
+}
+
+#[tokio::test]
+async fn attach_stdin_attaches_the_piped_data() {
+    let email = sent(&["--text", "see attached", "--attach-stdin", "report.pdf"], "%PDF").await;
+    let attachment = &email["attachments"][0];
+    assert_eq!(attachment["filename"], "report.pdf");
+    assert_eq!(attachment["contentType"], "application/pdf");
+    assert_eq!(attachment["content"], json!(b"%PDF"));
+}
+
+#[tokio::test]
+async fn header_shortcuts_merge_with_explicit_headers() {
+    let email = sent(
+        &[
+            "--text",
+            "hi",
+            "--priority",
+            "high",
+            "--list-unsubscribe",
+            "https://example.com/u/1",
+            "--header",
+            "x-priority: 2",
+            "--header",
+            "X-Campaign: spring",
+        ],
+        "",
+    )
+    .await;
+    let headers = &email["headers"];
+    assert_eq!(headers["List-Unsubscribe"], "<https://example.com/u/1>");
+    assert_eq!(headers["List-Unsubscribe-Post"], "List-Unsubscribe=One-Click");
+    assert_eq!(headers["X-Campaign"], "spring");
+    // The explicit header replaces the shortcut's, whatever its case.
+    assert_eq!(headers["x-priority"], "2");
+    assert!(headers.get("X-Priority").is_none(), "headers: {headers}");
+}
+
+#[tokio::test]
+async fn importance_sets_importance_and_a_matching_x_priority() {
+    let email = sent(&["--text", "hi", "--importance", "low"], "").await;
+    assert_eq!(email["headers"]["Importance"], "low");
+    assert_eq!(email["headers"]["X-Priority"], "5 (Lowest)");
+
+    let email = sent(&["--text", "hi", "--importance", "high", "--priority", "normal"], "").await;
+    assert_eq!(email["headers"]["Importance"], "high");
+    assert_eq!(email["headers"]["X-Priority"], "3 (Normal)");
+}
+
+#[tokio::test]
+async fn template_name_renders_the_named_template() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("welcome.html"), "<p>Hi {{ name }}</p>").unwrap();
+    std::fs::write(dir.path().join("welcome.txt"), "Hi {{name}}").unwrap();
+    let templates = dir.path().to_str().unwrap();
+    let email = sent(
+        &["--template-name", "welcome", "--templates-dir", templates, "--var", "name=<Bo>"],
+        "",
+    )
+    .await;
+    assert_eq!(email["html"], "<p>Hi &lt;Bo&gt;</p>");
+    assert_eq!(email["text"], "Hi <Bo>");
+}
+
+#[tokio::test]
+async fn non_ascii_subjects_pass_through_or_round_trip_encoded() {
+    let subject = "Café, crème brûlée & 🎉🚀 for everyone in Zürich, São Paulo and Kraków";
+    let email = sent(&["--text", "hi", "--subject-prefix", subject], "").await;
+    assert_eq!(email["subject"], format!("{subject} hi"));
+
+    let args = ["--text", "hi", "--subject-prefix", subject, "--force-encoded-subject"];
+    let email = sent(&args, "").await;
+    let encoded = email["subject"].as_str().unwrap();
+    let mut decoded = Vec::new();
+    for word in encoded.split(' ') {
+        assert!(word.len() <= 75, "word too long: {word}");
+        let payload = word.strip_prefix("=?UTF-8?B?").and_then(|w| w.strip_suffix("?=")).unwrap();
+        let bytes = base64::engine::general_purpose::STANDARD.decode(payload).unwrap();
+        // Each word holds whole characters.
+        decoded.push(String::from_utf8(bytes).unwrap());
+    }
+    assert!(decoded.len() > 1, "subject: {encoded}");
+    assert_eq!(decoded.concat(), format!("{subject} hi"));
+}
+
+#[tokio::test]
+async fn attach_glob_attaches_every_match() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["b.pdf", "a.pdf", "notes.txt"] {
+        std::fs::write(dir.path().join(name), name).unwrap();
+    }
+    let pattern = format!("{}/*.pdf", dir.path().display());
+    let email = sent(&["--text", "attached", "--attach-glob", &pattern], "").await;
+    let names: Vec<&str> = email["attachments"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|a| a["filename"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["a.pdf", "b.pdf"]);
+}
+
+#[tokio::test]
+async fn cc_and_bcc_files_merge_and_each_address_is_sent_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let cc = dir.path().join("cc.txt");
+    let bcc = dir.path().join("bcc.txt");
+    std::fs::write(&cc, "# team\nc@example.com\nB@example.com\n").unwrap();
+    std::fs::write(&bcc, "c@example.com\nd@example.com\n").unwrap();
+    let (cc, bcc) = (cc.to_str().unwrap(), bcc.to_str().unwrap());
+    let email = sent(
+        &["--text", "hi", "--cc", "e@example.com", "--cc-file", cc, "--bcc-file", bcc],
+        "",
+    )
+    .await;
+    // b@example.com is the To address, so neither copy field repeats it.
+    assert_eq!(email["cc"], json!(["e@example.com", "c@example.com"]));
+    assert_eq!(email["bcc"], json!(["d@example.com"]));
+}
+
+#[tokio::test]
+async fn sandbox_replaces_every_recipient_with_the_test_address() {
+    let email = sent(
+        &["--text", "hi", "--cc", "c@example.com", "--sandbox", "bounced"],
+        "",
+    )
+    .await;
+    assert_eq!(email["to"], json!(["bounced@resend.dev"]));
+    assert!(email.get("cc").is_none_or(|cc| cc.is_null()), "email: {email}");
+}
+
+#[tokio::test]
+async fn preheader_is_hidden_right_after_the_body_tag() {
+    let email = sent(
+        &["--html", "<html><body class=\"x\"><p>Hi</p></body></html>", "--preheader", "A & B"],
+        "",
+    )
+    .await;
+    let html = email["html"].as_str().unwrap();
+    let rest = html.strip_prefix("<html><body class=\"x\"><div style=\"display:none;").unwrap();
+    assert!(rest.contains(">A &amp; B&zwnj;&nbsp;"), "html: {html}");
+    assert!(html.ends_with("</div><p>Hi</p></body></html>"), "html: {html}");
+}
+
+#[tokio::test]
+async fn hide_recipients_moves_to_into_bcc_and_addresses_the_sender() {
+    let email = sent(
+        &["--text", "hi", "--bcc", "c@example.com", "--hide-recipients"],
+        "",
+    )
+    .await;
+    assert_eq!(email["to"], json!(["a@example.com"]));
+    assert_eq!(email["bcc"], json!(["b@example.com", "c@example.com"]));
+}
+
+#[tokio::test]
+async fn json_input_sends_the_message_object_as_given() {
+    let message = r#"{
+        "from": "a@example.com", "to": "b@example.com", "cc": ["c@example.com"],
+        "subject": "hi", "text": "hello", "headers": {"X-Campaign": "spring"},
+        "tags": [{"name": "kind", "value": "test"}],
+        "attachments": [{"filename": "a.txt", "content": "aGk="}]
+    }"#;
+    let email = posted(&["send", "--json-input", "-"], message).await;
+    assert_eq!(email["to"], json!(["b@example.com"]));
+    assert_eq!(email["cc"], json!(["c@example.com"]));
+    assert_eq!(email["headers"]["X-Campaign"], "spring");
+    assert_eq!(email["tags"][0]["name"], "kind");
+    assert_eq!(email["attachments"][0]["content"], json!(b"hi"));
+}
+
+#[tokio::test]
+async fn individual_sends_with_attachments_go_out_one_by_one() {
+    let api = Harness::start().await;
+    api.reply("POST", "/emails", json!({"id": "e1"})).await;
+    let dir = tempfile::tempdir().unwrap();
+    let report = dir.path().join("report.csv");
+    std::fs::write(&report, "a,b\n").unwrap();
+    api.run(&[
+        "send", "-f", "a@example.com", "-t", "u1@example.com,u2@example.com", "-s", "hi",
+        "--text", "x", "--attach", report.to_str().unwrap(), "--individual",
+    ]);
+    let sent = api.bodies("/emails").await;
+    let to: Vec<_> = sent.iter().map(|email| email["to"][0].clone()).collect();
+    assert_eq!(to, [json!("u1@example.com"), json!("u2@example.com")]);
+    for email in &sent {
+        assert_eq!(email["attachments"][0]["filename"], "report.csv");
+        assert_eq!(email["attachments"][0]["content"], json!(b"a,b\n"));
+    }
+    assert!(api.bodies("/emails/batch").await.is_empty());
+}
+
+#[tokio::test]
+async fn staggered_failures_count_messages_from_one() {
+    let api = Harness::start().await;
+    let error = json!({"statusCode": 422, "name": "validation_error", "message": "bad"});
+    Mock::given(path("/emails"))
+        .and(body_string_contains("u2@example.com"))
+        .respond_with(ResponseTemplate::new(422).set_body_json(error))
+        .with_priority(1)
+        .mount(&api.server)
+        .await;
+    api.reply("POST", "/emails", json!({"id": "e1"})).await;
+    let file = batch_file(&api, 3);
+    let out = api.output(&["batch", &file, "--chunk-size", "2", "--stagger", "1h"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("failed message 2:"), "{stderr}");
+}
@@ -0,0 +1,124 @@
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// Runs the binary with an empty config dir and an API endpoint nothing listens on.
+fn rusend(args: &[&str]) -> Output {
+    let config = tempfile::tempdir().unwrap();
+    Command::new(env!("CARGO_BIN_EXE_rusend"))
+        .args(args)
+        .env("XDG_CONFIG_HOME", config.path())
+        .env("RESEND_BASE_URL", "http://127.0.0.1:9")
+        .env("RESEND_API_KEY", "re_test")
+        .env("RUST_BACKTRACE", "0")
+        .output()
+        .unwrap()
+}
+
+/// Runs `batch` on `messages` with `args` and returns the request bodies it
+/// dumped before trying to send.
+fn dumped_batch(dir: &Path, messages: &str, args: &[&str]) -> serde_json::Value {
+    let file = dir.join("batch.json");
+    std::fs::write(&file, messages).unwrap();
+    let dump = dir.join("dump.json");
+    let mut all = vec!["batch", file.to_str().unwrap(), "--dump-request", dump.to_str().unwrap()];
+    all.extend(args);
+    rusend(&all);
+    let dumped = std::fs::read_to_string(&dump).unwrap();
+    serde_json::from_str(&dumped).unwrap()
+}
+
+#[test]
+fn rows_inherit_defaults_and_override_them() {
+    let dir = tempfile::tempdir().unwrap();
+    let html = dir.path().join("body.html");
+    std::fs::write(&html, "<p>Shared</p>").unwrap();
+    let messages = r#"[
+        {"from": "a@example.com", "to": ["b@example.com"]},
+        {"from": "a@example.com", "to": ["c@example.com"], "subject": "Own", "html": "<p>Own</p>"}
+    ]"#;
+    let emails = dumped_batch(
+        dir.path(),
+        messages,
+        &["--default-subject", "Shared", "--default-html-file", html.to_str().unwrap()],
+    );
+    assert_eq!(emails[0]["subject"], "Shared");
+    assert_eq!(emails[0]["html"], "<p>Shared</p>");
+    assert_eq!(emails[1]["subject"], "Own");
+    assert_eq!(emails[1]["html"], "<p>Own</p>");
+}
+
+#[test]
+fn messages_left_without_subject_or_body_are_listed() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("batch.json");
+    let messages = r#"[
+        {"from": "a@example.com", "to": ["b@example.com"], "text": "hi"},
+        {"from": "a@example.com", "to": ["c@example.com"]}
+    ]"#;
+    std::fs::write(&file, messages).unwrap();
+    let out = rusend(&["batch", file.to_str().unwrap(), "--default-subject", "Shared"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("message 2: no html or text body"), "stderr: {stderr}");
+    assert!(!stderr.contains("message 1"), "stderr: {stderr}");
+}
+
+#[test]
+fn messages_without_recipients_are_listed() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("batch.json");
+    let messages = r#"[
+        {"from": "a@example.com", "to": ["b@example.com"], "subject": "s", "text": "hi"},
+        {"from": "a@example.com", "to": [], "subject": "s", "text": "hi"},
+        {"from": "a@example.com", "to": ["  "], "subject": "s", "text": "hi"}
+    ]"#;
+    std::fs::write(&file, messages).unwrap();
+    let out = rusend(&["batch", file.to_str().unwrap()]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("no valid recipients in message(s) 2, 3"), "stderr: {stderr}");
+}
+
+#[test]
+fn shuffle_with_a_seed_is_repeatable() {
+    let dir = tempfile::tempdir().unwrap();
+    let messages: Vec<_> = (0..20)
+        .map(|i| {
+            serde_json::json!({
+                "from": "a@example.com",
+                "to": [format!("u{i}@example.com")],
+                "subject": "s",
+                "text": "t",
+            })
+        })
+        .collect();
+    let messages = serde_json::to_string(&messages).unwrap();
+    let order = |seed: &str| -> Vec<String> {
+        let emails = dumped_batch(dir.path(), &messages, &["--shuffle", "--seed", seed]);
+        emails.as_array().unwrap().iter().map(|e| e["to"][0].to_string()).collect()
+    };
+    let first = order("7");
+    assert_eq!(first, order("7"));
+    assert_ne!(first, order("8"));
+    let mut sorted = first.clone();
+    sorted.sort();
+    let mut expected: Vec<String> = (0..20).map(|i| format!("\"u{i}@example.com\"")).collect();
+    expected.sort();
+    assert_eq!(sorted, expected);
+}
+
+#[test]
+fn global_dedupe_keeps_each_address_in_its_first_message() {
+    let dir = tempfile::tempdir().unwrap();
+    let messages = r#"[
+        {"from": "a@example.com", "to": ["b@example.com"], "subject": "1", "text": "t"},
+        {"from": "a@example.com", "to": ["B@example.com"], "subject": "2", "text": "t"},
+        {"from": "a@example.com", "to": ["c@example.com"], "cc": ["b@example.com"],
+         "subject": "3", "text": "t"}
+    ]"#;
+    let emails = dumped_batch(dir.path(), messages, &["--global-dedupe"]);
+    let subjects: Vec<&str> =
+        emails.as_array().unwrap().iter().map(|e| e["subject"].as_str().unwrap()).collect();
+    assert_eq!(subjects, ["1", "3"]);
+    assert!(emails[1].get("cc").is_none_or(|cc| cc.is_null()), "emails: {emails}");
+}
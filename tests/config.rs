@@ -0,0 +1,216 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+
+/// Runs the binary against the config dir `config`.
+fn rusend(config: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rusend"))
+        .args(args)
+        .env("XDG_CONFIG_HOME", config)
+        .env("RESEND_BASE_URL", "http://127.0.0.1:9")
+        .env("RUST_BACKTRACE", "0")
+        .env_remove("RESEND_API_KEY")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn reads_during_a_config_write_see_a_whole_file() {
+    let config = tempfile::tempdir().unwrap();
+    let dir = config.path().to_path_buf();
+    assert!(rusend(&dir, &["config", "-k", "re_test", "--no-validate"]).status.success());
+    assert!(rusend(&dir, &["config", "senders", "add", "base", "a@example.com"]).status.success());
+
+    let writer = {
+        let dir = dir.clone();
+        thread::spawn(move || {
+            for i in 0..40 {
+                let to = format!("user{i}@example.com");
+                let out = rusend(&dir, &["config", "--default-to", &to]);
+                assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+            }
+        })
+    };
+    while !writer.is_finished() {
+        let out = rusend(&dir, &["config", "senders", "list", "--output", "json"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        let senders: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+        assert_eq!(senders["base"], "a@example.com", "read a partial config");
+    }
+    writer.join().unwrap();
+}
+
+#[test]
+fn unset_removes_only_the_api_key() {
+    let config = tempfile::tempdir().unwrap();
+    let dir = config.path();
+    assert!(rusend(dir, &["config", "-k", "re_test", "--no-validate"]).status.success());
+    assert!(rusend(dir, &["config", "senders", "add", "base", "a@example.com"]).status.success());
+
+    let out = rusend(dir, &["config", "unset"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let version: serde_json::Value =
+        serde_json::from_slice(&rusend(dir, &["version", "--output", "json"]).stdout).unwrap();
+    assert_eq!(version["api_key_set"], false);
+    let out = rusend(dir, &["config", "senders", "list", "--output", "json"]);
+    let senders: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(senders["base"], "a@example.com");
+
+    assert!(rusend(dir, &["config", "unset", "--all"]).status.success());
+    let out = rusend(dir, &["config", "senders", "list", "--output", "json"]);
+    let senders: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert!(senders.as_object().is_some_and(|s| s.is_empty()), "senders: {senders}");
+}
+
+#[test]
+fn a_bare_unset_asks_which_profile_when_there_are_several() {
+    let config = tempfile::tempdir().unwrap();
+    let dir = config.path();
+    assert!(rusend(dir, &["config", "-k", "re_default", "--no-validate"]).status.success());
+    assert!(rusend(dir, &["config", "--profile", "prod", "-k", "re_prod", "--no-validate"])
+        .status
+        .success());
+
+    let out = rusend(dir, &["config", "unset"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("(the default, prod)"), "stderr: {stderr}");
+    assert_eq!(saved_key(dir), "re_default");
+
+    let out = rusend(dir, &["--profile", "prod", "config", "unset"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let prod = std::fs::read_to_string(dir.join("rusend/credentials.prod")).unwrap();
+    assert!(serde_json::from_str::<serde_json::Value>(&prod).unwrap()["api_key"].is_null());
+    assert!(rusend(dir, &["config", "unset", "--all"]).status.success());
+    assert!(!dir.join("rusend/credentials").exists());
+}
+
+#[test]
+fn migrate_converts_a_plain_key_file_and_keeps_a_backup() {
+    let config = tempfile::tempdir().unwrap();
+    let dir = config.path().join("rusend");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("credentials"), "re_legacy\n").unwrap();
+
+    let out = rusend(config.path(), &["config", "migrate"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert_eq!(std::fs::read_to_string(dir.join("credentials.bak")).unwrap(), "re_legacy\n");
+    let saved: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("credentials")).unwrap()).unwrap();
+    assert_eq!(saved["api_key"], "re_legacy");
+
+    let out = rusend(config.path(), &["config", "migrate"]);
+    assert!(String::from_utf8_lossy(&out.stdout).contains("already uses the current layout"));
+    assert!(!dir.join("credentials.bak.1").exists());
+}
+
+#[test]
+fn profiles_keep_their_keys_apart() {
+    let config = tempfile::tempdir().unwrap();
+    let dir = config.path();
+    assert!(rusend(dir, &["config", "-k", "re_default", "--no-validate"]).status.success());
+    let out = rusend(dir, &["config", "--profile", "prod", "-k", "re_prod", "--no-validate"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert_eq!(saved_key(dir), "re_default");
+    let prod = std::fs::read_to_string(dir.join("rusend/credentials.prod")).unwrap();
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&prod).unwrap()["api_key"], "re_prod");
+
+    let version = |args: &[&str]| -> serde_json::Value {
+        let out = rusend(dir, &[args, &["version", "--output", "json"]].concat());
+        serde_json::from_slice(&out.stdout).unwrap()
+    };
+    assert!(version(&["--profile", "prod"])["config_file"].as_str().unwrap().ends_with(".prod"));
+    assert!(version(&[])["config_file"].as_str().unwrap().ends_with("credentials"));
+
+    let out = rusend(dir, &["--profile", "staging", "config", "export", "--out", "x.json"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("rusend config --profile staging"), "stderr: {stderr}");
+    assert!(!rusend(dir, &["--profile", "../up", "version"]).status.success());
+}
+
+/// Like [`rusend`], with `stdin` piped in.
+fn rusend_with_stdin(config: &Path, args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rusend"))
+        .args(args)
+        .env("XDG_CONFIG_HOME", config)
+        .env("RESEND_BASE_URL", "http://127.0.0.1:9")
+        .env("RUST_BACKTRACE", "0")
+        .env_remove("RESEND_API_KEY")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn saved_key(config: &Path) -> serde_json::Value {
+    let saved = std::fs::read_to_string(config.join("rusend/credentials")).unwrap();
+    serde_json::from_str::<serde_json::Value>(&saved).unwrap()["api_key"].clone()
+}
+
+#[test]
+fn from_stdin_saves_the_piped_key() {
+    let config = tempfile::tempdir().unwrap();
+    let from_stdin = |stdin| {
+        rusend_with_stdin(config.path(), &["config", "--from-stdin", "--no-validate"], stdin)
+    };
+    assert!(from_stdin("  re_piped\n").status.success());
+    assert_eq!(saved_key(config.path()), "re_piped");
+
+    let out = from_stdin("");
+    assert!(String::from_utf8_lossy(&out.stderr).contains("--from-stdin read no API key"));
+    let out = from_stdin("re_a\nre_b\n");
+    assert!(!out.status.success());
+}
+
+#[test]
+fn the_key_prompt_reads_piped_input_without_a_terminal() {
+    let config = tempfile::tempdir().unwrap();
+    let out = rusend_with_stdin(config.path(), &["config", "--no-validate"], "re_prompted\n");
+    assert!(out.status.success(), "stderr: {}", String::from_utf8_lossy(&out.stderr));
+    assert_eq!(saved_key(config.path()), "re_prompted");
+}
+
+#[cfg(unix)]
+#[test]
+fn a_readable_credentials_file_is_reported_and_fixed_on_request() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let config = tempfile::tempdir().unwrap();
+    let dir = config.path();
+    assert!(rusend(dir, &["config", "-k", "re_test", "--no-validate"]).status.success());
+    let path = dir.join("rusend/credentials");
+    assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+    let out = rusend(dir, &["templates", "list"]);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("accessible to other users (mode 644)"), "stderr: {stderr}");
+    assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o644);
+
+    let out = rusend(dir, &["--fix-permissions", "templates", "list"]);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Restricted"));
+    assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    let out = rusend(dir, &["templates", "list"]);
+    assert!(!String::from_utf8_lossy(&out.stderr).contains("warning"));
+}
+
+#[cfg(unix)]
+#[test]
+fn an_export_with_the_key_is_readable_by_its_owner_only() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let config = tempfile::tempdir().unwrap();
+    let dir = config.path();
+    assert!(rusend(dir, &["config", "-k", "re_test", "--no-validate"]).status.success());
+    let export = dir.join("export.json");
+    let out =
+        rusend(dir, &["config", "export", "--out", export.to_str().unwrap(), "--include-keys"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert_eq!(std::fs::metadata(&export).unwrap().permissions().mode() & 0o777, 0o600);
+    assert!(std::fs::read_to_string(&export).unwrap().contains("re_test"));
+}
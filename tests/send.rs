@@ -0,0 +1,320 @@
+use std::process::{Command, Output};
+
+/// Runs the binary with an empty config dir and an API endpoint nothing listens on.
+fn rusend(args: &[&str]) -> Output {
+    let config = tempfile::tempdir().unwrap();
+    Command::new(env!("CARGO_BIN_EXE_rusend"))
+        .args(args)
+        .env("XDG_CONFIG_HOME", config.path())
+        .env("RESEND_BASE_URL", "http://127.0.0.1:9")
+        .env("RUST_BACKTRACE", "0")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn send_without_body_is_rejected() {
+    let out = rusend(&["send", "-f", "a@example.com", "-t", "b@example.com", "-s", "hi"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("no body given"), "stderr: {stderr}");
+}
+
+#[test]
+fn allow_empty_body_skips_the_check() {
+    let out = rusend(&[
+        "send",
+        "-f",
+        "a@example.com",
+        "-t",
+        "b@example.com",
+        "-s",
+        "hi",
+        "--allow-empty-body",
+    ]);
+    // Gets as far as the (unreachable) API instead.
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(!stderr.contains("no body given"), "stderr: {stderr}");
+    assert!(stderr.contains("send failed"), "stderr: {stderr}");
+}
+
+#[test]
+fn blank_to_is_rejected_before_any_api_call() {
+    for to in ["", " , ,"] {
+        let out = rusend(&["send", "-f", "a@example.com", "-t", to, "-s", "hi", "--text", "x"]);
+        assert!(!out.status.success());
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(stderr.contains("no valid recipients"), "stderr: {stderr}");
+        assert!(!stderr.contains("send failed"), "stderr: {stderr}");
+    }
+}
+
+#[test]
+fn malformed_base64_bodies_are_rejected() {
+    let out =
+        rusend(&["send", "-f", "a@e.com", "-t", "b@e.com", "-s", "hi", "--html-base64", "no!"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("not valid base64"), "stderr: {stderr}");
+
+    let out =
+        rusend(&["send", "-f", "a@e.com", "-t", "b@e.com", "-s", "hi", "--text-base64", "/w=="]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("not UTF-8"), "stderr: {stderr}");
+}
+
+#[test]
+fn attach_stdin_conflicts_with_a_stdin_body() {
+    let out = rusend(&[
+        "send",
+        "-f",
+        "a@example.com",
+        "-t",
+        "b@example.com",
+        "-s",
+        "hi",
+        "--from-stdin",
+        "--attach-stdin",
+        "report.pdf",
+    ]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("cannot be used with"), "stderr: {stderr}");
+}
+
+#[test]
+fn attach_glob_without_matches_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let pattern = format!("{}/*.pdf", dir.path().display());
+    let out = rusend(&[
+        "send",
+        "-f",
+        "a@example.com",
+        "-t",
+        "b@example.com",
+        "-s",
+        "hi",
+        "--text",
+        "attached",
+        "--attach-glob",
+        &pattern,
+    ]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("matches no files"), "stderr: {stderr}");
+}
+
+#[test]
+fn short_links_warn_or_fail_with_strict() {
+    let mut args = vec![
+        "send",
+        "-f",
+        "a@example.com",
+        "-t",
+        "b@example.com",
+        "-s",
+        "hi",
+        "--html",
+        r#"<a href="https://bit.ly/abc">x</a> <a href="https://example.com/">y</a>"#,
+        "--warn-short-links",
+        "--dry-run",
+    ];
+    let out = rusend(&args);
+    assert!(out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("warning:"), "stderr: {stderr}");
+    assert!(stderr.contains("https://bit.ly/abc"), "stderr: {stderr}");
+    assert!(!stderr.contains("example.com/"), "stderr: {stderr}");
+
+    args.push("--strict");
+    let out = rusend(&args);
+    assert!(!out.status.success());
+    assert!(out.stdout.is_empty());
+}
+
+#[test]
+fn dry_run_prints_the_body_and_verbose_summarizes_it() {
+    let args = [
+        "send", "-f", "a@example.com", "-t", "b@example.com", "-s", "hi", "--text", "hello",
+        "--dry-run",
+    ];
+    // The API is unreachable, so success means nothing was sent.
+    let out = rusend(&args);
+    assert!(out.status.success(), "stderr: {}", String::from_utf8_lossy(&out.stderr));
+    let email: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(email["text"], "hello");
+    assert!(!String::from_utf8_lossy(&out.stderr).contains("Dry run"));
+
+    let out = rusend(&[&["--verbose"], &args[..]].concat());
+    assert!(out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Recipients:      1 (1 To, 0 Cc, 0 Bcc)"), "stderr: {stderr}");
+    assert!(stderr.contains("text only, 5 bytes"), "stderr: {stderr}");
+}
+
+#[test]
+fn strip_tracking_params_cleans_links_in_the_html() {
+    let html = concat!(
+        r#"<a href="https://acme.com/p?id=7&amp;utm_source=mail&amp;utm_medium=x">p</a> "#,
+        r#"<a href='https://acme.com/?fbclid=abc'>home</a> "#,
+        r#"<a href="https://acme.com/plain?id=1">plain</a> "#,
+        r#"<a href="{{link}}?utm_source=mail">tpl</a>"#,
+    );
+    let out = rusend(&[
+        "--verbose", "send", "-f", "a@example.com", "-t", "b@example.com", "-s", "hi", "--html",
+        html, "--strip-tracking-params", "--dry-run",
+    ]);
+    assert!(out.status.success(), "stderr: {}", String::from_utf8_lossy(&out.stderr));
+    let email: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(
+        email["html"],
+        concat!(
+            r#"<a href="https://acme.com/p?id=7">p</a> "#,
+            r#"<a href='https://acme.com/'>home</a> "#,
+            r#"<a href="https://acme.com/plain?id=1">plain</a> "#,
+            r#"<a href="{{link}}?utm_source=mail">tpl</a>"#,
+        )
+    );
+    assert!(String::from_utf8_lossy(&out.stderr).contains("cleaned 2 link(s)"));
+}
+
+#[test]
+fn network_only_retries_connection_failures_quickly() {
+    let out = rusend(&[
+        "--retry-policy", "network-only", "send", "-f", "a@example.com", "-t", "b@example.com",
+        "-s", "hi", "--text", "x",
+    ]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("retrying in 250ms (attempt 2 of 3)"), "stderr: {stderr}");
+    assert!(stderr.contains("retrying in 500ms (attempt 3 of 3)"), "stderr: {stderr}");
+}
+
+#[test]
+fn json_input_reports_every_field_error_with_its_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("message.json");
+    let message = r#"{
+        "from": "a@example.com", "to": ["b@example.com", "nope"], "subject": 7,
+        "tags": [{"name": "kind"}], "attachments": [{"content": "%%"}], "colour": "red"
+    }"#;
+    std::fs::write(&path, message).unwrap();
+    let out = rusend(&["send", "--json-input", path.to_str().unwrap()]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    for problem in [
+        "colour: unknown field",
+        "to[1]: invalid address \"nope\"",
+        "subject: expected a string, got a number",
+        "html, text: at least one body is required",
+        "tags[0].value: missing",
+        "attachments[0].filename: required with content",
+        "attachments[0].content: not valid base64",
+    ] {
+        assert!(stderr.contains(problem), "missing {problem:?} in: {stderr}");
+    }
+}
+
+#[test]
+fn json_input_takes_the_options_that_adjust_the_message() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("message.json");
+    let message = r#"{
+        "from": "a@example.com", "to": ["b@example.com", "B@example.com"],
+        "subject": "hi", "text": "hello", "headers": {"X-Campaign": "spring"}
+    }"#;
+    std::fs::write(&path, message).unwrap();
+    let send = ["send", "--json-input", path.to_str().unwrap()];
+    let out = rusend(&[
+        &send[..],
+        &["--dedupe", "--bcc-self", "--subject-prefix", "[x]", "--header", "X-Campaign: fall"],
+        &["--dry-run"],
+    ]
+    .concat());
+    assert!(out.status.success(), "stderr: {}", String::from_utf8_lossy(&out.stderr));
+    let email: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(email["to"], serde_json::json!(["b@example.com"]));
+    assert_eq!(email["bcc"], serde_json::json!(["a@example.com"]));
+    assert_eq!(email["subject"], "[x] hi");
+    assert_eq!(email["headers"]["X-Campaign"], "fall");
+
+    let out = rusend(&[&send[..], &["--max-recipients", "1", "--dry-run"]].concat());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("over the limit of 1"));
+    let out = rusend(&[&send[..], &["--strip-tracking-params"]].concat());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("cannot be used with"));
+}
+
+#[test]
+fn individual_sends_each_always_bcc_address_one_copy() {
+    let dir = tempfile::tempdir().unwrap();
+    let env = dir.path().join(".env");
+    std::fs::write(&env, "RUSEND_ALWAYS_BCC=audit@example.com\n").unwrap();
+    let send = [
+        "--env-file", env.to_str().unwrap(), "send", "-f", "a@example.com",
+        "-t", "u1@example.com,u2@example.com,u3@example.com", "-s", "hi", "--text", "x",
+        "--individual",
+    ];
+    let out = rusend(&[&send[..], &["--dry-run"]].concat());
+    assert!(out.status.success(), "stderr: {}", String::from_utf8_lossy(&out.stderr));
+    let emails: Vec<serde_json::Value> = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(emails.len(), 3);
+    let copies = emails.iter().filter(|e| e["bcc"] == serde_json::json!(["audit@example.com"]));
+    assert_eq!(copies.count(), 1);
+
+    let out = rusend(&[&send[..], &["--cc", "boss@example.com"]].concat());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("cannot be used with"));
+}
+
+#[test]
+fn mask_recipients_hides_addresses_in_the_dry_run_unless_verbose() {
+    let send = [
+        "--mask-recipients", "send", "-f", "a@example.com", "-t", "Jane Doe <jane@example.com>",
+        "--cc", "bob@example.org", "-s", "hi", "--text", "x", "--dry-run",
+    ];
+    let out = rusend(&send);
+    assert!(out.status.success(), "stderr: {}", String::from_utf8_lossy(&out.stderr));
+    let email: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(email["to"], serde_json::json!(["J*** <j***@example.com>"]));
+    assert_eq!(email["cc"], serde_json::json!(["b***@example.org"]));
+    assert_eq!(email["from"], "a@example.com");
+
+    let out = rusend(&[&["--verbose"], &send[..]].concat());
+    let email: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(email["to"], serde_json::json!(["Jane Doe <jane@example.com>"]));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Cc:              bob@example.org"), "stderr: {stderr}");
+}
+
+#[test]
+fn inline_images_count_toward_the_attachment_limit() {
+    let dir = tempfile::tempdir().unwrap();
+    let (report, logo) = (dir.path().join("report.pdf"), dir.path().join("logo.png"));
+    std::fs::File::create(&report).unwrap().set_len(30 << 20).unwrap();
+    std::fs::File::create(&logo).unwrap().set_len(15 << 20).unwrap();
+    let html = format!(r#"<img src="{}">"#, logo.display());
+    let out = rusend(&[
+        "send", "-f", "a@example.com", "-t", "b@example.com", "-s", "hi", "--html", &html,
+        "--attach", report.to_str().unwrap(), "--attach-inline-from-html", "--dry-run",
+    ]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("over the 40 MB limit"), "stderr: {stderr}");
+}
+
+#[cfg(unix)]
+#[test]
+fn attachments_dir_doesnt_follow_a_symlink_to_an_ancestor() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    std::fs::write(dir.path().join("sub/a.txt"), "a").unwrap();
+    std::os::unix::fs::symlink(dir.path(), dir.path().join("sub/loop")).unwrap();
+    let out = rusend(&[
+        "send", "-f", "a@example.com", "-t", "b@example.com", "-s", "hi", "--text", "x",
+        "--attachments-dir", dir.path().to_str().unwrap(), "--recursive", "--dry-run",
+    ]);
+    assert!(out.status.success(), "stderr: {}", String::from_utf8_lossy(&out.stderr));
+    let email: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(email["attachments"].as_array().unwrap().len(), 1, "email: {email}");
+}